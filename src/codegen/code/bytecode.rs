@@ -13,4 +13,68 @@ pub enum Instruction {
     // FFI to Rust
     RustCallMut { rust_fn: usize, arg: Register, out: Register},
     RustCallRef { rust_fn: usize, arg: Register},
-}
\ No newline at end of file
+
+    // constants (strings live in the program's string table)
+    LoadInteger { value: i64, out: Register },
+    LoadString { string: usize, out: Register },
+
+    // data movement: clones if the type isn't Copy
+    Copy { from: Register, out: Register },
+
+    // arithmetic
+    IntegerUOp { op: UOp, arg: Register, out: Register },
+    IntegerBinOp { arg1: Register, op: BinOp, arg2: Register, out: Register },
+    StringConcat { arg1: Register, arg2: Register, out: Register },
+
+    // logic (and and or are jumps)
+    Compare { arg1: Register, op: Comparison, arg2: Register, out: Register },
+    BoolNot { arg: Register, out: Register },
+
+    // builtins
+    Print { arg: Register },
+
+    // control flow
+    Jump { to: usize },
+    Call { procedure: usize, args: usize, out: usize },  // args and out are rows
+    Return { values: usize },  // values is a row, copied into the caller's out
+    JumpUnlessEqual { arg1: Register, arg2: Register, to: usize },
+    JumpIf { arg: Register, to: usize },
+    JumpUnless { arg: Register, to: usize },
+
+    // queries (and calls): row is an index into the procedure's row table, with one register per column
+    // the bits of bound say which of those are inputs: the rest get filled in by Next
+    Open { iter: usize, source: Source, row: usize, bound: u32 },
+    Next { iter: usize, row: usize, bound: u32, done: usize },  // jumps to done when there are no more rows
+    Close { iter: usize },  // for leaving a loop before it's done
+    Emit { row: usize },  // adds a row to the result of the view being evaluated
+    Accumulate { aggregate: usize, row: usize },  // adds a row to what an aggregate has collected so far
+
+    // writes to a table: row is the whole row, or just the key for an expunge
+    Write { write: Write, table: usize, row: usize },
+    HoldWrites,  // until the matching release, writes wait (for a for: nothing should change under it)
+    ReleaseWrites,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Source {
+    Table(usize),  // index into the program's tables
+    View(usize),  // index of the procedure that evaluates the view
+    Full(usize),  // a view in the component being evaluated: every row found so far
+    Delta(usize),  // the same, but only the rows that were new last round
+    Aggregate(usize),  // index into the procedure's aggregates: one row per group, and it starts over
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum UOp { Negate }
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Aggregate { Count, Sum, Min, Max, Collect }
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Write { Insert, Remove, Expunge }
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum BinOp { Add, Subtract, Multiply, Divide }
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Comparison { Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual }
@@ -0,0 +1,67 @@
+use crate::frontend::Located;
+
+use super::Register;
+
+// where a procedure's code came from, for errors and the debugger
+#[derive(Debug, Default)]
+pub struct DebugInfo {
+    pub spans: Vec<Located<()>>,  // per instruction: the statement or query goal it came from
+    pub variables: Vec<Option<String>>,  // per local: the variable it holds, or None for a temporary
+}
+
+impl DebugInfo {
+    pub fn span(&self, ip: usize) -> Option<Located<()>> {
+        self.spans.get(ip).copied()
+    }
+
+    // args are always variables, so this only answers for locals
+    pub fn variable(&self, register: Register) -> Option<&str> {
+        match register {
+            Register::Arg(_) => None,
+            Register::Local(l) => self.variables.get(l)?.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::SourceFile;
+    use crate::runtime::{UntaggedValue, VM};
+    use crate::testing::compile;
+
+    const SOURCE: &str = "def f(@n Integer) Integer {\n    @m := @n - 1\n    return 10 / @m\n}\n\
+        def main() {\n    @x := 1 + 2 * 3\n    print(f(1))\n}\n";
+
+    #[test]
+    fn every_instruction_has_a_span() {
+        let file = SourceFile::new("test.kupo", SOURCE);
+        let program = compile(SOURCE).unwrap();
+        let f = &program.procedures[program.procedure_named("f").unwrap()];
+        assert_eq!(f.debug.spans.len(), f.code.instructions.len());
+        let lines: Vec<_> = f.debug.spans.iter().map(|s| file.line_col(s.start).0).collect();
+        assert!(lines.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!((lines[0], lines[lines.len() - 1]), (2, 3));
+    }
+
+    #[test]
+    fn variable_names() {
+        let program = compile(SOURCE).unwrap();
+        let main = &program.procedures[program.procedure_named("main").unwrap()];
+        assert_eq!(main.debug.variables.len(), main.locals.fields.len());
+        let named: Vec<_> = main.debug.variables.iter().flatten().collect();
+        assert_eq!(named, vec!["@x"]);
+        // the rest are temporaries
+        assert!(main.debug.variables.len() > 1);
+    }
+
+    #[test]
+    fn traces() {
+        let file = SourceFile::new("test.kupo", SOURCE);
+        let program = compile(SOURCE).unwrap();
+        let main = program.procedure_named("main").unwrap();
+        let args = UntaggedValue::instantiate(&program.procedures[main].args);
+        let error = VM::new(program).call(main, args).unwrap_err();
+        let trace: Vec<_> = error.trace.iter().map(|t| (t.procedure.as_str(), file.line_col(t.location.unwrap().start).0)).collect();
+        assert_eq!(trace, vec![("f", 3), ("main", 7)]);
+    }
+}
@@ -1,19 +1,66 @@
 mod bytecode;
+mod debug_info;
 mod structure;
 
-pub use bytecode::{Bytecode, Instruction, Register};
-pub use structure::{Struct, StructBuilder, TypeData};
+pub use bytecode::{Aggregate, BinOp, Bytecode, Comparison, Instruction, Register, Source, UOp, Write};
+pub use debug_info::DebugInfo;
+pub use structure::{DebugUnknown, Struct, StructBuilder, TypeData};
 
-use crate::runtime::{dynamism::{MutToUnknown, RefToUnknown}};
+use crate::runtime::{KupoRuntimeError, Pinned, dynamism::{MutToUnknown, RefToUnknown}};
 
 pub struct Program {
     pub(crate) procedures: Vec<Procedure>,
+    pub(crate) strings: Vec<String>,
+    pub(crate) tables: Vec<Pinned>,
     pub(crate) ffi_ref: Vec<fn(RefToUnknown)>,
     pub(crate) ffi_mut: Vec<fn(RefToUnknown, MutToUnknown)>,
+    pub(crate) components: Vec<Component>,
 }
 
 pub struct Procedure {
+    pub(crate) name: String,
     pub(crate) args: Struct,
     pub(crate) locals: Struct,
+    pub(crate) returns: Vec<TypeData>,
     pub(crate) code: Bytecode,
+    pub(crate) debug: DebugInfo,
+    pub(crate) rows: Vec<Vec<Register>>,
+    pub(crate) iterators: usize,
+    pub(crate) emits: Option<Vec<TypeData>>,  // for views: the columns of each row
+    pub(crate) aggregates: Vec<Aggregation>,
+}
+
+// rows are groups (whatever else the body binds), the value's own variables, then the value:
+// so equal values only count twice if they came from different variables
+pub struct Aggregation {
+    pub(crate) op: Aggregate,
+    pub(crate) columns: Vec<TypeData>,
+    pub(crate) groups: usize,
+    pub(crate) result: TypeData,
+}
+
+// views that use each other: each view's procedure has the clauses that don't, its delta procedure the rest
+pub struct Component {
+    pub(crate) views: Vec<usize>,
+    pub(crate) deltas: Vec<usize>,  // one per view
+}
+
+impl Program {
+    pub fn procedure_named(&self, name: &str) -> Option<usize> {
+        self.procedures.iter().position(|p| p.name == name)
+    }
+
+    // fails if the host has swapped any table this was compiled against
+    pub fn check_tables(&self) -> Result<(), KupoRuntimeError> {
+        self.tables.iter().try_for_each(|t| t.relation().map(|_| ()))
+    }
+}
+
+impl Procedure {
+    pub fn type_of(&self, register: Register) -> &TypeData {
+        match register {
+            Register::Arg(a) => &self.args.fields[a].type_data,
+            Register::Local(l) => &self.locals.fields[l].type_data,
+        }
+    }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use crate::runtime::dynamism::*;
-use std::{alloc::Layout, any::{Any, TypeId}, fmt};
+use std::{alloc::Layout, any::{Any, TypeId, type_name}, cmp::Ordering, fmt};
 
 // TODO: Track Clone, Debug, and Drop status of types
 // Also TODO: Structs should really be made of Structs, not of Ts
@@ -11,40 +11,63 @@ pub struct Field {
     pub type_data: TypeData,
 }
 
+#[derive(Clone, Copy)]
 pub struct TypeData {
     pub rust_type: TypeId,
+    pub rust_name: &'static str,  // for messages: TypeIds can't say what they are
     pub layout: Layout,
     pub clone_callback: Option<fn(RefToUnknown<'_>, MutToUnknown<'_>)>,
     pub debug_callback: fn(RefToUnknown<'_>, &mut fmt::Formatter<'_>),
     pub drop_callback: Option<fn(MutToUnknown<'_>)>,
+    pub cmp_callback: Option<fn(RefToUnknown<'_>, RefToUnknown<'_>) -> Ordering>,
 }
 
 impl std::fmt::Debug for TypeData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TypeData")
-        .field("rust_type", &self.rust_type)
+        .field("rust_type", &self.rust_name)
         .field("layout", &self.layout)
         .field("is_copy", &self.is_copy())
         .field("drop_callback", &self.drop_callback.is_some())
+        .field("cmp_callback", &self.cmp_callback.is_some())
         .finish()
     }
 }
 
+// same Rust type, same kupo type
+impl PartialEq for TypeData {
+    fn eq(&self, other: &TypeData) -> bool {
+        self.rust_type == other.rust_type
+    }
+}
+
+impl Eq for TypeData {}
+
 impl TypeData {
     pub fn is_copy(&self) -> bool {
         self.clone_callback.is_none()
     }
 
+    pub fn is_ordered(&self) -> bool {
+        self.cmp_callback.is_some()
+    }
+
+    pub fn debug<'a>(&'a self, value: RefToUnknown<'a>) -> DebugUnknown<'a> {
+        DebugUnknown { type_data: self, value }
+    }
+
     pub fn new_copy<T: Any+Copy>(
         debug_callback: fn(RefToUnknown<'_>, &mut fmt::Formatter<'_>),
     ) -> TypeData {
         let rust_type = TypeId::of::<T>();
         TypeData {
             rust_type, 
+            rust_name: type_name::<T>(),
             layout: Layout::new::<InPlace<T>>(),
             clone_callback: None,
             debug_callback: debug_callback,
             drop_callback: None,
+            cmp_callback: None,
         }
     }
     pub fn new_clone<T: Any>(
@@ -55,14 +78,54 @@ impl TypeData {
         let rust_type = TypeId::of::<T>();
         TypeData {
             rust_type, 
+            rust_name: type_name::<T>(),
             layout: Layout::new::<InPlace<T>>(),
             clone_callback: Some(clone_callback),
             debug_callback: debug_callback,
             drop_callback: drop_callback,
+            cmp_callback: None,
+        }
+    }
+
+    // only ordered types can be compared or deduped
+    pub fn ordered<T: Any+Ord>(mut self) -> TypeData {
+        assert_eq!(self.rust_type, TypeId::of::<T>());
+        self.cmp_callback = Some(compare_via::<T>);
+        self
+    }
+
+    // into must be uninitialized
+    pub fn clone_into(&self, from: RefToUnknown<'_>, mut into: MutToUnknown<'_>) {
+        match self.clone_callback {
+            None => into.copy_from(from),
+            Some(clone) => clone(from, into),
+        }
+    }
+
+    pub fn compare(&self, a: RefToUnknown<'_>, b: RefToUnknown<'_>) -> Ordering {
+        match self.cmp_callback {
+            Some(cmp) => cmp(a, b),
+            None => panic!("type can't be compared (codegen should have caught this)"),
         }
     }
 }
 
+fn compare_via<T: Any+Ord>(a: RefToUnknown<'_>, b: RefToUnknown<'_>) -> Ordering {
+    a.cast::<T>().get().cmp(&b.cast::<T>().get())
+}
+
+pub struct DebugUnknown<'a> {
+    type_data: &'a TypeData,
+    value: RefToUnknown<'a>,
+}
+
+impl<'a> fmt::Debug for DebugUnknown<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.type_data.debug_callback)(self.value, f);
+        Ok(())
+    }
+}
+
 pub struct StructBuilder {
     // Type ID, offset, layout
     pub fields: Vec<Field>,
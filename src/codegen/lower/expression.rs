@@ -0,0 +1,207 @@
+use crate::frontend::{Located, ast};
+use crate::semantics::HostFunctionKind;
+
+use super::{FFIRef, ProcedureLowerer};
+use super::super::code::*;
+
+// None means there's no value, or an error was already reported
+type Lowered = Option<Register>;
+
+// None means an error was already reported
+type LoweredValues = Option<Vec<Register>>;
+
+impl<'m, 'e> ProcedureLowerer<'m, 'e> {
+    pub(super) fn lower_expression(&mut self, expr: &Located<ast::Expression>) -> Lowered {
+        match &expr.value {
+            ast::Expression::StringLiteral { it } => {
+                let string = self.module.intern(it);
+                let out = self.temporary(self.builtin_type("String"));
+                self.emit(Instruction::LoadString { string, out });
+                Some(out)
+            }
+            ast::Expression::IntegerLiteral { it } => {
+                let out = self.temporary(self.builtin_type("Integer"));
+                self.emit(Instruction::LoadInteger { value: *it as i64, out });
+                Some(out)
+            }
+            ast::Expression::Variable { name } => {
+                Some(self.lookup(name))
+            }
+            ast::Expression::Call { call } => {
+                self.lower_call(call)?.first().copied()
+            }
+            ast::Expression::UOp { op, arg } => {
+                let arg = self.lower_expression(arg)?;
+                match op {
+                    ast::UOp::Plus => Some(arg),
+                    ast::UOp::Negate => {
+                        let out = self.temporary(self.builtin_type("Integer"));
+                        self.emit(Instruction::IntegerUOp { op: UOp::Negate, arg, out });
+                        Some(out)
+                    }
+                    ast::UOp::Not => {
+                        let out = self.temporary(self.builtin_type("Bool"));
+                        self.emit(Instruction::BoolNot { arg, out });
+                        Some(out)
+                    }
+                }
+            }
+            ast::Expression::BinOp { arg1, op: op @ (ast::BinOp::And | ast::BinOp::Or), arg2 } => {
+                self.lower_short_circuit(arg1, op, arg2)
+            }
+            ast::Expression::BinOp { arg1, op, arg2 } => {
+                let arg1 = self.lower_expression(arg1);
+                let arg2 = self.lower_expression(arg2);
+                let (arg1, arg2) = (arg1?, arg2?);
+
+                let out = self.temporary(self.module.typing.single(expr));
+                if let Some(op) = Self::comparison(op) {
+                    self.emit(Instruction::Compare { arg1, op, arg2, out });
+                    return Some(out)
+                }
+                if self.type_of(out) == self.builtin_type("String") {
+                    self.emit(Instruction::StringConcat { arg1, arg2, out });
+                    return Some(out)
+                }
+
+                let op = match op {
+                    ast::BinOp::Add => BinOp::Add,
+                    ast::BinOp::Subtract => BinOp::Subtract,
+                    ast::BinOp::Multiply => BinOp::Multiply,
+                    ast::BinOp::Divide => BinOp::Divide,
+                    _ => unreachable!(),
+                };
+                self.emit(Instruction::IntegerBinOp { arg1, op, arg2, out });
+                Some(out)
+            }
+        }
+    }
+
+    fn comparison(op: &ast::BinOp) -> Option<Comparison> {
+        Some(match op {
+            ast::BinOp::Equal => Comparison::Equal,
+            ast::BinOp::NotEqual => Comparison::NotEqual,
+            ast::BinOp::Less => Comparison::Less,
+            ast::BinOp::LessEqual => Comparison::LessEqual,
+            ast::BinOp::Greater => Comparison::Greater,
+            ast::BinOp::GreaterEqual => Comparison::GreaterEqual,
+            _ => return None,
+        })
+    }
+
+    // arg2 only runs if arg1 didn't decide it
+    //
+    //     Copy arg1 -> out
+    //     JumpUnless out E  (JumpIf for or)
+    //     Copy arg2 -> out
+    //     E:
+    fn lower_short_circuit(&mut self, arg1: &Located<ast::Expression>, op: &ast::BinOp, arg2: &Located<ast::Expression>) -> Lowered {
+        let out = self.temporary(self.builtin_type("Bool"));
+        let arg1 = self.lower_expression(arg1)?;
+        self.emit(Instruction::Copy { from: arg1, out });
+        let jump = self.instructions.len();
+        self.emit(Instruction::Jump { to: usize::MAX });
+
+        let arg2 = self.lower_expression(arg2);
+        if let Some(arg2) = arg2 {
+            self.emit(Instruction::Copy { from: arg2, out });
+        }
+
+        let to = self.instructions.len();
+        self.instructions[jump] = match op {
+            ast::BinOp::And => Instruction::JumpUnless { arg: out, to },
+            _ => Instruction::JumpIf { arg: out, to },
+        };
+        arg2.map(|_| out)
+    }
+
+    // like lower_expression, but a call can produce any number of values
+    pub(super) fn lower_multi_expression(&mut self, expr: &Located<ast::Expression>) -> LoweredValues {
+        match &expr.value {
+            ast::Expression::Call { call } => self.lower_call(call),
+            _ => self.lower_expression(expr).map(|v| vec![v]),
+        }
+    }
+
+    // a single expression can produce any number of values: otherwise each one produces one
+    pub(super) fn lower_values(&mut self, values: &Located<ast::AssignTarget>) -> LoweredValues {
+        let args = &values.value.args;
+        if let [arg] = &args[..] {
+            return self.lower_multi_expression(arg)
+        }
+        let registers: Vec<Lowered> = args.iter().map(|a| self.lower_expression(a)).collect();
+        registers.into_iter().collect()
+    }
+
+    pub(super) fn lower_call(&mut self, call: &Located<ast::Call>) -> LoweredValues {
+        let name = &call.value.name;
+        let mut args = vec![];
+        for arg in &call.value.args {
+            args.push(self.lower_expression(arg));
+        }
+        let args: Vec<Register> = args.into_iter().collect::<Option<_>>()?;
+
+        if name.value == "print" {
+            self.emit(Instruction::Print { arg: args[0] });
+            return Some(vec![])
+        }
+
+        let (ix, function) = if let Some(f) = self.module.env.function_named(&name.value) { f } else {
+            return Some(self.lower_procedure_call(&name.value, args))
+        };
+
+        let arg = args[0];
+        match (self.module.ffi[ix], function.kind) {
+            (FFIRef::Ref(rust_fn), _) => {
+                self.emit(Instruction::RustCallRef { rust_fn, arg });
+                Some(vec![])
+            }
+            (FFIRef::Mut(rust_fn), HostFunctionKind::Mut { returns, .. }) => {
+                let out = self.temporary(returns);
+                self.emit(Instruction::RustCallMut { rust_fn, arg, out });
+                Some(vec![out])
+            }
+            (FFIRef::Mut(_), _) => unreachable!(),
+        }
+    }
+
+    fn lower_procedure_call(&mut self, name: &str, args: Vec<Register>) -> Vec<Register> {
+        let procedure = self.module.procedure_index(name);
+        let returns = self.module.typing.signature(name).unwrap().returns.clone();
+        let out: Vec<Register> = returns.into_iter().map(|t| self.temporary(t)).collect();
+
+        let args = self.row(args);
+        let out_row = self.row(out.clone());
+        self.emit(Instruction::Call { procedure, args, out: out_row });
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{compile, run};
+
+    #[test]
+    fn filters() {
+        let source = "view [@s String] in v { @x in vampire, @s := true_name(@x), @s != \"Orlok\", @s < \"V\" }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[\"Carmilla\"]", "[\"Dracula\"]"]);
+        let source = "view [@s String] in v { @x in lonely, @s := true_name(@x), @s == \"Orlok\" or not (@s < \"C\") and @s <= \"E\" }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[\"Dracula\"]", "[\"Orlok\"]"]);
+        let source = "view [@x NPC, @n Integer] in v { @x in vampire, @n := count(@y : [@x, @y] in sired), @n > 0, 2 >= @n }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[NPC(0), 2]", "[NPC(3), 1]"]);
+    }
+
+    #[test]
+    fn heads() {
+        let source = "def f() Integer {\n    @n := 0\n    for @x in vampire, true_name(@x) >= \"D\" { @n = @n + 1 }\n    if @n == 3 { return @n * 10 }\n    return @n\n}\n\
+            view [@n Integer] in v { @n := f() }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[30]"]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(compile("view [@x NPC] in v { @x in vampire, 1 + 2 }").err(), Some(vec!["C0022"]));
+        assert_eq!(compile("view [@x NPC] in v { @x in vampire, @x < \"a\" }").err(), Some(vec!["C0034"]));
+        assert_eq!(compile("view [@x NPC] in v { @x in vampire, 1 and 2 == 2 }").err(), Some(vec!["C0034"]));
+    }
+}
@@ -0,0 +1,450 @@
+mod expression;
+mod query;
+mod statement;
+
+use crate::frontend::{Located, ast};
+use crate::semantics::{Environment, Errors, HostFunctionKind, Typing, kce};
+
+use super::code::*;
+
+// one procedure per def and per view (plus a delta procedure per recursive view).
+// everything's been resolved and type checked already
+pub fn lower_module(module: &ast::Module, env: &Environment, typing: &Typing) -> Result<Program, Errors> {
+    let mut lowerer = ModuleLowerer::new(env, typing);
+    lowerer.lower_items(module, None);
+    lowerer.finish()
+}
+
+// What the REPL wants done with its def
+#[derive(Clone, Copy)]
+pub enum Scratch {
+    Statements,  // run the body, then emit one row of every variable still in scope
+    Solutions,  // the body is a for with one clause and nothing in it: emit the variables it binds, once per solution
+}
+
+// for the REPL's wrapper def: lowered like a view, so its variables come back as rows (named by the Vec)
+pub fn lower_scratch(module: &ast::Module, env: &Environment, typing: &Typing, name: &str, scratch: Scratch) -> Result<(Program, Vec<String>), Errors> {
+    let mut lowerer = ModuleLowerer::new(env, typing);
+    let columns = lowerer.lower_items(module, Some((name, scratch)));
+    lowerer.finish().map(|program| (program, columns))
+}
+
+#[derive(Clone, Copy)]
+enum FFIRef {
+    Ref(usize),
+    Mut(usize),
+}
+
+struct ModuleLowerer<'e> {
+    env: &'e Environment,
+    typing: &'e Typing,
+    ffi: Vec<FFIRef>,  // one per host function in env
+    items: Vec<String>,  // one per procedure
+    tables: Vec<String>,  // one per table in program.tables
+    program: Program,
+    errors: Errors,
+}
+
+impl<'e> ModuleLowerer<'e> {
+    fn new(env: &'e Environment, typing: &'e Typing) -> Self {
+        let mut program = Program { procedures: vec![], strings: vec![], tables: vec![], ffi_ref: vec![], ffi_mut: vec![], components: vec![] };
+        let mut ffi = vec![];
+        for f in env.functions() {
+            match f.kind {
+                HostFunctionKind::Ref { rust_fn } => {
+                    ffi.push(FFIRef::Ref(program.ffi_ref.len()));
+                    program.ffi_ref.push(rust_fn);
+                }
+                HostFunctionKind::Mut { rust_fn, .. } => {
+                    ffi.push(FFIRef::Mut(program.ffi_mut.len()));
+                    program.ffi_mut.push(rust_fn);
+                }
+            }
+        }
+        ModuleLowerer { env, typing, ffi, items: vec![], tables: vec![], program, errors: vec![] }
+    }
+
+    fn lower_items(&mut self, module: &ast::Module, scratch: Option<(&str, Scratch)>) -> Vec<String> {
+        // procedures are numbered in the order they're written, so views can be referred to before they're lowered
+        for item in &module.items {
+            self.items.push(match &item.value {
+                ast::Item::Def(d) => d.name.value.clone(),
+                ast::Item::View(v) => v.name.value.clone(),
+            });
+        }
+        let mut columns = vec![];
+        for item in &module.items {
+            match (&item.value, &scratch) {
+                (ast::Item::Def(d), Some((name, scratch))) if d.name.value == *name => columns = self.lower_scratch(d, scratch),
+                (ast::Item::Def(d), _) => self.lower_def(d),
+                (ast::Item::View(v), _) => self.lower_view(v),
+            }
+        }
+
+        let typing = self.typing;
+        for component in typing.components() {
+            let mut views = vec![];
+            let mut deltas = vec![];
+            for name in component {
+                let view = match &module.item(name).unwrap().value { ast::Item::View(v) => v, _ => unreachable!() };
+                views.push(self.procedure_index(name));
+                deltas.push(self.program.procedures.len());
+                self.lower_delta(view, component);
+            }
+            self.program.components.push(Component { views, deltas });
+        }
+        columns
+    }
+
+    fn finish(self) -> Result<Program, Errors> {
+        if self.errors.is_empty() { Ok(self.program) } else { Err(self.errors) }
+    }
+
+    fn error<T>(&mut self, loc: &Located<T>, code: &'static str, s: &str) {
+        self.errors.push(loc.replace(kce(code, s)))
+    }
+
+    fn intern(&mut self, s: &str) -> usize {
+        if let Some(ix) = self.program.strings.iter().position(|s2| s2 == s) {
+            return ix
+        }
+        self.program.strings.push(s.to_string());
+        self.program.strings.len() - 1
+    }
+
+    fn procedure_index(&self, name: &str) -> usize {
+        self.items.iter().position(|i| i == name).unwrap()
+    }
+
+    // where the rows of a table or view come from, and the type of each column
+    fn source(&mut self, name: &str) -> (Source, Vec<TypeData>) {
+        if let Some(table) = self.env.table_named(name) {
+            let ix = match self.tables.iter().position(|t| t == name) {
+                Some(ix) => ix,
+                None => {
+                    self.tables.push(name.to_string());
+                    self.program.tables.push(table.pin());
+                    self.tables.len() - 1
+                }
+            };
+            return (Source::Table(ix), table.columns.clone())
+        }
+        let columns = self.typing.signature(name).unwrap().args.clone();
+        (Source::View(self.procedure_index(name)), columns)
+    }
+
+    fn lower_def(&mut self, def: &ast::Def) {
+        let signature = self.typing.signature(&def.name.value).unwrap().clone();
+
+        let mut lowerer = ProcedureLowerer::new(self, def.name.location());
+        lowerer.bind_args(def, &signature.args);
+        lowerer.lower_block(&def.body);
+
+        let procedure = lowerer.finish(def.name.value.clone(), signature.returns, None);
+        self.program.procedures.push(procedure);
+    }
+
+    fn lower_scratch(&mut self, def: &ast::Def, scratch: &Scratch) -> Vec<String> {
+        let signature = self.typing.signature(&def.name.value).unwrap().clone();
+
+        let mut lowerer = ProcedureLowerer::new(self, def.name.location());
+        lowerer.bind_args(def, &signature.args);
+        let mut columns = (vec![], vec![]);
+        match scratch {
+            Scratch::Statements => {
+                // no block scope, so whatever gets introduced is still around at the end
+                for statement in &def.body.value.items {
+                    lowerer.lower_statement(statement);
+                }
+                columns = lowerer.emit_variables(0);
+            }
+            Scratch::Solutions => {
+                let query = match def.body.value.items.as_slice() {
+                    [Located { value: ast::Statement::For { clauses, .. }, .. }] if clauses.len() == 1 => &clauses[0],
+                    _ => panic!("scratch solutions need a lone for with one clause"),
+                };
+                lowerer.lower_query(query, &mut |s, _| columns = s.emit_variables(1));
+            }
+        }
+
+        let (names, types) = columns;
+        let procedure = lowerer.finish(def.name.value.clone(), vec![], Some(types));
+        self.program.procedures.push(procedure);
+        names
+    }
+
+    // each clause runs in turn, emitting a row per solution: that's the union of the clauses
+    fn lower_view(&mut self, view: &ast::View) {
+        let signature = self.typing.signature(&view.name.value).unwrap().clone();
+        let component = self.typing.component(&view.name.value);
+        if component.is_some() && !signature.args.iter().all(|t| t.is_ordered()) {
+            // otherwise there's no telling when we've stopped finding new rows
+            self.error(&view.name, "C0040", &format!("{} is recursive, so every column has to be comparable", view.name.value));
+        }
+
+        let mut lowerer = ProcedureLowerer::new(self, view.name.location());
+        for clause in &view.clauses {
+            // the clauses that use the component are in the delta procedure
+            if component.is_some_and(|c| !recursive_uses(clause, c).is_empty()) { continue }
+            lowerer.lower_clause(view, clause);
+        }
+
+        let procedure = lowerer.finish(view.name.value.clone(), vec![], Some(signature.args));
+        self.program.procedures.push(procedure);
+    }
+
+    // each clause once per use of the component, with that use reading last round's new rows
+    fn lower_delta(&mut self, view: &ast::View, component: &[String]) {
+        let signature = self.typing.signature(&view.name.value).unwrap().clone();
+
+        let mut lowerer = ProcedureLowerer::new(self, view.name.location());
+        for clause in &view.clauses {
+            for delta in recursive_uses(clause, component) {
+                lowerer.fixpoint = Some(Fixpoint { component: component.to_vec(), delta });
+                lowerer.lower_clause(view, clause);
+            }
+        }
+
+        let procedure = lowerer.finish(view.name.value.clone(), vec![], Some(signature.args));
+        self.program.procedures.push(procedure);
+    }
+}
+
+// where a query uses views from the component, by the span of each name
+fn recursive_uses(query: &Located<ast::QueryExpression>, component: &[String]) -> Vec<Located<()>> {
+    let mut uses = vec![];
+    for goal in &query.value.items {
+        match &goal.value {
+            ast::QueryGoal::In { from, .. } if component.contains(&from.value) => uses.push(from.location()),
+            ast::QueryGoal::Disjunction { clauses } => for clause in clauses { uses.extend(recursive_uses(clause, component)) },
+            // stratification keeps the component out of nots
+            _ => {}
+        }
+    }
+    uses
+}
+
+// lowering one round of a recursive view
+struct Fixpoint {
+    component: Vec<String>,
+    delta: Located<()>,  // the use that reads last round's rows
+}
+
+struct Variable {
+    name: String,
+    register: Register,
+}
+
+struct ProcedureLowerer<'m, 'e> {
+    module: &'m mut ModuleLowerer<'e>,
+    args: StructBuilder,
+    locals: StructBuilder,
+    scopes: Vec<Vec<Variable>>,
+    instructions: Vec<Instruction>,
+    span: Located<()>,  // where the instructions being emitted right now came from
+    debug: DebugInfo,
+    rows: Vec<Vec<Register>>,
+    iterators: usize,
+    aggregates: Vec<Aggregation>,
+    fixpoint: Option<Fixpoint>,
+}
+
+impl<'m, 'e> ProcedureLowerer<'m, 'e> {
+    fn new(module: &'m mut ModuleLowerer<'e>, span: Located<()>) -> Self {
+        ProcedureLowerer { 
+            module, 
+            args: StructBuilder::new(),
+            locals: StructBuilder::new(),
+            scopes: vec![vec![]],
+            instructions: vec![],
+            span,
+            debug: DebugInfo::default(),
+            rows: vec![],
+            iterators: 0,
+            aggregates: vec![],
+            fixpoint: None,
+        }
+    }
+
+    fn finish(self, name: String, returns: Vec<TypeData>, emits: Option<Vec<TypeData>>) -> Procedure {
+        Procedure {
+            name,
+            args: self.args.build(),
+            locals: self.locals.build(),
+            returns,
+            code: Bytecode { instructions: self.instructions },
+            debug: self.debug,
+            rows: self.rows,
+            iterators: self.iterators,
+            emits,
+            aggregates: self.aggregates,
+        }
+    }
+
+    fn error<T>(&mut self, loc: &Located<T>, code: &'static str, s: &str) {
+        self.module.error(loc, code, s)
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+        self.debug.spans.push(self.span);
+    }
+
+    fn builtin_type(&self, name: &str) -> TypeData {
+        self.module.env.type_named(name).unwrap()
+    }
+
+    fn type_of(&self, register: Register) -> TypeData {
+        match register {
+            Register::Arg(a) => self.args.fields[a].type_data,
+            Register::Local(l) => self.locals.fields[l].type_data,
+        }
+    }
+
+    // like ModuleLowerer::source, but a recursive view reads the rows of its component from the fixpoint
+    fn source(&mut self, from: &Located<String>) -> (Source, Vec<TypeData>) {
+        let (source, columns) = self.module.source(&from.value);
+        let source = match (&self.fixpoint, source) {
+            (Some(f), Source::View(v)) if f.component.contains(&from.value) => {
+                if (f.delta.start, f.delta.end) == (from.start, from.end) { Source::Delta(v) } else { Source::Full(v) }
+            }
+            (_, source) => source,
+        };
+        (source, columns)
+    }
+
+    fn lower_clause(&mut self, view: &ast::View, clause: &Located<ast::QueryExpression>) {
+        self.span = clause.location();
+        self.lower_query(clause, &mut |s, _| {
+            let row = view.args.iter().map(|a| s.lookup(&a.value.name.value)).collect();
+            let row = s.row(row);
+            s.emit(Instruction::Emit { row });
+        });
+    }
+
+    fn row(&mut self, registers: Vec<Register>) -> usize {
+        self.rows.push(registers);
+        self.rows.len() - 1
+    }
+
+    fn iterator(&mut self) -> usize {
+        self.iterators += 1;
+        self.iterators - 1
+    }
+
+    // == variables ==
+    fn try_lookup(&self, name: &str) -> Option<Register> {
+        for scope in self.scopes.iter().rev() {
+            for v in scope.iter().rev() {
+                if v.name == name { return Some(v.register) }
+            }
+        }
+        None
+    }
+
+    fn lookup(&self, name: &str) -> Register {
+        match self.try_lookup(name) {
+            Some(register) => register,
+            None => panic!("unresolved variable (the resolver should have caught this): {}", name),
+        }
+    }
+
+    fn bind_args(&mut self, def: &ast::Def, types: &[TypeData]) {
+        for (arg, type_data) in def.args.iter().zip(types) {
+            self.args.push(arg.value.name.value.clone(), *type_data);
+            let register = Register::Arg(self.args.fields.len() - 1);
+            self.bind(&arg.value.name.value, register);
+        }
+    }
+
+    // emits a row of every variable in scopes from the given one inwards, returning their names and types
+    fn emit_variables(&mut self, from_scope: usize) -> (Vec<String>, Vec<TypeData>) {
+        let variables: Vec<(String, Register)> = self.scopes[from_scope..].iter().flatten()
+            .map(|v| (v.name.clone(), v.register)).collect();
+        let types = variables.iter().map(|(_, r)| self.type_of(*r)).collect();
+        let row = self.row(variables.iter().map(|(_, r)| *r).collect());
+        self.emit(Instruction::Emit { row });
+        (variables.into_iter().map(|(n, _)| n).collect(), types)
+    }
+
+    fn bind(&mut self, name: &str, register: Register) {
+        self.scopes.last_mut().unwrap().push(Variable { name: name.to_string(), register })
+    }
+
+    fn temporary(&mut self, type_data: TypeData) -> Register {
+        let name = format!("%{}", self.locals.fields.len());
+        self.locals.push(name, type_data);
+        self.debug.variables.push(None);
+        Register::Local(self.locals.fields.len() - 1)
+    }
+
+    fn is_temporary(&self, register: Register) -> bool {
+        match register {
+            Register::Arg(_) => false,
+            Register::Local(l) => self.debug.variables[l].is_none(),
+        }
+    }
+
+    // give a variable a home, reusing the register if the value is a fresh temporary
+    fn introduce(&mut self, name: &str, value: Register) -> Register {
+        if self.is_temporary(value) {
+            if let Register::Local(l) = value {
+                self.locals.fields[l].name = name.to_string();
+                self.debug.variables[l] = Some(name.to_string());
+            }
+            self.bind(name, value);
+            return value
+        }
+
+        let type_data = self.type_of(value);
+        self.locals.push(name.to_string(), type_data);
+        self.debug.variables.push(Some(name.to_string()));
+        let register = Register::Local(self.locals.fields.len() - 1);
+        self.emit(Instruction::Copy { from: value, out: register });
+        self.bind(name, register);
+        register
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{compile, run};
+
+    const VAMPIRES: &str = include_str!("../../../samplecode/vampires.kupo");
+
+    #[test]
+    fn samplecode_compiles() {
+        for source in [
+            include_str!("../../../samplecode/calls.kupo"),
+            include_str!("../../../samplecode/example1.kupo"),
+            include_str!("../../../samplecode/hello.kupo"),
+            VAMPIRES,
+        ] {
+            assert!(compile(source).is_ok());
+        }
+    }
+
+    #[test]
+    fn sample_views() {
+        assert_eq!(run(VAMPIRES, "lonely_vampire").unwrap(), vec![
+            "[NPC(0), \"Dracula\"]", "[NPC(2), \"Orlok\"]", "[NPC(3), \"Vlad, this month\"]",
+        ]);
+        assert_eq!(run(VAMPIRES, "neighbour").unwrap(), vec![
+            "[NPC(0), \"Dracula\"]", "[NPC(0), \"Orlok\"]", "[NPC(2), \"Dracula\"]",
+            "[NPC(2), \"Orlok\"]", "[NPC(3), \"Dracula\"]", "[NPC(3), \"Orlok\"]",
+        ]);
+    }
+
+    #[test]
+    fn expressions() {
+        let source = "view [@n Integer, @s String] in v {\n    @n := -(1 + 2 * 3) / 2,\n    @s := \"a\" + \"b\",\n}";
+        assert_eq!(run(source, "v").unwrap(), vec!["[-3, \"ab\"]"]);
+        assert_eq!(run("view [@n Integer] in v { @n := 1 / 0 }", "v").unwrap_err(), "division by zero");
+    }
+
+    #[test]
+    fn host_functions() {
+        let source = "view [@s String] in v { @x in lonely, @s := true_name(@x) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[\"Bartholomew\"]", "[\"Dracula\"]", "[\"Orlok\"]"]);
+    }
+}
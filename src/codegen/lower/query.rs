@@ -0,0 +1,316 @@
+use crate::frontend::{Located, ast};
+
+use super::{ProcedureLowerer, Variable};
+use super::super::code::*;
+use super::super::plan::{bound_by_all, plan_conjunction};
+
+// one loop per `in` goal, with inner in the middle (it gets the open iterators, to leave early).
+// failed filters jump to the innermost Next. a disjunction lowers everything after it once per clause
+//
+//     Open it0 ...
+//     L0: Next it0 ... (done: E0)
+//         Open it1 ...
+//         L1: Next it1 ... (done: E1)
+//             <inner>
+//             Jump L1
+//         E1: Jump L0
+//     E0:
+impl<'m, 'e> ProcedureLowerer<'m, 'e> {
+    pub(super) fn lower_query(&mut self, query: &Located<ast::QueryExpression>, inner: &mut dyn FnMut(&mut Self, &[usize])) {
+        self.scopes.push(vec![]);
+
+        let plan = {
+            let me = &*self;
+            plan_conjunction(&query.value.items, &|name| me.try_lookup(name).is_some())
+        };
+        self.lower_goals(&plan, &[], inner);
+
+        self.scopes.pop();
+    }
+
+    // open: iterators that were already open around these goals
+    fn lower_goals(&mut self, goals: &[&Located<ast::QueryGoal>], open: &[usize], inner: &mut dyn FnMut(&mut Self, &[usize])) {
+        let split = goals.iter().position(|g| matches!(g.value, ast::QueryGoal::Disjunction { .. })).unwrap_or(goals.len());
+
+        let mut loops = vec![];
+        let mut skips = vec![];
+        let mut ok = true;
+        for goal in &goals[..split] {
+            let outer = std::mem::replace(&mut self.span, goal.location());
+            ok = self.lower_goal(goal, &mut loops, &mut skips);
+            self.span = outer;
+            // later goals (and inner) might need variables this one didn't get to bind
+            if !ok { break }
+        }
+        if ok {
+            let mut iterators = open.to_vec();
+            iterators.extend(loops.iter().map(|(iter, _)| *iter));
+            match goals.get(split).map(|g| &g.value) {
+                Some(ast::QueryGoal::Disjunction { clauses }) => 
+                    self.lower_disjunction(clauses, &goals[split + 1..], &iterators, inner),
+                _ => inner(self, &iterators),
+            }
+        }
+
+        for (_, next) in loops.into_iter().rev() {
+            self.emit(Instruction::Jump { to: next });
+            let end = self.instructions.len();
+            if let Instruction::Next { done, .. } = &mut self.instructions[next] {
+                *done = end
+            }
+        }
+        let end = self.instructions.len();
+        for skip in skips {
+            if let Instruction::Jump { to } | Instruction::JumpUnless { to, .. } = &mut self.instructions[skip] {
+                *to = end
+            }
+        }
+    }
+
+    fn lower_disjunction(
+        &mut self, 
+        clauses: &[Located<ast::QueryExpression>], 
+        rest: &[&Located<ast::QueryGoal>], 
+        open: &[usize], 
+        inner: &mut dyn FnMut(&mut Self, &[usize]),
+    ) {
+        let shared: Vec<String> = {
+            let me = &*self;
+            bound_by_all(clauses, &|name| me.try_lookup(name).is_some()).into_iter().map(String::from).collect()
+        };
+        let depth = self.scopes.len();
+        for (i, clause) in clauses.iter().enumerate() {
+            self.lower_query(clause, &mut |s, iterators| {
+                // the rest only sees what every clause binds, in the same order every time
+                let variables = shared.iter().map(|name| Variable { name: name.clone(), register: s.lookup(name) }).collect();
+                for scope in &mut s.scopes[depth..] { scope.clear() }
+                s.scopes[depth] = variables;
+
+                let errors = s.module.errors.len();
+                let mut open = open.to_vec();
+                open.extend(iterators);
+                s.lower_goals(rest, &open, inner);
+                // anything wrong with the rest was already reported the first time
+                if i > 0 { s.module.errors.truncate(errors) }
+            })
+        }
+    }
+
+    // false if there was an error
+    // the body of a for or an if: it gets lowered once for each clause of the head
+    pub(super) fn lower_head(&mut self, clauses: &[Located<ast::QueryExpression>], body: &mut dyn FnMut(&mut Self, &[usize])) {
+        for (i, clause) in clauses.iter().enumerate() {
+            self.lower_query(clause, &mut |s, iterators| {
+                let errors = s.module.errors.len();
+                body(s, iterators);
+                // anything wrong with the body was already reported the first time
+                if i > 0 { s.module.errors.truncate(errors) }
+            })
+        }
+    }
+
+    fn lower_goal(&mut self, goal: &Located<ast::QueryGoal>, loops: &mut Vec<(usize, usize)>, skips: &mut Vec<usize>) -> bool {
+        match &goal.value {
+            ast::QueryGoal::In { args, from } => {
+                let (source, columns) = self.source(from);
+                let args = &args.value.args;
+                if args.len() > 32 {
+                    self.error(goal, "C0041", "can't query more than 32 columns at once");
+                    return false
+                }
+
+                let mut row = vec![];
+                let mut bound = 0u32;
+                let mut fresh: Vec<(&str, Register)> = vec![];  // variables bound by this goal
+                let mut repeats = vec![];  // later uses of those variables in the same goal
+                for (i, arg) in args.iter().enumerate() {
+                    if let ast::Expression::Variable { name } = &arg.value {
+                        if self.try_lookup(name).is_none() {
+                            let register = self.temporary(columns[i]);
+                            match fresh.iter().find(|(n, _)| n == name) {
+                                Some((_, first)) => {
+                                    if !columns[i].is_ordered() {
+                                        self.error(arg, "C0042", &format!("{} can't be matched against itself: its type can't be compared", name));
+                                        return false
+                                    }
+                                    repeats.push((*first, register))
+                                }
+                                None => fresh.push((name, register)),
+                            }
+                            row.push(register);
+                            continue
+                        }
+                    }
+
+                    let register = if let Some(r) = self.lower_expression(arg) { r } else { return false };
+                    bound |= 1 << i;
+                    row.push(register);
+                }
+
+                let row = self.row(row);
+                let iter = self.iterator();
+                self.emit(Instruction::Open { iter, source, row, bound });
+                let next = self.instructions.len();
+                self.emit(Instruction::Next { iter, row, bound, done: usize::MAX });
+                for (first, repeat) in repeats {
+                    self.emit(Instruction::JumpUnlessEqual { arg1: first, arg2: repeat, to: next });
+                }
+                for (name, register) in fresh {
+                    self.introduce(name, register);
+                }
+                loops.push((iter, next));
+                true
+            }
+            ast::QueryGoal::Assign { args, expression } => {
+                let values = if let Some(values) = self.lower_multi_expression(expression) { values } else { return false };
+
+                for (target, value) in args.value.args.iter().zip(values) {
+                    if let ast::Expression::Variable { name } = &target.value {
+                        self.introduce(name, value);
+                    }
+                }
+                true
+            }
+            ast::QueryGoal::Filter { condition } => {
+                let arg = if let Some(arg) = self.lower_expression(condition) { arg } else { return false };
+                match loops.last() {
+                    Some((_, next)) => self.emit(Instruction::JumpUnless { arg, to: *next }),
+                    None => {
+                        skips.push(self.instructions.len());
+                        self.emit(Instruction::JumpUnless { arg, to: usize::MAX });
+                    }
+                }
+                true
+            }
+            ast::QueryGoal::Not { body } => {
+                let errors = self.module.errors.len();
+                let mut found = vec![];
+                self.lower_query(body, &mut |s, iterators| {
+                    for iter in iterators {
+                        s.emit(Instruction::Close { iter: *iter });
+                    }
+                    found.push(s.instructions.len());
+                    s.emit(Instruction::Jump { to: usize::MAX });
+                });
+                match loops.last() {
+                    Some((_, next)) => for jump in found { self.instructions[jump] = Instruction::Jump { to: *next } },
+                    None => skips.extend(found),
+                }
+                self.module.errors.len() == errors
+            }
+            ast::QueryGoal::Aggregate { args, op, value, body } => {
+                let locals = value.value.variables();
+                let groups: Vec<String> = {
+                    let me = &*self;
+                    bound_by_all(std::slice::from_ref(body), &|name| me.try_lookup(name).is_some())
+                        .into_iter().filter(|v| !locals.contains(v)).map(String::from).collect()
+                };
+                if groups.len() >= 32 {
+                    self.error(goal, "C0043", "can't group by more than 31 variables at once");
+                    return false
+                }
+
+                let aggregate = self.aggregates.len();
+                let result = self.module.typing.single(&args.value.args[0]);
+                let op = match op.value {
+                    ast::Aggregate::Count => Aggregate::Count,
+                    ast::Aggregate::Sum => Aggregate::Sum,
+                    ast::Aggregate::Min => Aggregate::Min,
+                    ast::Aggregate::Max => Aggregate::Max,
+                    ast::Aggregate::Collect => Aggregate::Collect,
+                };
+                // columns get filled in once the body's lowered
+                self.aggregates.push(Aggregation { op, columns: vec![], groups: groups.len(), result });
+
+                let errors = self.module.errors.len();
+                let mut columns = vec![];
+                self.lower_query(body, &mut |s, _| {
+                    let mut row: Vec<Register> = groups.iter().map(|name| s.lookup(name)).collect();
+                    row.extend(locals.iter().map(|name| s.lookup(name)));
+                    let value = if let Some(value) = s.lower_expression(value) { value } else { return };
+                    row.push(value);
+                    columns = row.iter().map(|r| s.type_of(*r)).collect();
+                    let row = s.row(row);
+                    s.emit(Instruction::Accumulate { aggregate, row });
+                });
+                if self.module.errors.len() != errors { return false }
+                if !columns.iter().all(|t| t.is_ordered()) {
+                    self.error(goal, "C0044", "can't aggregate over values that can't be compared");
+                    return false
+                }
+
+                let mut registers: Vec<Register> = columns[..groups.len()].iter().map(|t| self.temporary(*t)).collect();
+                registers.push(self.temporary(result));
+                self.aggregates[aggregate].columns = columns;
+
+                let row = self.row(registers.clone());
+                let iter = self.iterator();
+                self.emit(Instruction::Open { iter, source: Source::Aggregate(aggregate), row, bound: 0 });
+                let next = self.instructions.len();
+                self.emit(Instruction::Next { iter, row, bound: 0, done: usize::MAX });
+                for (name, register) in groups.iter().zip(&registers) {
+                    self.introduce(name, *register);
+                }
+                if let ast::Expression::Variable { name } = &args.value.args[0].value {
+                    self.introduce(name, registers[groups.len()]);
+                }
+                loops.push((iter, next));
+                true
+            }
+            ast::QueryGoal::Disjunction { .. } => unreachable!("lower_goals lowers disjunctions itself"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{compile, run};
+
+    #[test]
+    fn not() {
+        let source = include_str!("../../../samplecode/vampires.kupo");
+        assert_eq!(run(source, "sociable_vampire").unwrap(), vec!["[NPC(1)]", "[NPC(3)]"]);
+        let source = "view [@x NPC] in v { [@x, @c] in lives_in, not { @x in lonely, @x in vampire } }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[NPC(1)]", "[NPC(3)]"]);
+        let source = "view [@s String] in v { @x in vampire, @s := true_name(@x), not ({ @x in lonely } or { @s == \"Vlad\" }) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[\"Carmilla\"]"]);
+        // a plain boolean not is just a filter
+        let source = "view [@s String] in v { @x in lonely, @s := true_name(@x), not @s == \"Dracula\" }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[\"Bartholomew\"]", "[\"Orlok\"]"]);
+        let source = "def f() Integer {\n    @n := 0\n    for @x in vampire, not @x in lonely { @n = @n + 1 }\n    return @n\n}\n\
+            view [@n Integer] in v { @n := f() }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[2]"]);
+    }
+
+    #[test]
+    fn not_errors() {
+        // planning could fix the order, but the order it's written in has to work too
+        assert_eq!(compile("view [@x NPC] in v { not @x in lonely, @x in vampire }").err(), Some(vec!["C0009"]));
+        assert_eq!(compile("view [@x NPC] in v { @x in vampire, not { [@x, @y] in sired } }").err(), Some(vec!["C0009"]));
+        assert_eq!(compile("view [@x NPC] in v { @x in vampire, not { @y := 1 } }").err(), Some(vec!["C0010"]));
+    }
+
+    #[test]
+    fn disjunctions() {
+        let source = include_str!("../../../samplecode/vampires.kupo");
+        assert_eq!(run(source, "notable_vampire").unwrap(), vec![
+            "[NPC(0), \"Dracula\"]", "[NPC(2), \"Orlok\"]", "[NPC(3), \"Vlad\"]",
+        ]);
+        // what every clause binds can be used afterwards
+        let source = "view [@s String] in v { ({ [@x, @y] in sired } or { [@y, @x] in sired }), @x in lonely, @s := true_name(@y) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[\"Carmilla\"]", "[\"Vlad\"]"]);
+        let source = "view [@x NPC] in v { @x in lonely, ({ ({ @x in vampire } or { @x in vampire }) }) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[NPC(0)]", "[NPC(2)]"]);
+        // a for runs once per solution of each clause
+        let source = "def f() Integer {\n    @n := 0\n    for @x in vampire, ({ @x in lonely } or { [@x, @z] in sired }) { @n = @n + 1 }\n    return @n\n}\n\
+            view [@n Integer] in v { @n := f() }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[5]"]);
+    }
+
+    #[test]
+    fn disjunction_errors() {
+        // only one clause binds @z
+        let source = "view [@x NPC] in v { @x in vampire, ({ [@x, @z] in sired } or { @x in lonely }), @x != @z }";
+        assert_eq!(compile(source).err(), Some(vec!["C0018"]));
+    }
+}
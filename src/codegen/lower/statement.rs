@@ -0,0 +1,121 @@
+use crate::frontend::{Located, ast};
+
+use super::ProcedureLowerer;
+use super::super::code::*;
+
+impl<'m, 'e> ProcedureLowerer<'m, 'e> {
+    pub(super) fn lower_block(&mut self, block: &Located<ast::Block>) {
+        self.scopes.push(vec![]);
+        for statement in &block.value.items {
+            self.lower_statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    pub(super) fn lower_statement(&mut self, statement: &Located<ast::Statement>) {
+        let outer = std::mem::replace(&mut self.span, statement.location());
+        self.lower_statement_here(statement);
+        self.span = outer;
+    }
+
+    fn lower_statement_here(&mut self, statement: &Located<ast::Statement>) {
+        match &statement.value {
+            // once per solution of each clause (so twice if two clauses find it).
+            // writes wait until the end, so they can't change later solutions
+            ast::Statement::For { clauses, body } => {
+                self.emit(Instruction::HoldWrites);
+                self.lower_head(clauses, &mut |s, _| s.lower_block(body));
+                self.emit(Instruction::ReleaseWrites);
+            }
+            // the first solution wins: close the query's iterators and skip the other clauses
+            ast::Statement::If { clauses, body, else_ } => {
+                let mut exits = vec![];
+                self.lower_head(clauses, &mut |s, iterators| {
+                    for iter in iterators {
+                        s.emit(Instruction::Close { iter: *iter });
+                    }
+                    s.lower_block(body);
+                    exits.push(s.instructions.len());
+                    s.emit(Instruction::Jump { to: usize::MAX });
+                });
+                if let Some(else_) = else_ {
+                    self.lower_block(else_);
+                }
+                let end = self.instructions.len();
+                for exit in exits {
+                    self.instructions[exit] = Instruction::Jump { to: end };
+                }
+            }
+            ast::Statement::Return { args } => {
+                if let Some(values) = self.lower_values(args) {
+                    let values = self.row(values);
+                    self.emit(Instruction::Return { values });
+                }
+            }
+            ast::Statement::Call { call } => {
+                self.lower_call(call);
+            }
+            ast::Statement::Assign { first, variable, arg } => {
+                let values = if let Some(values) = self.lower_multi_expression(arg) { values } else { return };
+
+                for (target, value) in variable.value.args.iter().zip(values) {
+                    let name = match &target.value {
+                        ast::Expression::Variable { name } => name,
+                        _ => unreachable!(),
+                    };
+                    if *first {
+                        self.introduce(name, value);
+                    } else {
+                        let register = self.lookup(name);
+                        self.emit(Instruction::Copy { from: value, out: register });
+                    }
+                }
+            }
+            ast::Statement::Write { op, args, table } => {
+                let values = if let Some(values) = self.lower_values(args) { values } else { return };
+                let table = match self.module.source(&table.value) {
+                    (Source::Table(t), _) => t,
+                    _ => unreachable!("only tables can be written to"),
+                };
+                let write = match op.value {
+                    ast::Write::Insert => Write::Insert,
+                    ast::Write::Remove => Write::Remove,
+                    ast::Write::Expunge => Write::Expunge,
+                };
+                let row = self.row(values);
+                self.emit(Instruction::Write { write, table, row });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::run;
+
+    fn value(defs: &str) -> Vec<String> {
+        run(&format!("{}\nview [@v String] in v {{ @v := f() }}", defs), "v").unwrap()
+    }
+
+    #[test]
+    fn for_every_solution() {
+        let source = "def f() String {\n    @s := \"\"\n    for @x in lonely or @x in vampire { @s = @s + true_name(@x) + \" \" }\n    return @s\n}";
+        assert_eq!(value(source), vec!["[\"Dracula Orlok Bartholomew Dracula Carmilla Orlok Vlad \"]"]);
+        let source = "def f() String {\n    for @x in lonely, not @x in vampire { return true_name(@x) }\n    return \"nobody\"\n}";
+        assert_eq!(value(source), vec!["[\"Bartholomew\"]"]);
+        let source = "def f() String {\n    for [@x, @y] in sired, @y in lonely { return \"someone\" }\n    return \"nobody\"\n}";
+        assert_eq!(value(source), vec!["[\"someone\"]"]);
+    }
+
+    #[test]
+    fn if_else() {
+        // only the first solution runs the body
+        let source = "def f() String {\n    @s := \"\"\n    if @x in vampire { @s = @s + true_name(@x) }\n    return @s\n}";
+        assert_eq!(value(source), vec!["[\"Dracula\"]"]);
+        let source = "def f() String {\n    if @x in lonely, not @x in lonely { return \"yes\" } else { return \"no\" }\n}";
+        assert_eq!(value(source), vec!["[\"no\"]"]);
+        // or the first clause's, skipping the rest
+        let source = "def f() String {\n    if [@x, @y] in friendly_bat or [@x, @y] in lonely_vampires_monthly { return @y } else { return \"no\" }\n}";
+        assert_eq!(value(source), vec!["[\"Bartholomew the bat\"]"]);
+    }
+}
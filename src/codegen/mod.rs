@@ -1,3 +1,6 @@
 mod code;
+mod lower;
+mod plan;
 
-pub use code::*;
\ No newline at end of file
+pub use code::*;
+pub use lower::{Scratch, lower_module, lower_scratch};
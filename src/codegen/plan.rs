@@ -0,0 +1,232 @@
+use crate::frontend::{Located, ast};
+
+// only picks the order goals run in: := and filters as soon as they can, then whichever `in`
+// knows the most columns (the key counts extra). disjunctions and aggregates go as late as they can.
+// the resolver checked the written order works, so something's always ready
+pub fn plan_conjunction<'a>(
+    goals: &'a [Located<ast::QueryGoal>],
+    is_bound: &dyn Fn(&str) -> bool,
+) -> Vec<&'a Located<ast::QueryGoal>> {
+    let mut planner = Planner { is_bound, bound: vec![] };
+    // the order they were written in always works
+    planner.plan(goals).unwrap_or_else(|| goals.iter().collect())
+}
+
+// the variables a disjunction binds: the ones every clause binds
+pub fn bound_by_all<'a>(
+    clauses: &'a [Located<ast::QueryExpression>],
+    is_bound: &dyn Fn(&str) -> bool,
+) -> Vec<&'a str> {
+    Planner { is_bound, bound: vec![] }.bound_by_all(clauses)
+}
+
+struct Planner<'a, 'b> {
+    is_bound: &'b dyn Fn(&str) -> bool,
+    bound: Vec<&'a str>,
+}
+
+impl<'a, 'b> Planner<'a, 'b> {
+    // None if some goal could never run
+    fn plan(&mut self, goals: &'a [Located<ast::QueryGoal>]) -> Option<Vec<&'a Located<ast::QueryGoal>>> {
+        let mut remaining: Vec<&'a Located<ast::QueryGoal>> = goals.iter().collect();
+        let mut plan = vec![];
+
+        while !remaining.is_empty() {
+            let mut best = 0;
+            let mut best_score = None;
+            for (i, goal) in remaining.iter().enumerate() {
+                let score = self.score(goal);
+                if score > best_score {
+                    best = i;
+                    best_score = score;
+                }
+            }
+            best_score?;
+
+            let goal = remaining.remove(best);
+            self.run(goal);
+            plan.push(goal);
+        }
+        Some(plan)
+    }
+
+    fn fork(&self) -> Planner<'a, 'b> {
+        Planner { is_bound: self.is_bound, bound: self.bound.clone() }
+    }
+
+    fn bound_by_all(&self, clauses: &'a [Located<ast::QueryExpression>]) -> Vec<&'a str> {
+        let mut shared: Option<Vec<&'a str>> = None;
+        for clause in clauses {
+            let mut fork = self.fork();
+            fork.plan(&clause.value.items);
+            let here: Vec<&'a str> = fork.bound.into_iter().filter(|v| !self.known(v)).collect();
+            shared = Some(match shared {
+                None => here,
+                Some(s) => s.into_iter().filter(|v| here.contains(v)).collect(),
+            });
+        }
+        shared.unwrap_or_default()
+    }
+
+    fn known(&self, name: &str) -> bool {
+        self.bound.contains(&name) || (self.is_bound)(name)
+    }
+
+    fn known_expression(&self, expr: &Located<ast::Expression>) -> bool {
+        let mut variables = vec![];
+        variables_of(expr, &mut variables);
+        variables.iter().all(|v| self.known(v))
+    }
+
+    // None if the goal can't run yet
+    fn score(&self, goal: &'a Located<ast::QueryGoal>) -> Option<usize> {
+        match &goal.value {
+            ast::QueryGoal::In { args, .. } => {
+                let mut score = 0;
+                let mut all_bound = true;
+                for (i, arg) in args.value.args.iter().enumerate() {
+                    let bound = match &arg.value {
+                        ast::Expression::Variable { name } => self.known(name),
+                        _ if self.known_expression(arg) => true,
+                        _ => return None,
+                    };
+                    if bound {
+                        score += if i == 0 { 10 } else { 1 };
+                    }
+                    all_bound &= bound;
+                }
+                // a pure membership test
+                if all_bound { score += 100 }
+                Some(score)
+            }
+            ast::QueryGoal::Assign { expression, .. } => {
+                if self.known_expression(expression) { Some(usize::MAX) } else { None }
+            }
+            ast::QueryGoal::Filter { condition } => {
+                if self.known_expression(condition) { Some(usize::MAX) } else { None }
+            }
+            // it can only test, so it waits for everything it uses
+            ast::QueryGoal::Not { body } => {
+                let mut variables = vec![];
+                for goal in &body.value.items { variables_of_goal(goal, &mut variables) }
+                if variables.iter().all(|v| self.known(v)) { Some(usize::MAX) } else { None }
+            }
+            ast::QueryGoal::Disjunction { clauses } => {
+                let ready = clauses.iter().all(|c| self.fork().plan(&c.value.items).is_some());
+                if ready { Some(0) } else { None }
+            }
+            ast::QueryGoal::Aggregate { body, .. } => {
+                if self.fork().plan(&body.value.items).is_some() { Some(0) } else { None }
+            }
+        }
+    }
+
+    fn run(&mut self, goal: &'a Located<ast::QueryGoal>) {
+        let args = match &goal.value {
+            ast::QueryGoal::In { args, .. } => args,
+            ast::QueryGoal::Assign { args, .. } => args,
+            ast::QueryGoal::Filter { .. } | ast::QueryGoal::Not { .. } => return,  // binds nothing
+            ast::QueryGoal::Disjunction { clauses } => {
+                let bound = self.bound_by_all(clauses);
+                self.bound.extend(bound);
+                return
+            }
+            // its groups, then the result
+            ast::QueryGoal::Aggregate { args, value, body, .. } => {
+                let locals = value.value.variables();
+                let groups = self.bound_by_all(std::slice::from_ref(body));
+                self.bound.extend(groups.into_iter().filter(|v| !locals.contains(v)));
+                args
+            }
+        };
+        for arg in &args.value.args {
+            if let ast::Expression::Variable { name } = &arg.value {
+                self.bound.push(name)
+            }
+        }
+    }
+}
+
+fn variables_of_goal<'a>(goal: &'a Located<ast::QueryGoal>, out: &mut Vec<&'a str>) {
+    match &goal.value {
+        ast::QueryGoal::In { args, .. } => {
+            for arg in &args.value.args { variables_of(arg, out) }
+        }
+        ast::QueryGoal::Assign { args, expression } => {
+            for arg in &args.value.args { variables_of(arg, out) }
+            variables_of(expression, out);
+        }
+        ast::QueryGoal::Filter { condition } => variables_of(condition, out),
+        ast::QueryGoal::Aggregate { args, value, body, .. } => {
+            for arg in &args.value.args { variables_of(arg, out) }
+            let locals = value.value.variables();
+            let mut inside = vec![];
+            for goal in &body.value.items { variables_of_goal(goal, &mut inside) }
+            out.extend(inside.into_iter().filter(|v| !locals.contains(v)));
+        }
+        ast::QueryGoal::Not { body } => {
+            for goal in &body.value.items { variables_of_goal(goal, out) }
+        }
+        ast::QueryGoal::Disjunction { clauses } => {
+            for goal in clauses.iter().flat_map(|c| &c.value.items) { variables_of_goal(goal, out) }
+        }
+    }
+}
+
+fn variables_of<'a>(expr: &'a Located<ast::Expression>, out: &mut Vec<&'a str>) {
+    match &expr.value {
+        ast::Expression::StringLiteral { .. } | ast::Expression::IntegerLiteral { .. } => {}
+        ast::Expression::Variable { name } => out.push(name),
+        ast::Expression::Call { call } => {
+            for arg in &call.value.args { variables_of(arg, out) }
+        }
+        ast::Expression::UOp { arg, .. } => variables_of(arg, out),
+        ast::Expression::BinOp { arg1, arg2, .. } => {
+            variables_of(arg1, out);
+            variables_of(arg2, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{ast, parse_module};
+    use crate::testing::run;
+
+    use super::plan_conjunction;
+
+    // the goals of the first clause of the view in source, in the order they'd run
+    fn plan(source: &str) -> Vec<String> {
+        let module = parse_module(source).unwrap();
+        let clause = match &module.value.items[0].value {
+            ast::Item::View(v) => &v.clauses[0],
+            ast::Item::Def(_) => panic!("not a view"),
+        };
+        plan_conjunction(&clause.value.items, &|_| false).iter().map(|g| source[g.start..g.end].trim_end_matches([',', ' ', '\n']).to_string()).collect()
+    }
+
+    const ROOMMATES: &str = "view [@x NPC, @y String] in v {\n    @y := true_name(@x),\n    @d != @x,\n    [@d, @c] in lives_in,\n    [@x, @c] in lives_in,\n    @x in lonely,\n}";
+
+    #[test]
+    fn order() {
+        // := and filters as soon as they can go (in the order they were written), then whichever `in` knows the most
+        assert_eq!(plan(ROOMMATES), vec![
+            "[@d, @c] in lives_in", "[@x, @c] in lives_in", "@y := true_name(@x)", "@d != @x", "@x in lonely",
+        ]);
+        let source = "view [@x NPC] in v {\n    [@y, @x] in sire_of,\n    @y in lonely,\n}";
+        assert_eq!(plan(source), vec!["[@y, @x] in sire_of", "@y in lonely"]);
+        let source = "view [@x NPC, @n Integer] in v {\n    @n := count(@y : [@x, @y] in sired),\n    ({ @x in lonely } or { @x in vampire }),\n    @x in vampire,\n}";
+        // the count needs nothing from outside so it's ready straight away; the `or` waits for @x
+        assert_eq!(plan(source), vec!["@n := count(@y : [@x, @y] in sired)", "@x in vampire", "({ @x in lonely } or { @x in vampire })"]);
+    }
+
+    #[test]
+    fn same_rows_either_way() {
+        let written = "view [@x NPC, @y String] in v {\n    @x in lonely,\n    [@x, @c] in lives_in,\n    [@d, @c] in lives_in,\n    @d != @x,\n    @y := true_name(@x),\n}";
+        let planned = "view [@x NPC, @y String] in v {\n    @x in lonely,\n    @y := true_name(@x),\n    [@x, @c] in lives_in,\n    [@d, @c] in lives_in,\n    @d != @x,\n}";
+        assert_eq!(plan(written), plan(planned));
+        assert_eq!(plan(planned)[1], "@y := true_name(@x)");
+        assert_eq!(run(written, "v").unwrap(), run(planned, "v").unwrap());
+        assert_eq!(run(written, "v").unwrap(), vec!["[NPC(0), \"Dracula\"]", "[NPC(2), \"Orlok\"]"]);
+    }
+}
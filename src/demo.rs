@@ -0,0 +1,110 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use moogle::{IdLike, OneToMany, Set, SharedAnySet, SharedAnyToMany, SharedAnyToOne, ToOne};
+
+use crate::runtime::dynamics::{Backward, Forward};
+use crate::runtime::{Database, Tuples, id_type};
+use crate::semantics::Environment;
+
+// pretend game data for samplecode/ to run against
+#[allow(clippy::upper_case_acronyms)]  // it's what kupo code calls it
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NPC(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Castle(usize);
+
+const NPCS: &[&str] = &["Dracula", "Carmilla", "Orlok", "Vlad", "Bartholomew"];
+const CASTLES: &[&str] = &["Bran", "Karnstein"];
+
+impl IdLike for NPC {
+    fn id_min_value() -> Self { NPC(usize::id_min_value()) }
+    fn id_max_value() -> Self { NPC(usize::id_max_value()) }
+}
+
+impl IdLike for Castle {
+    fn id_min_value() -> Self { Castle(usize::id_min_value()) }
+    fn id_max_value() -> Self { Castle(usize::id_max_value()) }
+}
+
+impl fmt::Debug for NPC {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NPC({})", self.0)
+    }
+}
+
+impl fmt::Debug for Castle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Castle({})", CASTLES[self.0])
+    }
+}
+
+thread_local! {
+    // for new_month: host functions can't hang on to anything themselves
+    static DATABASE: RefCell<Option<Rc<Database>>> = const { RefCell::new(None) };
+}
+
+pub fn environment() -> Environment {
+    let mut env = Environment::new();
+    env.add_type("NPC", id_type::<NPC>());
+    env.add_type("Castle", id_type::<Castle>());
+    let string = env.type_named("String").unwrap();
+
+    let npc = id_type::<NPC>();
+    let castle = id_type::<Castle>();
+    let mut db = Database::new();
+
+    let vampire = Set::new();
+    for npc in &[0, 1, 2, 3] { vampire.fwd().insert(NPC(*npc)); }
+    db.add_set("vampire", npc, vampire).unwrap();
+
+    let lonely = Set::new();
+    for npc in &[0, 2, 4] { lonely.fwd().insert(NPC(*npc)); }
+    db.add_set("lonely", npc, lonely).unwrap();
+
+    let lives_in = ToOne::new();
+    for (npc, castle) in &[(0, 0), (1, 1), (2, 0), (3, 0)] { lives_in.fwd().insert(NPC(*npc), Castle(*castle)); }
+    db.add_to_one("lives_in", npc, castle, lives_in).unwrap();
+
+    // who turned whom: everyone has at most one sire, so sire_of is the same thing backwards
+    let sired = Rc::new(OneToMany::new());
+    for (sire, npc) in &[(0, 1), (0, 3), (3, 2)] { sired.fwd().insert(NPC(*sire), NPC(*npc)); }
+    db.add_to_many("sired", npc, npc, Forward(sired.clone())).unwrap();
+    db.add_to_one("sire_of", npc, npc, Backward(sired)).unwrap();
+
+    // these have strings in them, so they can't be moogle structures
+    // (the RefCell is what lets kupo code write to them)
+    let mut lonely_vampires_monthly = Tuples::new(&[npc, string]);
+    push_named(&mut lonely_vampires_monthly, 3, "Vlad, this month");
+    db.add_table("lonely_vampires_monthly", Rc::new(RefCell::new(Rc::new(lonely_vampires_monthly)))).unwrap();
+
+    let mut friendly_bat = Tuples::new(&[npc, string]);
+    push_named(&mut friendly_bat, 4, "Bartholomew the bat");
+    db.add_table("friendly_bat", Rc::new(RefCell::new(Rc::new(friendly_bat)))).unwrap();
+
+    let db = Rc::new(db);
+    env.use_database(db.clone());
+    DATABASE.with(|d| *d.borrow_mut() = Some(db));
+
+    // starts lonely_vampires_monthly over with a new table, so code that was using the old one has to be recompiled
+    env.add_function_ref("new_month", "String", |month| DATABASE.with(|db| {
+        let db = db.borrow();
+        let db = db.as_ref().unwrap();
+        let mut lonely_vampires_monthly = Tuples::new(&db.table_named("lonely_vampires_monthly").unwrap().columns);
+        push_named(&mut lonely_vampires_monthly, 3, &format!("Vlad, {}", month.cast::<String>().get()));
+        db.swap("lonely_vampires_monthly", Rc::new(RefCell::new(Rc::new(lonely_vampires_monthly)))).unwrap();
+    }));
+
+    env.add_function_mut("true_name", "NPC", "String", |npc, out| {
+        let npc = *npc.cast::<NPC>().get();
+        out.cast::<String>().initialize(NPCS[npc.0].to_string())
+    });
+    env
+}
+
+fn push_named(tuples: &mut Tuples, npc: usize, name: &str) {
+    tuples.push_with(|column, ptr| match column {
+        0 => ptr.cast::<NPC>().initialize(NPC(npc)),
+        _ => ptr.cast::<String>().initialize(name.to_string()),
+    })
+}
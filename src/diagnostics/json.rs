@@ -0,0 +1,287 @@
+use std::fmt::{self, Write};
+
+use crate::frontend::Located;
+
+use super::{Diagnostic, SourceFile};
+
+// just enough JSON for LSP and --json. objects keep their keys in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+const NULL: Json = Json::Null;
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn string(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+
+    pub fn number(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+
+    // null if there's no such key, or this isn't an object
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self { Json::String(s) => Some(s), _ => None }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json] {
+        match self { Json::Array(items) => items, _ => &[] }
+    }
+
+    pub fn parse(s: &str) -> Option<Json> {
+        let mut parser = Parser { chars: s.chars().collect(), at: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.at == parser.chars.len() { Some(value) } else { None }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write!(f, "{}", quote(s)),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ",")? }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 { write!(f, ",")? }
+                    write!(f, "{}:{}", quote(k), v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// one object per line. spans are byte offsets; lines and columns count from 1 (columns in characters)
+pub fn to_json(diagnostic: &Diagnostic, files: &[&SourceFile]) -> String {
+    let mut fields = location(diagnostic.at, files);
+    fields.extend([
+        ("severity", Json::string(diagnostic.severity.name())),
+        ("code", diagnostic.code.map_or(Json::Null, Json::string)),
+        ("message", Json::string(&diagnostic.message)),
+        ("labels", Json::Array(diagnostic.labels.iter().map(|label| {
+            let mut fields = location(Some(label.location()), files);
+            fields.push(("message", Json::string(&label.value)));
+            Json::object(fields)
+        }).collect())),
+        ("notes", Json::Array(diagnostic.notes.iter().map(|n| Json::string(n)).collect())),
+    ]);
+    Json::object(fields).to_string()
+}
+
+fn location(at: Option<Located<()>>, files: &[&SourceFile]) -> Vec<(&'static str, Json)> {
+    let found = at.and_then(|at| files.iter().find(|f| f.contains(at.start)).map(|f| (at, f)));
+    let (at, file) = if let Some(found) = found { found } else {
+        return vec![("file", Json::Null), ("span", Json::Null), ("range", Json::Null)]
+    };
+    // the same span the text renderer underlines
+    let at = file.trim(at);
+
+    let position = |offset| {
+        let (line, column) = file.line_col(offset);
+        Json::object(vec![("line", Json::number(line)), ("column", Json::number(column))])
+    };
+    vec![
+        ("file", Json::string(&file.name)),
+        ("span", Json::object(vec![("start", Json::number(file.local(at.start))), ("end", Json::number(file.local(at.end)))])),
+        ("range", Json::object(vec![("start", position(at.start)), ("end", position(at.end))])),
+    ]
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.at += 1;
+        Some(c)
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() { self.at += 1 }
+    }
+
+    fn expect(&mut self, s: &str) -> Option<()> {
+        for c in s.chars() {
+            if self.next()? != c { return None }
+        }
+        Some(())
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.whitespace();
+        match self.peek()? {
+            'n' => { self.expect("null")?; Some(Json::Null) }
+            't' => { self.expect("true")?; Some(Json::Bool(true)) }
+            'f' => { self.expect("false")?; Some(Json::Bool(false)) }
+            '"' => Some(Json::String(self.string()?)),
+            '[' => {
+                self.at += 1;
+                let mut items = vec![];
+                self.whitespace();
+                if self.peek()? == ']' { self.at += 1; return Some(Json::Array(items)) }
+                loop {
+                    items.push(self.value()?);
+                    self.whitespace();
+                    match self.next()? {
+                        ',' => {}
+                        ']' => return Some(Json::Array(items)),
+                        _ => return None,
+                    }
+                }
+            }
+            '{' => {
+                self.at += 1;
+                let mut fields = vec![];
+                self.whitespace();
+                if self.peek()? == '}' { self.at += 1; return Some(Json::Object(fields)) }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.whitespace();
+                    self.expect(":")?;
+                    fields.push((key, self.value()?));
+                    self.whitespace();
+                    match self.next()? {
+                        ',' => {}
+                        '}' => return Some(Json::Object(fields)),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Option<Json> {
+        let start = self.at;
+        while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() { self.at += 1 }
+        let text: String = self.chars[start..self.at].iter().collect();
+        text.parse().ok().map(Json::Number)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Some(out),
+                '\\' => match self.next()? {
+                    '"' => out.push('"'),
+                    '\\' => out.push('\\'),
+                    '/' => out.push('/'),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'u' => {
+                        let high = self.hex()?;
+                        // characters outside the BMP come in as a surrogate pair
+                        let c = if (0xd800..0xdc00).contains(&high) {
+                            self.expect("\\u")?;
+                            let low = self.hex()?;
+                            0x10000 + ((high - 0xd800) << 10) + (low.checked_sub(0xdc00)?)
+                        } else { high };
+                        out.push(char::from_u32(c)?);
+                    }
+                    _ => return None,
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Option<u32> {
+        let mut n = 0;
+        for _ in 0..4 {
+            n = n * 16 + self.next()?.to_digit(16)?;
+        }
+        Some(n)
+    }
+}
+
+// s as a JSON string literal
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::demo;
+    use crate::diagnostics::{Diagnostic, SourceFile};
+    use crate::frontend::parse_module;
+    use crate::semantics;
+
+    use super::{Json, to_json};
+
+    #[test]
+    fn range_stops_at_the_token() {
+        let source = "def main() {\n    @x := 1 + \"a\"\n    print(@x)\n}\n";
+        let module = parse_module(source).unwrap();
+        let errors = semantics::check_module(&module.value, &demo::environment()).err().unwrap();
+        let file = SourceFile::new("test.kupo", source);
+        let json = to_json(&Diagnostic::from(&errors[0]), &[&file]);
+        assert!(json.contains("\"code\":\"C0034\""), "{}", json);
+        assert!(json.contains("\"span\":{\"start\":23,\"end\":30}"), "{}", json);
+        assert_eq!(Json::parse(&json).unwrap().get("message").as_str(), Some("can't apply Add to Integer and String"));
+        assert!(json.contains("\"range\":{\"start\":{\"line\":2,\"column\":11},\"end\":{\"line\":2,\"column\":18}}"), "{}", json);
+    }
+}
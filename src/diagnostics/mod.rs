@@ -0,0 +1,135 @@
+mod json;
+mod render;
+mod source;
+
+pub use self::json::{Json, to_json};
+pub use self::render::{Style, render};
+pub use self::source::SourceFile;
+
+use crate::frontend::{KupoParseError, Located};
+use crate::runtime::KupoRuntimeError;
+use crate::semantics::KupoCompileError;
+
+// any kind of kupo error, for showing to people
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,  // see PARSE_ERRORS and COMPILE_ERRORS
+    pub message: String,
+    pub at: Option<Located<()>>,  // runtime errors from code without debug info aren't anywhere
+    pub labels: Vec<Located<String>>,
+    pub notes: Vec<String>,
+}
+
+// only errors so far
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+}
+
+// how diagnostics get shown
+#[derive(Clone, Copy)]
+pub enum Format {
+    Text(Style),
+    Json,  // one object per line
+}
+
+impl Format {
+    pub fn show(self, diagnostic: &Diagnostic, files: &[&SourceFile]) -> String {
+        match self {
+            Format::Text(style) => render(diagnostic, files, style),
+            Format::Json => to_json(diagnostic, files) + "\n",
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn error(message: &str, at: Option<Located<()>>) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, code: None, message: message.to_string(), at, labels: vec![], notes: vec![] }
+    }
+
+    pub fn label(mut self, at: Located<()>, text: &str) -> Diagnostic {
+        self.labels.push(at.replace(text.to_string()));
+        self
+    }
+
+    pub fn note(mut self, text: &str) -> Diagnostic {
+        self.notes.push(text.to_string());
+        self
+    }
+}
+
+impl From<&Located<KupoParseError>> for Diagnostic {
+    fn from(e: &Located<KupoParseError>) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(e.value.message, Some(e.location()));
+        diagnostic.code = Some(e.value.code);
+        diagnostic
+    }
+}
+
+impl From<&Located<KupoCompileError>> for Diagnostic {
+    fn from(e: &Located<KupoCompileError>) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(&e.value.message, Some(e.location()));
+        diagnostic.code = e.value.code;
+        diagnostic
+    }
+}
+
+// the innermost frame is where it went wrong: the rest are how it got there
+impl From<&KupoRuntimeError> for Diagnostic {
+    fn from(e: &KupoRuntimeError) -> Diagnostic {
+        let mut trace = e.trace.iter();
+        let mut diagnostic = match trace.next() {
+            Some(top) => Diagnostic::error(&e.message, top.location).note(&format!("while running {}", top.procedure)),
+            None => Diagnostic::error(&e.message, None),
+        };
+        // runaway recursion repeats the same frame thousands of times: say so once
+        let mut trace = trace.peekable();
+        while let Some(entry) = trace.next() {
+            let mut repeats = 1;
+            while trace.next_if(|next| next.procedure == entry.procedure && same_place(next.location, entry.location)).is_some() {
+                repeats += 1
+            }
+            if let Some(at) = entry.location {
+                let times = if repeats > 1 { format!(" (×{})", repeats) } else { String::new() };
+                diagnostic = diagnostic.label(at, &format!("called from {}{}", entry.procedure, times));
+            }
+        }
+        diagnostic
+    }
+}
+
+fn same_place(a: Option<Located<()>>, b: Option<Located<()>>) -> bool {
+    a.map(|a| (a.start, a.end)) == b.map(|b| (b.start, b.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::TraceEntry;
+
+    fn entry(procedure: &str, start: usize) -> TraceEntry {
+        TraceEntry { procedure: procedure.to_string(), location: Some(Located { value: (), start, end: start + 1 }) }
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let error = KupoRuntimeError {
+            message: "stack overflow".to_string(),
+            trace: vec![entry("f", 10), entry("f", 20), entry("f", 20), entry("f", 20), entry("g", 30), entry("main", 40)],
+        };
+        let diagnostic = Diagnostic::from(&error);
+        assert_eq!(diagnostic.at.map(|a| a.start), Some(10));
+        assert_eq!(diagnostic.notes, vec!["while running f"]);
+        let labels: Vec<_> = diagnostic.labels.iter().map(|l| (l.start, l.value.as_str())).collect();
+        assert_eq!(labels, vec![(20, "called from f (×3)"), (30, "called from g"), (40, "called from main")]);
+    }
+}
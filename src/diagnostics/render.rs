@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+use crate::frontend::Located;
+
+use super::{Diagnostic, SourceFile};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Color,  // ANSI escapes, for terminals
+}
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl Style {
+    pub fn for_stdout() -> Style {
+        if io::stdout().is_terminal() { Style::Color } else { Style::Plain }
+    }
+
+    fn paint(self, color: &str, text: &str) -> String {
+        match self {
+            Style::Plain => text.to_string(),
+            Style::Color => format!("{}{}{}", color, text, RESET),
+        }
+    }
+}
+
+// somewhere to underline, what to underline it with, and what to say about it
+struct Mark<'a> {
+    at: Located<()>,
+    primary: bool,
+    label: &'a str,
+}
+
+// rustc-style. anything that isn't in one of the files doesn't get shown
+pub fn render(diagnostic: &Diagnostic, files: &[&SourceFile], style: Style) -> String {
+    let mut out = String::new();
+    let severity = match diagnostic.code {
+        Some(code) => format!("{}[{}]", diagnostic.severity.name(), code),
+        None => diagnostic.severity.name().to_string(),
+    };
+    writeln!(out, "{}: {}", style.paint(RED, &severity), style.paint(BOLD, &diagnostic.message)).unwrap();
+
+    let mut marks = vec![];
+    if let Some(at) = diagnostic.at {
+        marks.push(Mark { at, primary: true, label: "" });
+    }
+    for label in &diagnostic.labels {
+        marks.push(Mark { at: label.location(), primary: false, label: &label.value });
+    }
+    let marks: Vec<(&SourceFile, Mark)> = marks.into_iter()
+        .filter_map(|m| files.iter().find(|f| f.contains(m.at.start)).map(|f| (*f, Mark { at: f.trim(m.at), ..m })))
+        .collect();
+
+    let widest = marks.iter().map(|(f, m)| f.line_col(m.at.end).0).max().unwrap_or(0);
+    let gutter = " ".repeat(widest.to_string().len());
+    let bar = style.paint(BLUE, "|");
+
+    // each file gets its own snippet, in the order they first come up
+    let mut shown: Vec<&str> = vec![];
+    for (file, first) in &marks {
+        if shown.contains(&file.name.as_str()) { continue }
+        shown.push(&file.name);
+
+        let (line, col) = file.line_col(first.at.start);
+        writeln!(out, "{}{} {}:{}:{}", gutter, style.paint(BLUE, "-->"), file.name, line, col).unwrap();
+        writeln!(out, "{} {}", gutter, bar).unwrap();
+
+        // a span over several lines shows its first and last
+        let here: Vec<&Mark> = marks.iter().filter(|(f, _)| f.name == file.name).map(|(_, m)| m).collect();
+        let ends = |m: &Mark| [file.line_col(m.at.start).0, file.line_col(m.at.end).0];
+        let mut lines: Vec<usize> = here.iter().flat_map(|m| ends(m)).collect();
+        lines.sort_unstable();
+        lines.dedup();
+        for line in lines {
+            let text = file.line_text(line);
+            writeln!(out, "{} {} {}", style.paint(BLUE, &format!("{:>1$}", line, gutter.len())), bar, text).unwrap();
+            for mark in here.iter().filter(|m| ends(m).contains(&line)) {
+                writeln!(out, "{} {} {}", gutter, bar, underline(file, line, mark, style)).unwrap();
+            }
+        }
+    }
+
+    for note in &diagnostic.notes {
+        writeln!(out, "{} {} note: {}", gutter, style.paint(BLUE, "="), note).unwrap();
+    }
+    out
+}
+
+// line is the first or last line of the span. the first of several ends in ..., and the label goes on the last
+fn underline(file: &SourceFile, line: usize, mark: &Mark, style: Style) -> String {
+    let text = file.line_text(line);
+    let (first, start) = file.line_col(mark.at.start);
+    let (last, end) = file.line_col(mark.at.end);
+    let start = if first == line { start - 1 } else { text.chars().take_while(|c| c.is_whitespace()).count() };
+    let end = if last == line { end - 1 } else { text.chars().count() };
+
+    // tabs stay tabs, so the underline lines up however wide the terminal makes them
+    let indent: String = text.chars().take(start).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let (marker, color) = if mark.primary { ("^", RED) } else { ("-", BLUE) };
+    let underline = marker.repeat(end.saturating_sub(start).max(1));
+    let underline = if last != line { format!("{} ...", underline) }
+        else if mark.label.is_empty() { underline }
+        else { format!("{} {}", underline, mark.label) };
+    format!("{}{}", indent, style.paint(color, &underline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "def f() {\n    print(@x)\n}\n\n\tf(1,\n\t  2)\n";
+
+    fn at(start: usize, end: usize) -> Located<()> {
+        Located { value: (), start, end }
+    }
+
+    fn plain(diagnostic: &Diagnostic) -> String {
+        render(diagnostic, &[&SourceFile::new("test.kupo", SOURCE)], Style::Plain)
+    }
+
+    #[test]
+    fn one_line() {
+        let mut diagnostic = Diagnostic::error("no variable named @x", Some(at(20, 22))).note("it has to be assigned first");
+        diagnostic.code = Some("C0001");
+        assert_eq!(plain(&diagnostic), "\
+error[C0001]: no variable named @x
+ --> test.kupo:2:11
+  |
+2 |     print(@x)
+  |           ^^
+  = note: it has to be assigned first
+");
+        let diagnostic = Diagnostic::error("wrong", Some(at(20, 22))).label(at(4, 5), "f is here");
+        assert_eq!(plain(&diagnostic), "\
+error: wrong
+ --> test.kupo:2:11
+  |
+1 | def f() {
+  |     - f is here
+2 |     print(@x)
+  |           ^^
+");
+    }
+
+    #[test]
+    fn several_lines() {
+        // tabs stay tabs, the last line starts after its indent, and the label goes there
+        let diagnostic = Diagnostic::error("wrong", None).label(at(28, 38), "this call");
+        assert_eq!(plain(&diagnostic), "\
+error: wrong
+ --> test.kupo:5:2
+  |
+5 | \tf(1,
+  | \t---- ...
+6 | \t  2)
+  | \t  -- this call
+");
+    }
+
+    #[test]
+    fn elsewhere() {
+        // nothing to show for a location that isn't in any of the files
+        let diagnostic = Diagnostic::error("wrong", Some(at(500, 501)));
+        assert_eq!(plain(&diagnostic), "error: wrong\n");
+    }
+}
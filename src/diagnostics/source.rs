@@ -0,0 +1,72 @@
+use crate::frontend::Located;
+
+// turns byte offsets into lines and columns. base is where the text starts (the REPL's input comes after the module)
+pub struct SourceFile {
+    pub name: String,
+    text: String,
+    base: usize,
+    line_starts: Vec<usize>,  // byte offset into text of each line
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> SourceFile {
+        SourceFile::at(name, text, 0)
+    }
+
+    pub fn at(name: &str, text: &str, base: usize) -> SourceFile {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        SourceFile { name: name.to_string(), text: text.to_string(), base, line_starts }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // the end counts, because that's where errors about running out of input go
+    pub fn contains(&self, offset: usize) -> bool {
+        self.base <= offset && offset <= self.base + self.text.len()
+    }
+
+    // a location as a byte offset into text
+    pub fn local(&self, offset: usize) -> usize {
+        self.index(offset)
+    }
+
+    // both count from 1, and columns count characters, not bytes
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = self.index(offset);
+        let line = self.line_starts.partition_point(|s| *s <= offset) - 1;
+        let col = self.text[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    // without its line ending
+    pub fn line_text(&self, line: usize) -> &str {
+        let span = self.line_span(line).unwrap();
+        &self.text[span.start - self.base..span.end - self.base]
+    }
+
+    // the locations a line covers, counting from 1
+    pub fn line_span(&self, line: usize) -> Option<Located<()>> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map_or(self.text.len(), |next| next - 1);
+        let end = if self.text[start..end].ends_with('\r') { end - 1 } else { end };
+        Some(Located { value: (), start: self.base + start, end: self.base + end })
+    }
+
+    // without the whitespace locations run on with, up to the next token
+    pub fn trim(&self, at: Located<()>) -> Located<()> {
+        let (start, end) = (self.index(at.start), self.index(at.end));
+        if start >= end { return at }
+        let trimmed = self.text[start..end].trim_end().len();
+        Located { value: (), start: at.start, end: at.start + trimmed.max(1) }
+    }
+
+    // where offset lands in text, backed off onto a character boundary
+    fn index(&self, offset: usize) -> usize {
+        let mut index = offset.saturating_sub(self.base).min(self.text.len());
+        while !self.text.is_char_boundary(index) { index -= 1 }
+        index
+    }
+}
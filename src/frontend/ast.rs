@@ -8,7 +8,19 @@ use super::parser::internal_ast::{self, KupoParseError};
 // == structural ==
 #[derive(Debug)]
 pub struct Module {
-    items: Vec<Located<Item>>
+    pub items: Vec<Located<Item>>
+}
+
+impl Module {
+    // the def or view with that name
+    pub fn item(&self, name: &str) -> Option<&Located<Item>> {
+        self.items.iter().find(|i| i.value.name().value == name)
+    }
+
+    // None if there's no such item, or it doesn't have a doc comment
+    pub fn docs(&self, name: &str) -> Option<&str> {
+        self.item(name)?.value.docs().map(|d| d.value.as_str())
+    }
 }
 
 #[derive(Debug)]
@@ -17,51 +29,69 @@ pub enum Item {
     View(View),
 }
 
+impl Item {
+    pub fn name(&self) -> &Located<String> {
+        match self {
+            Item::Def(d) => &d.name,
+            Item::View(v) => &v.name,
+        }
+    }
+
+    pub fn docs(&self) -> Option<&Located<String>> {
+        match self {
+            Item::Def(d) => d.docs.as_ref(),
+            Item::View(v) => v.docs.as_ref(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Def {
-    name: Located<String>,
-    args: Vec<Located<Arg>>,
-    return_type: Option<Vec<Located<Type>>>,
-    body: Located<Block>,
+    pub docs: Option<Located<String>>,  // from the ## lines right before it
+    pub name: Located<String>,
+    pub args: Vec<Located<Arg>>,
+    pub return_type: Option<Vec<Located<Type>>>,
+    pub body: Located<Block>,
 }
 
 #[derive(Debug)]
 pub struct View {
-    name: Located<String>,
-    args: Vec<Located<Arg>>,
-    clauses: Vec<Located<QueryExpression>>
+    pub docs: Option<Located<String>>,
+    pub name: Located<String>,
+    pub args: Vec<Located<Arg>>,
+    pub clauses: Vec<Located<QueryExpression>>
 }
 
 #[derive(Debug)]
 pub struct Arg {
-    name: Located<String>,
-    type_name: Option<Located<Type>>,
+    pub name: Located<String>,
+    pub type_name: Option<Located<Type>>,
 }
 
 #[derive(Debug)]
 pub struct Type {
-    name: Located<String>,
+    pub name: Located<String>,
 }
 
 // == statement ==
 #[derive(Debug)]
 pub struct Block {
-    items: Vec<Located<Statement>>,
+    pub items: Vec<Located<Statement>>,
 }
 
 #[derive(Debug)]
 pub enum Statement {
     For {
-        arg: Located<QueryExpression>,
+        clauses: Vec<Located<QueryExpression>>,  // joined by or
         body: Located<Block>,
     },
     If { 
-        arg: Located<QueryExpression>,
+        clauses: Vec<Located<QueryExpression>>,
         body: Located<Block>,
         else_: Option<Located<Block>>,
     },
     Return { 
-        arg: Located<Expression>,
+        args: Located<AssignTarget>,
     },
     Call {
         call: Located<Call>
@@ -72,17 +102,42 @@ pub enum Statement {
 
         arg: Located<Expression>,
     },
+    Write {
+        op: Located<Write>,
+        args: Located<AssignTarget>,  // the row, or just the key for expunge
+        table: Located<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Write { Insert, Remove, Expunge }
+
+impl Write {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Write::Insert => "insert",
+            Write::Remove => "remove",
+            Write::Expunge => "expunge",
+        }
+    }
+
+    pub fn preposition(&self) -> &'static str {
+        match self {
+            Write::Insert => "into",
+            Write::Remove | Write::Expunge => "from",
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct AssignTarget {
-    args: Vec<Located<Expression>>,
+    pub args: Vec<Located<Expression>>,
 }
 
 // == query expression ==
 #[derive(Debug)]
 pub struct QueryExpression {
-    items: Vec<Located<QueryGoal>>
+    pub items: Vec<Located<QueryGoal>>
 }
 
 #[derive(Debug)]
@@ -95,6 +150,36 @@ pub enum QueryGoal {
         args: Located<AssignTarget>,
         expression: Located<Expression>
     },
+    Filter {
+        condition: Located<Expression>
+    },
+    Aggregate {
+        args: Located<AssignTarget>,
+        op: Located<Aggregate>,
+        value: Located<Expression>,
+        body: Located<QueryExpression>,
+    },
+    Not {
+        body: Located<QueryExpression>
+    },
+    Disjunction {
+        clauses: Vec<Located<QueryExpression>>
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate { Count, Sum, Min, Max, Collect }
+
+impl Aggregate {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Collect => "collect",
+        }
+    }
 }
 
 // == expression ==
@@ -102,6 +187,7 @@ pub enum QueryGoal {
 pub enum Expression {
     StringLiteral { it: String },
     IntegerLiteral { it: u64 },
+    Variable { name: String },
     Call { 
         call: Located<Call>
     },
@@ -116,16 +202,41 @@ pub enum Expression {
     }
 }
 
+impl Expression {
+    // every variable it uses, once each, in the order they come up
+    pub fn variables(&self) -> Vec<&str> {
+        fn walk<'a>(e: &'a Expression, out: &mut Vec<&'a str>) {
+            match e {
+                Expression::StringLiteral { .. } | Expression::IntegerLiteral { .. } => {}
+                Expression::Variable { name } => if !out.contains(&name.as_str()) { out.push(name) },
+                Expression::Call { call } => for arg in &call.value.args { walk(&arg.value, out) },
+                Expression::UOp { arg, .. } => walk(&arg.value, out),
+                Expression::BinOp { arg1, arg2, .. } => {
+                    walk(&arg1.value, out);
+                    walk(&arg2.value, out);
+                }
+            }
+        }
+        let mut out = vec![];
+        walk(self, &mut out);
+        out
+    }
+}
+
 #[derive(Debug)]
-pub enum UOp { Negate, Plus }
+pub enum UOp { Negate, Plus, Not }
 
 #[derive(Debug)]
-pub enum BinOp { Add, Subtract, Multiply, Divide }
+pub enum BinOp { 
+    Add, Subtract, Multiply, Divide,
+    Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual,
+    And, Or,
+}
 
 #[derive(Debug)]
 pub struct Call {
-    name: Located<String>,
-    args: Vec<Located<Expression>>,
+    pub name: Located<String>,
+    pub args: Vec<Located<Expression>>,
 }
 
 // == convert internal to external ast ==
@@ -213,6 +324,14 @@ pub fn simplify_module(it: Located<internal_ast::ASTModule>) -> Result<Located<M
     _simplify_module(it).to_result()
 }
 
+pub fn simplify_block(it: Located<internal_ast::ASTBlock>) -> Result<Located<Block>, Errors> {
+    _simplify_block(it).to_result()
+}
+
+pub fn simplify_query_expression(it: Located<internal_ast::ASTQueryExpression>) -> Result<Located<QueryExpression>, Errors> {
+    _simplify_query_expression(it).to_result()
+}
+
 fn _simplify_module(it: Located<internal_ast::ASTModule>) -> Simp<Located<Module>> {
     let loc = it.location();
     match it.value {
@@ -241,7 +360,7 @@ fn _simplify_item(it: Located<internal_ast::ASTItem>) -> Simp<Located<Item>> {
 fn _simplify_def(it: Located<internal_ast::ASTDef>) -> Simp<Located<Def>> { 
     let loc = it.location();
     match it.value {
-        internal_ast::ASTDef::Def { name, args, return_type, body } => {
+        internal_ast::ASTDef::Def { docs, name, args, return_type, body } => {
             Simp::tup4(
                 Simp::new(name),
                 _simplify_args(args),
@@ -249,7 +368,7 @@ fn _simplify_def(it: Located<internal_ast::ASTDef>) -> Simp<Located<Def>> {
                 _simplify_block(body)
             ).simpmap(
                 |(name, args, return_type, body)|
-                loc.replace(Def { name, args, return_type, body})
+                loc.replace(Def { docs, name, args, return_type, body})
             )
         }
         internal_ast::ASTDef::Invalid(e) => Simp::fail(loc.replace(e))
@@ -259,14 +378,14 @@ fn _simplify_def(it: Located<internal_ast::ASTDef>) -> Simp<Located<Def>> {
 fn _simplify_view(it: Located<internal_ast::ASTView>) -> Simp<Located<View>> { // not located; the enclosing item is
     let loc = it.location();
     match it.value {
-        internal_ast::ASTView::View { name, args, clauses } => {
+        internal_ast::ASTView::View { docs, name, args, clauses } => {
             Simp::tup3(
                 Simp::new(name),
                 _simplify_args(args),
                 Simp::concat(clauses.into_iter().map(_simplify_query_expression))
             ).simpmap(
                 |(name, args, clauses)|
-                loc.replace(View { name, args, clauses})
+                loc.replace(View { docs, name, args, clauses})
             )
         }
         internal_ast::ASTView::Invalid(e) => Simp::fail(loc.replace(e))
@@ -328,22 +447,25 @@ fn _simplify_block(it: Located<internal_ast::ASTBlock>) -> Simp<Located<Block>>
 fn _simplify_statement(it: Located<internal_ast::ASTStatement>) -> Simp<Located<Statement>> {
     let loc = it.location();
     match it.value {
-        internal_ast::ASTStatement::For { arg, body } => 
-            Simp::tup2(_simplify_query_expression(arg), _simplify_block(body)).simpmap(
-                |(arg, body)| loc.replace(Statement::For { arg, body })
+        internal_ast::ASTStatement::For { clauses, body } => 
+            Simp::tup2(
+                Simp::concat(clauses.into_iter().map(_simplify_query_expression)), 
+                _simplify_block(body),
+            ).simpmap(
+                |(clauses, body)| loc.replace(Statement::For { clauses, body })
             ),
-        internal_ast::ASTStatement::If { arg, body, else_ } => 
+        internal_ast::ASTStatement::If { clauses, body, else_ } => 
             Simp::tup3(
-                _simplify_query_expression(arg), 
+                Simp::concat(clauses.into_iter().map(_simplify_query_expression)), 
                 _simplify_block(body),
                 Simp::new_with_optional(else_, _simplify_block),
             ).simpmap(
-                |(arg, body, else_)|
-                loc.replace(Statement::If { arg, body, else_})
+                |(clauses, body, else_)|
+                loc.replace(Statement::If { clauses, body, else_})
             ),
-        internal_ast::ASTStatement::Return { arg } =>
-            _simplify_expression(arg).simpmap(|arg| 
-                loc.replace(Statement::Return { arg })
+        internal_ast::ASTStatement::Return { args } =>
+            _simplify_assign_target(args).simpmap(|args| 
+                loc.replace(Statement::Return { args })
             ),
         internal_ast::ASTStatement::Call { call } => 
             _simplify_call(call).simpmap(|call| 
@@ -357,6 +479,10 @@ fn _simplify_statement(it: Located<internal_ast::ASTStatement>) -> Simp<Located<
                 |(variable, arg)|
                 loc.replace(Statement::Assign { first, variable, arg })
             ),
+        internal_ast::ASTStatement::Write { op, args, table } =>
+            _simplify_assign_target(args).simpmap(|args|
+                loc.replace(Statement::Write { op: op.locmap(_simplify_write), args, table })
+            ),
         internal_ast::ASTStatement::Invalid(e) => Simp::fail(loc.replace(e)),
     }
 }
@@ -397,10 +523,32 @@ fn _simplify_query_goal(it: Located<internal_ast::ASTQueryGoal>) -> Simp<Located
                     ).simpmap(|(args, expression)| 
                         loc.replace(QueryGoal::Assign { args, expression })
                     ),
+                internal_ast::ASTQueryGoalSource::Aggregate { op, value, body } => 
+                    Simp::tup3(
+                        _simplify_assign_target(args),
+                        _simplify_expression(value),
+                        _simplify_query_expression(body),
+                    ).simpmap(|(args, value, body)| 
+                        loc.replace(QueryGoal::Aggregate { args, op: op.locmap(_simplify_aggregate), value, body })
+                    ),
                 internal_ast::ASTQueryGoalSource::Invalid(e) => 
                     Simp::fail(loc_source.replace(e))
             }
         }
+        internal_ast::ASTQueryGoal::Filter { condition } => 
+            _simplify_expression(condition).simpmap(|condition|
+                loc.replace(QueryGoal::Filter { condition })
+            ),
+        internal_ast::ASTQueryGoal::Not { body } => 
+            _simplify_query_expression(body).simpmap(|body|
+                loc.replace(QueryGoal::Not { body })
+            ),
+        internal_ast::ASTQueryGoal::Disjunction { clauses } => 
+            Simp::concat(clauses.into_iter().map(_simplify_query_expression)).simpmap(|clauses|
+                loc.replace(QueryGoal::Disjunction { clauses })
+            ),
+        internal_ast::ASTQueryGoal::Invalid(e) => 
+            Simp::fail(loc.replace(e)),
     }
 }
 
@@ -424,8 +572,12 @@ fn _simplify_expression(it: Located<internal_ast::ASTExpression>) -> Simp<Locate
             Simp::new(loc.replace(Expression::StringLiteral { it })),
         internal_ast::ASTExpression::IntegerLiteral { it } => 
             Simp::new(loc.replace(Expression::IntegerLiteral { it })),
+        internal_ast::ASTExpression::Variable { name } => 
+            Simp::new(loc.replace(Expression::Variable { name })),
         internal_ast::ASTExpression::Call { call } => 
             _simplify_call(call).simpmap(|call| loc.replace(Expression::Call { call })),
+        internal_ast::ASTExpression::Parens { arg } => 
+            _simplify_expression(*arg),
         internal_ast::ASTExpression::UOp { op, arg } => 
             _simplify_expression(*arg).simpmap(|arg|
                 loc.replace(Expression::UOp { 
@@ -469,10 +621,29 @@ fn _simplify_callargs(it: Located<internal_ast::ASTCallArgs>) -> Simp<Vec<Locate
     }
 }
 
+fn _simplify_aggregate(it: internal_ast::ASTAggregate) -> Aggregate {
+    match it {
+        internal_ast::ASTAggregate::Count => Aggregate::Count,
+        internal_ast::ASTAggregate::Sum => Aggregate::Sum,
+        internal_ast::ASTAggregate::Min => Aggregate::Min,
+        internal_ast::ASTAggregate::Max => Aggregate::Max,
+        internal_ast::ASTAggregate::Collect => Aggregate::Collect,
+    }
+}
+
+fn _simplify_write(it: internal_ast::ASTWrite) -> Write {
+    match it {
+        internal_ast::ASTWrite::Insert => Write::Insert,
+        internal_ast::ASTWrite::Remove => Write::Remove,
+        internal_ast::ASTWrite::Expunge => Write::Expunge,
+    }
+}
+
 fn _simplify_uop(it: internal_ast::ASTUOp) -> UOp {
     match it {
         internal_ast::ASTUOp::Negate => UOp::Negate,
         internal_ast::ASTUOp::Plus => UOp::Plus,
+        internal_ast::ASTUOp::Not => UOp::Not,
     }
 }
 
@@ -482,5 +653,48 @@ fn _simplify_binop(it: internal_ast::ASTBinOp) -> BinOp {
         internal_ast::ASTBinOp::Subtract => BinOp::Subtract,
         internal_ast::ASTBinOp::Multiply => BinOp::Multiply,
         internal_ast::ASTBinOp::Divide => BinOp::Divide,
+        internal_ast::ASTBinOp::Equal => BinOp::Equal,
+        internal_ast::ASTBinOp::NotEqual => BinOp::NotEqual,
+        internal_ast::ASTBinOp::Less => BinOp::Less,
+        internal_ast::ASTBinOp::LessEqual => BinOp::LessEqual,
+        internal_ast::ASTBinOp::Greater => BinOp::Greater,
+        internal_ast::ASTBinOp::GreaterEqual => BinOp::GreaterEqual,
+        internal_ast::ASTBinOp::And => BinOp::And,
+        internal_ast::ASTBinOp::Or => BinOp::Or,
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::parse_module;
+
+    const TEXT: &str = "\
+## Vampires who live alone.
+##   indented
+##
+## after a blank one
+view [@x NPC] in lonely_vampire {
+    ## not for anything
+    @x in vampire,
+}
+
+## cut off
+# by a plain comment
+def main() {
+    print(1)
+}
+
+##no space
+def other() {}
+";
+
+    #[test]
+    fn docs() {
+        let module = parse_module(TEXT).unwrap().value;
+        assert_eq!(module.docs("lonely_vampire"), Some("Vampires who live alone.\n  indented\n\nafter a blank one"));
+        assert_eq!(module.docs("main"), None);
+        assert_eq!(module.docs("other"), Some("no space"));
+        assert!(module.item("main").is_some());
+        assert!(module.item("nope").is_none() && module.docs("nope").is_none());
+    }
+}
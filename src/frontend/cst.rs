@@ -0,0 +1,432 @@
+use std::fmt;
+
+use super::lexer::{self, LosslessToken, Token};
+use super::located::Located;
+use super::parser::{self, internal_ast::*};
+
+// every token, trivia and all, in internal_ast's shape: printing it gives the source back byte-for-byte.
+// whatever didn't parse is an Error node
+pub struct SyntaxTree {
+    source: String,
+    pub root: Located<SyntaxNode>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+    // == structural ==
+    Module, Def, View, Args, Arg, Types, Type,
+    // == statement ==
+    Block, For, If, Return, CallStatement, Assign, Write,
+    // == query expression ==
+    QueryExpression, Goal, Filter, Not, Disjunction, AssignTarget, GoalSource, Aggregate,
+    // == expression ==
+    Literal, Variable, CallExpression, Parens, UOp, BinOp, Call, CallArgs,
+    Error,
+}
+
+// like every other location, a node's runs up to the token after it
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+pub enum SyntaxElement {
+    Node(Located<SyntaxNode>),
+    Token(LosslessToken),
+}
+
+pub fn parse_syntax_tree(s: &str) -> SyntaxTree {
+    let tokens = lexer::lex_lossless(s);
+    let (eof, ts) = tokens.split_last().unwrap();
+    let ts: Vec<Located<Token>> = ts.iter().map(|t| t.token.clone()).collect();
+    let module = parser::parse_module(&ts, eof.token.location(), &[]);
+
+    let mut tokens = tokens.into_iter().peekable();
+    let mut root = node(&mut tokens, child(&module));
+    // EOF, and anything the module didn't take
+    root.value.children.extend(tokens.map(SyntaxElement::Token));
+
+    let tree = SyntaxTree { source: s.to_string(), root };
+    debug_assert_eq!(tree.to_string(), s);
+    tree
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<LosslessToken>>;
+
+// a node gets every token that starts inside it and isn't in one of its children
+fn node(tokens: &mut Tokens, syntax: Located<&dyn Syntax>) -> Located<SyntaxNode> {
+    let mut children = vec![];
+    for c in syntax.value.children() {
+        take_tokens(tokens, c.start, &mut children);
+        children.push(SyntaxElement::Node(node(tokens, c)));
+    }
+    take_tokens(tokens, syntax.end, &mut children);
+    syntax.replace(SyntaxNode { kind: syntax.value.kind(), children })
+}
+
+fn take_tokens(tokens: &mut Tokens, before: usize, into: &mut Vec<SyntaxElement>) {
+    while let Some(t) = tokens.next_if(|t| t.token.start < before && t.token.value != Token::EOF) {
+        into.push(SyntaxElement::Token(t))
+    }
+}
+
+impl SyntaxTree {
+    fn text<T>(&self, at: &Located<T>) -> &str {
+        &self.source[at.start..at.end]
+    }
+
+    // one line per node, token and piece of trivia, indented by depth
+    pub fn outline(&self) -> String {
+        let mut out = String::new();
+        self.outline_node(&self.root, 0, &mut out);
+        out
+    }
+
+    fn outline_node(&self, node: &Located<SyntaxNode>, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&format!("{}{:?} {}..{}\n", indent, node.value.kind, node.start, node.end));
+        for child in &node.value.children {
+            match child {
+                SyntaxElement::Node(n) => self.outline_node(n, depth + 1, out),
+                SyntaxElement::Token(t) => {
+                    for trivia in &t.leading {
+                        out.push_str(&format!("{}  {:?} {:?}\n", indent, trivia.value, self.text(trivia)));
+                    }
+                    out.push_str(&format!("{}  {:?} {:?}\n", indent, t.token.value, self.text(&t.token)));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write(tree: &SyntaxTree, node: &SyntaxNode, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for child in &node.children {
+                match child {
+                    SyntaxElement::Node(n) => write(tree, &n.value, f)?,
+                    SyntaxElement::Token(t) => {
+                        for trivia in &t.leading { f.write_str(tree.text(trivia))? }
+                        f.write_str(tree.text(&t.token))?
+                    }
+                }
+            }
+            Ok(())
+        }
+        write(self, &self.root.value, f)
+    }
+}
+
+// == the shape of the internal AST ==
+trait Syntax {
+    fn kind(&self) -> SyntaxKind;
+    // in source order
+    fn children(&self) -> Vec<Located<&dyn Syntax>>;
+}
+
+fn child<T: Syntax>(t: &Located<T>) -> Located<&dyn Syntax> {
+    t.replace(&t.value as &dyn Syntax)
+}
+
+impl Syntax for ASTModule {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTModule::Module { .. } => SyntaxKind::Module, ASTModule::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTModule::Module { items } => items.iter().map(child).collect(),
+            ASTModule::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTItem {
+    fn kind(&self) -> SyntaxKind {
+        match self {
+            ASTItem::Def(d) => d.kind(),
+            ASTItem::View(v) => v.kind(),
+            ASTItem::Invalid(_) => SyntaxKind::Error,
+        }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTItem::Def(d) => d.children(),
+            ASTItem::View(v) => v.children(),
+            ASTItem::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTDef {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTDef::Def { .. } => SyntaxKind::Def, ASTDef::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTDef::Def { docs: _, name: _, args, return_type, body } => {
+                let mut children = vec![child(args)];
+                children.extend(return_type.iter().map(child));
+                children.push(child(body));
+                children
+            }
+            ASTDef::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTView {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTView::View { .. } => SyntaxKind::View, ASTView::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTView::View { docs: _, name: _, args, clauses } => {
+                let mut children = vec![child(args)];
+                children.extend(clauses.iter().map(child));
+                children
+            }
+            ASTView::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTArgs {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTArgs::Args { .. } => SyntaxKind::Args, ASTArgs::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTArgs::Args { args } => args.iter().map(child).collect(),
+            ASTArgs::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTTypes {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTTypes::Types { .. } => SyntaxKind::Types, ASTTypes::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTTypes::Types { types } => types.iter().map(child).collect(),
+            ASTTypes::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTArg {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTArg::Arg { .. } => SyntaxKind::Arg, ASTArg::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTArg::Arg { name: _, type_name } => type_name.iter().map(child).collect(),
+            ASTArg::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTType {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTType::Type { .. } => SyntaxKind::Type, ASTType::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        vec![]
+    }
+}
+
+impl Syntax for ASTBlock {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTBlock::Block { .. } => SyntaxKind::Block, ASTBlock::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTBlock::Block { items } => items.iter().map(child).collect(),
+            ASTBlock::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTStatement {
+    fn kind(&self) -> SyntaxKind {
+        match self {
+            ASTStatement::For { .. } => SyntaxKind::For,
+            ASTStatement::If { .. } => SyntaxKind::If,
+            ASTStatement::Return { .. } => SyntaxKind::Return,
+            ASTStatement::Call { .. } => SyntaxKind::CallStatement,
+            ASTStatement::Assign { .. } => SyntaxKind::Assign,
+            ASTStatement::Write { .. } => SyntaxKind::Write,
+            ASTStatement::Invalid(_) => SyntaxKind::Error,
+        }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTStatement::For { clauses, body } => {
+                let mut children: Vec<_> = clauses.iter().map(child).collect();
+                children.push(child(body));
+                children
+            }
+            ASTStatement::If { clauses, body, else_ } => {
+                let mut children: Vec<_> = clauses.iter().map(child).collect();
+                children.push(child(body));
+                children.extend(else_.iter().map(child));
+                children
+            }
+            ASTStatement::Return { args } => vec![child(args)],
+            ASTStatement::Call { call } => vec![child(call)],
+            ASTStatement::Assign { first: _, variable, arg } => vec![child(variable), child(arg)],
+            ASTStatement::Write { args, .. } => vec![child(args)],
+            ASTStatement::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTQueryExpression {
+    fn kind(&self) -> SyntaxKind {
+        match self {
+            ASTQueryExpression::QExpression { .. } => SyntaxKind::QueryExpression,
+            ASTQueryExpression::Invalid(_) => SyntaxKind::Error,
+        }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTQueryExpression::QExpression { items } => items.iter().map(child).collect(),
+            ASTQueryExpression::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTQueryGoal {
+    fn kind(&self) -> SyntaxKind {
+        match self { 
+            ASTQueryGoal::Goal { .. } => SyntaxKind::Goal, 
+            ASTQueryGoal::Filter { .. } => SyntaxKind::Filter, 
+            ASTQueryGoal::Not { .. } => SyntaxKind::Not,
+            ASTQueryGoal::Disjunction { .. } => SyntaxKind::Disjunction,
+            ASTQueryGoal::Invalid(_) => SyntaxKind::Error,
+        }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTQueryGoal::Goal { args, source } => vec![child(args), child(source)],
+            ASTQueryGoal::Filter { condition } => vec![child(condition)],
+            ASTQueryGoal::Not { body } => vec![child(body)],
+            ASTQueryGoal::Disjunction { clauses } => clauses.iter().map(child).collect(),
+            ASTQueryGoal::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTAssignTarget {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTAssignTarget::Target { .. } => SyntaxKind::AssignTarget, ASTAssignTarget::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTAssignTarget::Target { args } => args.iter().map(child).collect(),
+            ASTAssignTarget::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTQueryGoalSource {
+    fn kind(&self) -> SyntaxKind {
+        match self {
+            ASTQueryGoalSource::In { .. } | ASTQueryGoalSource::Assign { .. } => SyntaxKind::GoalSource,
+            ASTQueryGoalSource::Aggregate { .. } => SyntaxKind::Aggregate,
+            ASTQueryGoalSource::Invalid(_) => SyntaxKind::Error,
+        }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTQueryGoalSource::Assign { expression } => vec![child(expression)],
+            ASTQueryGoalSource::Aggregate { value, body, .. } => vec![child(value), child(body)],
+            ASTQueryGoalSource::In { .. } | ASTQueryGoalSource::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTExpression {
+    fn kind(&self) -> SyntaxKind {
+        match self {
+            ASTExpression::StringLiteral { .. } | ASTExpression::IntegerLiteral { .. } => SyntaxKind::Literal,
+            ASTExpression::Variable { .. } => SyntaxKind::Variable,
+            ASTExpression::Call { .. } => SyntaxKind::CallExpression,
+            ASTExpression::Parens { .. } => SyntaxKind::Parens,
+            ASTExpression::UOp { .. } => SyntaxKind::UOp,
+            ASTExpression::BinOp { .. } => SyntaxKind::BinOp,
+            ASTExpression::Invalid(_) => SyntaxKind::Error,
+        }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTExpression::Call { call } => vec![child(call)],
+            ASTExpression::Parens { arg } | ASTExpression::UOp { op: _, arg } => vec![child(arg)],
+            ASTExpression::BinOp { arg1, op: _, arg2 } => vec![child(arg1), child(arg2)],
+            _ => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTCall {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTCall::Call { .. } => SyntaxKind::Call, ASTCall::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTCall::Call { name: _, args } => vec![child(args)],
+            ASTCall::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl Syntax for ASTCallArgs {
+    fn kind(&self) -> SyntaxKind {
+        match self { ASTCallArgs::Args { .. } => SyntaxKind::CallArgs, ASTCallArgs::Invalid(_) => SyntaxKind::Error }
+    }
+
+    fn children(&self) -> Vec<Located<&dyn Syntax>> {
+        match self {
+            ASTCallArgs::Args { args } => args.iter().map(child).collect(),
+            ASTCallArgs::Invalid(_) => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_syntax_tree;
+
+    #[test]
+    fn round_trip() {
+        let sources = [
+            include_str!("../../samplecode/vampires.kupo"),
+            "",
+            "  # just a comment",
+            "def main() {\n    print((1 - 2\n}\n",
+            "view [@x NPC in lonely {\n    @x in vampire,,\n}\nor\n",
+            "def f(@a Integer) { return @a + }\n## docs for nothing",
+            "}}} def ] \"unterminated",
+            "def main() { for [@x, @y] in { @x in } { print(@x) } else }\n",
+        ];
+        for s in sources {
+            assert_eq!(parse_syntax_tree(s).to_string(), s);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use super::ast::{self, Expression, QueryGoal, Statement};
+use super::lexer::{self, Token};
+use super::located::Located;
+use super::parser::{self, internal_ast::{ASTBinOp, KupoParseError}};
+
+// kupo fmt: the layout only depends on the AST, so it's idempotent.
+// only comments and (single) blank lines are kept from the source
+pub fn format_module(s: &str) -> Result<String, Vec<Located<KupoParseError>>> {
+    let (tokens, eof, comments) = lexer::lex_with_comments(s);
+    let module = ast::simplify_module(parser::parse_module(&tokens, eof, &[]))?;
+
+    let mut printer = Printer { source: s, tokens: &tokens, comments: &comments, next_comment: 0, lines: vec![], indent: 0, blank: Blank::Source };
+    printer.module(&module.value);
+    let mut out = printer.lines.join("\n");
+    if !out.is_empty() { out.push('\n') }
+    Ok(out)
+}
+
+#[derive(Clone, Copy)]
+enum Blank {
+    Source,  // if there was one in the source
+    Never,  // right after an opening brace
+    Always,  // between items
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    tokens: &'a [Located<Token>],
+    comments: &'a [Located<()>],
+    next_comment: usize,  // everything before this has been printed
+    lines: Vec<String>,
+    indent: usize,
+    blank: Blank,  // what to do about a blank line before the next line
+}
+
+impl<'a> Printer<'a> {
+    // == lines ==
+    // at is where the line's contents started in the source: any comments before that come first
+    fn line(&mut self, at: usize, text: &str) {
+        self.comments_before(at);
+        self.push(at, text);
+    }
+
+    fn push(&mut self, at: usize, text: &str) {
+        if self.wants_blank(at) { self.lines.push(String::new()) }
+        self.lines.push(format!("{}{}", "    ".repeat(self.indent), text));
+        self.blank = Blank::Source;
+    }
+
+    fn wants_blank(&self, at: usize) -> bool {
+        if self.lines.is_empty() { return false }
+        match self.blank {
+            Blank::Source => self.source[self.previous(at)..at].matches('\n').count() >= 2,
+            Blank::Never => false,
+            Blank::Always => true,
+        }
+    }
+
+    fn comments_before(&mut self, at: usize) {
+        let comments = self.comments;
+        while let Some(comment) = comments.get(self.next_comment).filter(|c| c.start < at) {
+            self.next_comment += 1;
+            let text = self.source[comment.start..comment.end].trim_end();
+            let on_a_line_with_code = !self.source[self.previous(comment.start)..comment.start].contains('\n');
+            match self.lines.last_mut() {
+                Some(line) if on_a_line_with_code => { line.push(' '); line.push_str(text) }
+                _ => self.push(comment.start, text),
+            }
+        }
+    }
+
+    // where the last token or comment before at ended
+    fn previous(&self, at: usize) -> usize {
+        let token = self.tokens.partition_point(|t| t.end <= at);
+        let comment = self.comments.partition_point(|c| c.end <= at);
+        let token = token.checked_sub(1).map_or(0, |t| self.tokens[t].end);
+        let comment = comment.checked_sub(1).map_or(0, |c| self.comments[c].end);
+        token.max(comment)
+    }
+
+    // where the brace that ends a group is: its span runs on to the next token
+    fn closing<T>(&self, group: &Located<T>) -> usize {
+        let last = self.tokens.partition_point(|t| t.end <= group.end) - 1;
+        self.tokens[last].start
+    }
+
+    fn open(&mut self, at: usize, text: &str) {
+        self.line(at, text);
+        self.indent += 1;
+        self.blank = Blank::Never;
+    }
+
+    // comments at the end of a group stay inside it
+    fn close(&mut self, at: usize) {
+        self.comments_before(at);
+        self.indent -= 1;
+        self.blank = Blank::Never;
+    }
+
+    // a header and a braced group of lines, or just `{}` if there's nothing to put in it
+    fn braces(&mut self, at: usize, header: &str, group: &Located<()>, empty: bool, inside: impl FnOnce(&mut Self)) {
+        let close = self.closing(group);
+        let comments_inside = self.comments.get(self.next_comment).is_some_and(|c| c.start < close);
+        if empty && !comments_inside {
+            return self.line(at, &format!("{} {{}}", header))
+        }
+        self.open(at, &format!("{} {{", header));
+        inside(self);
+        self.close(close);
+        self.line(close, "}");
+    }
+
+    // == structural ==
+    fn module(&mut self, module: &ast::Module) {
+        for (i, item) in module.items.iter().enumerate() {
+            if i > 0 { self.blank = Blank::Always }
+            match &item.value {
+                ast::Item::Def(def) => self.def(item.start, def),
+                ast::Item::View(view) => self.view(item.start, view),
+            }
+        }
+        self.comments_before(usize::MAX);
+    }
+
+    fn def(&mut self, at: usize, def: &ast::Def) {
+        let mut header = format!("def {}({})", def.name.value, args(&def.args));
+        if let Some(types) = &def.return_type {
+            let types: Vec<&str> = types.iter().map(|t| t.value.name.value.as_str()).collect();
+            header.push(' ');
+            header.push_str(&if types.len() == 1 { types[0].to_string() } else { format!("[{}]", types.join(", ")) });
+        }
+        self.block(at, &header, &def.body);
+    }
+
+    fn view(&mut self, at: usize, view: &ast::View) {
+        for (i, clause) in view.clauses.iter().enumerate() {
+            let header = if i == 0 { format!("view [{}] in {}", args(&view.args), view.name.value) } else { "or".to_string() };
+            let at = if i == 0 { at } else { clause.start };
+            self.braces(at, &header, &clause.location(), clause.value.items.is_empty(), |s| {
+                for goal in &clause.value.items {
+                    s.line(goal.start, &format!("{},", query_goal(&goal.value, false)));
+                }
+            });
+        }
+    }
+
+    // == statement ==
+    fn block(&mut self, at: usize, header: &str, block: &Located<ast::Block>) {
+        self.braces(at, header, &block.location(), block.value.items.is_empty(), |s| {
+            for statement in &block.value.items { s.statement(statement) }
+        })
+    }
+
+    fn statement(&mut self, statement: &Located<Statement>) {
+        let at = statement.start;
+        match &statement.value {
+            Statement::For { clauses, body } => {
+                self.block(at, &format!("for {}", head(clauses)), body)
+            }
+            Statement::If { clauses, body, else_: None } => {
+                self.block(at, &format!("if {}", head(clauses)), body)
+            }
+            // } else { goes on one line, so this can't use block
+            Statement::If { clauses, body, else_: Some(else_) } => {
+                self.open(at, &format!("if {} {{", head(clauses)));
+                for statement in &body.value.items { self.statement(statement) }
+                let close = self.closing(body);
+                self.close(close);
+                self.open(close, "} else {");
+                for statement in &else_.value.items { self.statement(statement) }
+                let close = self.closing(else_);
+                self.close(close);
+                self.line(close, "}");
+            }
+            Statement::Return { args } => self.line(at, &format!("return {}", assign_target(&args.value, expression))),
+            Statement::Call { call } => self.line(at, &self::call(&call.value)),
+            Statement::Assign { first, variable, arg } => {
+                let op = if *first { ":=" } else { "=" };
+                self.line(at, &format!("{} {} {}", assign_target(&variable.value, expression), op, expression(&arg.value)))
+            }
+            Statement::Write { op, args, table } => {
+                self.line(at, &format!("{} {} {} {}", op.value.name(), assign_target(&args.value, expression), op.value.preposition(), table.value))
+            }
+        }
+    }
+}
+
+fn args(args: &[Located<ast::Arg>]) -> String {
+    let args: Vec<String> = args.iter().map(|a| match &a.value.type_name {
+        Some(t) => format!("{} {}", a.value.name.value, t.value.name.value),
+        None => a.value.name.value.clone(),
+    }).collect();
+    args.join(", ")
+}
+
+// == query expression ==
+fn head(clauses: &[Located<ast::QueryExpression>]) -> String {
+    let clauses: Vec<String> = clauses.iter().map(|c| {
+        let goals: Vec<String> = c.value.items.iter().map(|g| query_goal(&g.value, true)).collect();
+        goals.join(", ")
+    }).collect();
+    clauses.join(" or ")
+}
+
+// in a head, or starts the next clause, so a boolean or has to go in parens
+fn query_goal(goal: &QueryGoal, in_head: bool) -> String {
+    let expression = |e: &Expression| match e {
+        Expression::BinOp { op: ast::BinOp::Or, .. } if in_head => format!("({})", expression(e)),
+        _ => expression(e),
+    };
+    match goal {
+        QueryGoal::In { args, from } => format!("{} in {}", assign_target(&args.value, expression), from.value),
+        QueryGoal::Assign { args, expression: e } => format!("{} := {}", assign_target(&args.value, expression), expression(&e.value)),
+        QueryGoal::Filter { condition } => expression(&condition.value),
+        QueryGoal::Aggregate { args, op, value, body } => {
+            let goals: Vec<String> = body.value.items.iter().map(|g| query_goal(&g.value, false)).collect();
+            let value = self::expression(&value.value);
+            format!("{} := {}({} : {})", assign_target(&args.value, expression), op.value.name(), value, goals.join(", "))
+        }
+        // not @x in t is short for not { @x in t }
+        QueryGoal::Not { body } => match &body.value.items[..] {
+            [Located { value: QueryGoal::In { args, from }, .. }] => 
+                format!("not {} in {}", assign_target(&args.value, not_operand), from.value),
+            [Located { value: QueryGoal::Disjunction { .. }, .. }] => 
+                format!("not {}", query_goal(&body.value.items[0].value, false)),
+            _ => format!("not {}", inline_query(&body.value)),
+        },
+        QueryGoal::Disjunction { clauses } => {
+            let clauses: Vec<String> = clauses.iter().map(|c| inline_query(&c.value)).collect();
+            format!("({})", clauses.join(" or "))
+        }
+    }
+}
+
+// in braces, on one line
+fn inline_query(query: &ast::QueryExpression) -> String {
+    let goals: Vec<String> = query.items.iter().map(|g| query_goal(&g.value, false)).collect();
+    if goals.is_empty() { "{}".to_string() } else { format!("{{ {} }}", goals.join(", ")) }
+}
+
+// one thing on its own, or anything else in brackets
+fn assign_target(target: &ast::AssignTarget, expression: impl Fn(&Expression) -> String) -> String {
+    let args: Vec<String> = target.args.iter().map(|a| expression(&a.value)).collect();
+    if args.len() == 1 { args[0].clone() } else { format!("[{}]", args.join(", ")) }
+}
+
+// == expression ==
+fn expression(expression: &Expression) -> String {
+    match expression {
+        Expression::StringLiteral { it } => string_literal(it),
+        Expression::IntegerLiteral { it } => it.to_string(),
+        Expression::Variable { name } => name.clone(),
+        Expression::Call { call: c } => call(&c.value),
+        Expression::UOp { op: ast::UOp::Not, arg } => format!("not {}", not_operand(&arg.value)),
+        Expression::UOp { op, arg } => {
+            let op = match op { ast::UOp::Negate => "-", ast::UOp::Plus => "+", ast::UOp::Not => unreachable!() };
+            match &arg.value {
+                Expression::BinOp { .. } | Expression::UOp { op: ast::UOp::Not, .. } => 
+                    format!("{}({})", op, self::expression(&arg.value)),
+                _ => format!("{}{}", op, self::expression(&arg.value)),
+            }
+        }
+        Expression::BinOp { arg1, op, arg2 } => {
+            let op = internal(op);
+            format!("{} {} {}", operand(&arg1.value, &op, true), symbol(&op), operand(&arg2.value, &op, false))
+        }
+    }
+}
+
+// not takes everything up to the comparisons
+fn not_operand(arg: &Expression) -> String {
+    match arg {
+        Expression::BinOp { op, .. } if internal(op).precedence_level() > NOT_LEVEL => format!("({})", expression(arg)),
+        _ => expression(arg),
+    }
+}
+
+// parens if it binds looser than its parent, or as loosely on the side that doesn't associate
+fn operand(arg: &Expression, parent: &ASTBinOp, left: bool) -> String {
+    let needs_parens = match arg {
+        Expression::BinOp { op, .. } => {
+            let (level, parent_level) = (internal(op).precedence_level(), parent.precedence_level());
+            level > parent_level || (level == parent_level && left != parent.left_associative())
+        }
+        Expression::UOp { op: ast::UOp::Not, .. } => NOT_LEVEL > parent.precedence_level(),
+        _ => false,
+    };
+    if needs_parens { format!("({})", expression(arg)) } else { expression(arg) }
+}
+
+// where not would sit if it were a binop: between the comparisons and and
+const NOT_LEVEL: usize = 3;
+
+fn internal(op: &ast::BinOp) -> ASTBinOp {
+    match op {
+        ast::BinOp::Add => ASTBinOp::Add,
+        ast::BinOp::Subtract => ASTBinOp::Subtract,
+        ast::BinOp::Multiply => ASTBinOp::Multiply,
+        ast::BinOp::Divide => ASTBinOp::Divide,
+        ast::BinOp::Equal => ASTBinOp::Equal,
+        ast::BinOp::NotEqual => ASTBinOp::NotEqual,
+        ast::BinOp::Less => ASTBinOp::Less,
+        ast::BinOp::LessEqual => ASTBinOp::LessEqual,
+        ast::BinOp::Greater => ASTBinOp::Greater,
+        ast::BinOp::GreaterEqual => ASTBinOp::GreaterEqual,
+        ast::BinOp::And => ASTBinOp::And,
+        ast::BinOp::Or => ASTBinOp::Or,
+    }
+}
+
+fn symbol(op: &ASTBinOp) -> &'static str {
+    match op {
+        ASTBinOp::Add => "+",
+        ASTBinOp::Subtract => "-",
+        ASTBinOp::Multiply => "*",
+        ASTBinOp::Divide => "/",
+        ASTBinOp::Equal => "==",
+        ASTBinOp::NotEqual => "!=",
+        ASTBinOp::Less => "<",
+        ASTBinOp::LessEqual => "<=",
+        ASTBinOp::Greater => ">",
+        ASTBinOp::GreaterEqual => ">=",
+        ASTBinOp::And => "and",
+        ASTBinOp::Or => "or",
+    }
+}
+
+fn call(call: &ast::Call) -> String {
+    let args: Vec<String> = call.args.iter().map(|a| expression(&a.value)).collect();
+    format!("{}({})", call.name.value, args.join(", "))
+}
+
+// double quotes, and escapes for anything the lexer wouldn't take back as it is
+fn string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() && (c as u32) < 0x100 => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c if c.is_control() => out.push_str(&format!("\\U{:08x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_module;
+
+    fn fmt(s: &str) -> String {
+        format_module(s).unwrap()
+    }
+
+    #[test]
+    fn idempotent() {
+        let sources = [
+            include_str!("../../samplecode/calls.kupo"),
+            include_str!("../../samplecode/example1.kupo"),
+            include_str!("../../samplecode/hello.kupo"),
+            include_str!("../../samplecode/vampires.kupo"),
+            "def  main( ) {print(((1)))   # hi\n\n\n  if 1 == 1 { print(2) } else {  print(\"a\\\"b\") }}\n",
+        ];
+        for s in sources {
+            let once = fmt(s);
+            assert_eq!(fmt(&once), once);
+        }
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let s = "# before\ndef main() {\n    # first\n    print(1)  # trailing\n}\n# after\n";
+        assert_eq!(fmt(s), "# before\ndef main() {\n    # first\n    print(1) # trailing\n}\n# after\n");
+    }
+
+    #[test]
+    fn minimal_parens() {
+        assert_eq!(fmt("def main() { print((1 - 2) - 3) }"), "def main() {\n    print(1 - 2 - 3)\n}\n");
+        assert_eq!(fmt("def main() { print(1 - (2 - 3)) }"), "def main() {\n    print(1 - (2 - 3))\n}\n");
+        assert_eq!(fmt("def main() { print(((1 * 2)) + 3) }"), "def main() {\n    print(1 * 2 + 3)\n}\n");
+    }
+}
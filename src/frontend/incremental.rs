@@ -0,0 +1,361 @@
+use super::ast;
+use super::lex_at;
+use super::lexer::Token;
+use super::located::Located;
+use super::parser::{self, internal_ast::*};
+
+// re-parses only the items an edit touches (and the one before: a view takes any `or` after it),
+// shifting the rest. if the edit runs into the next item (say, an open brace), it parses everything again
+pub struct IncrementalModule {
+    text: String,
+    items: Vec<Located<ASTItem>>,
+}
+
+impl IncrementalModule {
+    pub fn new(text: &str) -> IncrementalModule {
+        let mut module = IncrementalModule { text: text.to_string(), items: vec![] };
+        module.reparse();
+        module
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // the same thing parse_module would give you for text()
+    pub fn module(&self) -> Result<Located<ast::Module>, Vec<Located<KupoParseError>>> {
+        let start = self.items.first().map_or(self.text.len(), |i| i.start);
+        let items = self.items.clone();
+        ast::simplify_module(Located { value: ASTModule::Module { items }, start, end: self.text.len() })
+    }
+
+    // replaces the bytes from start to end
+    pub fn edit(&mut self, start: usize, end: usize, replacement: &str) {
+        self.text.replace_range(start..end, replacement);
+        let grew = replacement.len() as isize - (end - start) as isize;
+
+        // item spans run up to the next item, so every byte of text is in one (besides leading whitespace)
+        let first = self.items.iter().position(|i| i.end >= start).unwrap_or(self.items.len());
+        let from = first.saturating_sub(1);
+        let to = self.items.iter().position(|i| i.start > end).unwrap_or(self.items.len());
+
+        let dirty_start = if from == 0 { 0 } else { self.items[from].start };
+        let (dirty_end, next) = match self.items.get(to) {
+            Some(Located { value: ASTItem::Def(_), start, .. }) => ((*start as isize + grew) as usize, Some("def")),
+            Some(Located { value: ASTItem::View(_), start, .. }) => ((*start as isize + grew) as usize, Some("view")),
+            Some(_) => return self.reparse(),
+            None => (self.text.len(), None),
+        };
+        // otherwise a comment, string or name at the end could have run on into the next item
+        if dirty_end < self.text.len() && !self.text[..dirty_end].ends_with('\n') {
+            return self.reparse()
+        }
+
+        let (mut ts, mut eof, mut docs) = lex_at(&self.text[dirty_start..dirty_end], dirty_start);
+        // the first item's doc comment is at the end of the item before it
+        if let Some(before) = from.checked_sub(1).map(|i| &self.items[i]) {
+            let (_, _, before_docs) = lex_at(&self.text[before.start..dirty_start], before.start);
+            docs.extend(before_docs.into_iter().filter(|d| d.end == dirty_start));
+        }
+        if let Some(keyword) = next {
+            let token = Located { value: Token::Keyword(keyword.to_string()), start: dirty_end, end: dirty_end + keyword.len() };
+            eof = Located { value: (), start: token.end, end: token.end };
+            ts.push(token);
+        }
+        let mut reparsed = match parser::parse_module(&ts, eof, &docs).value {
+            ASTModule::Module { items } => items,
+            ASTModule::Invalid(_) => unreachable!(),
+        };
+        if next.is_some() {
+            match reparsed.pop() {
+                Some(item) if item.start == dirty_end => {}
+                _ => return self.reparse(),
+            }
+        }
+        let mut after = self.items.split_off(to);
+        for item in &mut after { item.shift(grew) }
+        // the next item's doc comment is at the end of the edited part, so it might have changed
+        if let Some(next_docs) = after.first_mut().and_then(|i| docs_mut(&mut i.value)) {
+            *next_docs = docs.iter().find(|d| d.end == dirty_end).cloned();
+        }
+        self.items.truncate(from);
+        self.items.extend(reparsed);
+        self.items.extend(after);
+    }
+
+    fn reparse(&mut self) {
+        let (ts, eof, docs) = lex_at(&self.text, 0);
+        match parser::parse_module(&ts, eof, &docs).value {
+            ASTModule::Module { items } => self.items = items,
+            // the module ends at EOF, so it can't fail
+            ASTModule::Invalid(_) => unreachable!(),
+        }
+    }
+}
+
+fn docs_mut(item: &mut ASTItem) -> Option<&mut Option<Located<String>>> {
+    match item {
+        ASTItem::Def(ASTDef::Def { docs, .. }) | ASTItem::View(ASTView::View { docs, .. }) => Some(docs),
+        _ => None,
+    }
+}
+
+// moves every location in a tree along by the same amount
+trait Shift {
+    fn shift(&mut self, by: isize);
+}
+
+impl<T: Shift> Shift for Located<T> {
+    fn shift(&mut self, by: isize) {
+        self.start = (self.start as isize + by) as usize;
+        self.end = (self.end as isize + by) as usize;
+        self.value.shift(by)
+    }
+}
+
+impl<T: Shift> Shift for Vec<T> {
+    fn shift(&mut self, by: isize) {
+        for t in self { t.shift(by) }
+    }
+}
+
+impl<T: Shift> Shift for Option<T> {
+    fn shift(&mut self, by: isize) {
+        if let Some(t) = self { t.shift(by) }
+    }
+}
+
+impl<T: Shift> Shift for Box<T> {
+    fn shift(&mut self, by: isize) {
+        (**self).shift(by)
+    }
+}
+
+impl Shift for String {
+    fn shift(&mut self, _: isize) {}
+}
+
+impl Shift for ASTAggregate {
+    fn shift(&mut self, _: isize) {}
+}
+
+impl Shift for ASTWrite {
+    fn shift(&mut self, _: isize) {}
+}
+
+impl Shift for ASTItem {
+    fn shift(&mut self, by: isize) {
+        match self {
+            ASTItem::Def(d) => d.shift(by),
+            ASTItem::View(v) => v.shift(by),
+            ASTItem::Invalid(_) => {}
+        }
+    }
+}
+
+impl Shift for ASTDef {
+    fn shift(&mut self, by: isize) {
+        if let ASTDef::Def { docs, name, args, return_type, body } = self {
+            docs.shift(by);
+            name.shift(by);
+            args.shift(by);
+            return_type.shift(by);
+            body.shift(by);
+        }
+    }
+}
+
+impl Shift for ASTView {
+    fn shift(&mut self, by: isize) {
+        if let ASTView::View { docs, name, args, clauses } = self {
+            docs.shift(by);
+            name.shift(by);
+            args.shift(by);
+            clauses.shift(by);
+        }
+    }
+}
+
+impl Shift for ASTArgs {
+    fn shift(&mut self, by: isize) {
+        if let ASTArgs::Args { args } = self { args.shift(by) }
+    }
+}
+
+impl Shift for ASTTypes {
+    fn shift(&mut self, by: isize) {
+        if let ASTTypes::Types { types } = self { types.shift(by) }
+    }
+}
+
+impl Shift for ASTArg {
+    fn shift(&mut self, by: isize) {
+        if let ASTArg::Arg { name, type_name } = self {
+            name.shift(by);
+            type_name.shift(by);
+        }
+    }
+}
+
+impl Shift for ASTType {
+    fn shift(&mut self, by: isize) {
+        if let ASTType::Type { name } = self { name.shift(by) }
+    }
+}
+
+impl Shift for ASTBlock {
+    fn shift(&mut self, by: isize) {
+        if let ASTBlock::Block { items } = self { items.shift(by) }
+    }
+}
+
+impl Shift for ASTStatement {
+    fn shift(&mut self, by: isize) {
+        match self {
+            ASTStatement::For { clauses, body } => {
+                clauses.shift(by);
+                body.shift(by);
+            }
+            ASTStatement::If { clauses, body, else_ } => {
+                clauses.shift(by);
+                body.shift(by);
+                else_.shift(by);
+            }
+            ASTStatement::Return { args } => args.shift(by),
+            ASTStatement::Call { call } => call.shift(by),
+            ASTStatement::Assign { first: _, variable, arg } => {
+                variable.shift(by);
+                arg.shift(by);
+            }
+            ASTStatement::Write { op, args, table } => {
+                op.shift(by);
+                args.shift(by);
+                table.shift(by);
+            }
+            ASTStatement::Invalid(_) => {}
+        }
+    }
+}
+
+impl Shift for ASTQueryExpression {
+    fn shift(&mut self, by: isize) {
+        if let ASTQueryExpression::QExpression { items } = self { items.shift(by) }
+    }
+}
+
+impl Shift for ASTQueryGoal {
+    fn shift(&mut self, by: isize) {
+        match self {
+            ASTQueryGoal::Goal { args, source } => {
+                args.shift(by);
+                source.shift(by);
+            }
+            ASTQueryGoal::Filter { condition } => condition.shift(by),
+            ASTQueryGoal::Not { body } => body.shift(by),
+            ASTQueryGoal::Disjunction { clauses } => clauses.shift(by),
+            ASTQueryGoal::Invalid(_) => {}
+        }
+    }
+}
+
+impl Shift for ASTAssignTarget {
+    fn shift(&mut self, by: isize) {
+        if let ASTAssignTarget::Target { args } = self { args.shift(by) }
+    }
+}
+
+impl Shift for ASTQueryGoalSource {
+    fn shift(&mut self, by: isize) {
+        match self {
+            ASTQueryGoalSource::In { from } => from.shift(by),
+            ASTQueryGoalSource::Assign { expression } => expression.shift(by),
+            ASTQueryGoalSource::Aggregate { op, value, body } => {
+                op.shift(by);
+                value.shift(by);
+                body.shift(by);
+            }
+            ASTQueryGoalSource::Invalid(_) => {}
+        }
+    }
+}
+
+impl Shift for ASTExpression {
+    fn shift(&mut self, by: isize) {
+        match self {
+            ASTExpression::StringLiteral { .. } | ASTExpression::IntegerLiteral { .. } | ASTExpression::Variable { .. } => {}
+            ASTExpression::Call { call } => call.shift(by),
+            ASTExpression::Parens { arg } => arg.shift(by),
+            ASTExpression::UOp { op: _, arg } => arg.shift(by),
+            ASTExpression::BinOp { arg1, op: _, arg2 } => {
+                arg1.shift(by);
+                arg2.shift(by);
+            }
+            ASTExpression::Invalid(_) => {}
+        }
+    }
+}
+
+impl Shift for ASTCall {
+    fn shift(&mut self, by: isize) {
+        if let ASTCall::Call { name, args } = self {
+            name.shift(by);
+            args.shift(by);
+        }
+    }
+}
+
+impl Shift for ASTCallArgs {
+    fn shift(&mut self, by: isize) {
+        if let ASTCallArgs::Args { args } = self { args.shift(by) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IncrementalModule;
+    use crate::frontend::parse_module;
+
+    const TEXT: &str = "\
+## lonely ones
+view [@x NPC] in lonely {
+    @x in vampire,
+}
+or {
+    @x in bat,
+}
+
+## says hi
+def main() {
+    print(\"hi\")
+}
+
+def other() {
+    print(1 + 2)
+}
+";
+
+    // applies each edit in turn, checking against a parse of the whole text every time
+    fn check(edits: &[(&str, &str)]) {
+        let mut module = IncrementalModule::new(TEXT);
+        for (find, replacement) in edits {
+            let start = module.text().find(find).unwrap();
+            module.edit(start, start + find.len(), replacement);
+            assert_eq!(format!("{:?}", module.module()), format!("{:?}", parse_module(module.text())), "after replacing {:?}", find);
+        }
+    }
+
+    #[test]
+    fn open_brace() {
+        check(&[("print(\"hi\")\n}", "print(\"hi\")\n"), ("print(\"hi\")\n", "print(\"hi\")\n}")]);
+    }
+
+    #[test]
+    fn doc_comment() {
+        check(&[("says hi", "says hello"), ("## says hello\n", ""), ("def main", "## new docs\ndef main")]);
+    }
+
+    #[test]
+    fn or_after_view() {
+        check(&[("or {", "or  {"), ("or  {", "{"), ("}\n{", "}\nor {")]);
+    }
+}
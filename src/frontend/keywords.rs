@@ -1,10 +1,18 @@
 pub fn is_keyword(s: &str) -> bool {
     return
+        s == "and" ||
         s == "def" || 
         s == "else" ||
+        s == "expunge" ||
         s == "for" ||
+        s == "from" ||
         s == "if" ||
         s == "in" ||
+        s == "insert" ||
+        s == "into" ||
+        s == "not" ||
+        s == "or" ||
+        s == "remove" ||
         s == "return" ||
         s == "view"
 }
\ No newline at end of file
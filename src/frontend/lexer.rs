@@ -30,7 +30,21 @@ pub enum Grouping {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Operator {
     OAdd, OSubtract, OMultiply, ODivide, ODot,
-    OAssign, OAssignNew,
+    OAssign, OAssignNew, OColon,
+    OEqual, ONotEqual, OLess, OLessEqual, OGreater, OGreaterEqual,
+}
+
+// whatever's between tokens: the parser never sees it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trivia {
+    Whitespace, Comment, DocComment,
+}
+
+// a token, with the trivia that came right before it
+#[derive(Clone, Debug)]
+pub struct LosslessToken {
+    pub leading: Vec<Located<Trivia>>,
+    pub token: Located<Token>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -106,17 +120,64 @@ impl<'a> CStream<'a> {
 struct Lexer<'a> {
     cs: CStream<'a>,
     tokens: Vec<Located<Token>>,
+    keep_trivia: bool,
+    trivia: Vec<Located<Trivia>>,
+}
+
+pub fn lex(s: &str) -> (Vec<Located<Token>>, Located<()>, Vec<Located<String>>) {
+    let (tokens, eof, trivia) = Lexer { cs: CStream::new(s), tokens: vec![], keep_trivia: false, trivia: vec![] }.lex();
+    let docs = doc_comments(s, &tokens, eof, &trivia);
+    (tokens, eof, docs)
 }
 
-pub fn lex(s: &str) -> (Vec<Located<Token>>, Located<()>) {
-    let cs = CStream::new(s);
-    let tokens = vec![];
+// the parser never sees comments, but the formatter has to put them back
+pub fn lex_with_comments(s: &str) -> (Vec<Located<Token>>, Located<()>, Vec<Located<()>>) {
+    let (tokens, eof, trivia) = lex_with_trivia(s);
+    let comments = trivia.iter().filter(|t| t.value != Trivia::Whitespace).map(|t| t.location()).collect();
+    (tokens, eof, comments)
+}
 
-    Lexer { cs, tokens }.lex()
+// every byte of s is in exactly one token or piece of trivia. the last token is EOF
+pub fn lex_lossless(s: &str) -> Vec<LosslessToken> {
+    let (tokens, eof, trivia) = lex_with_trivia(s);
+    let mut trivia = trivia.into_iter().peekable();
+    let eof = eof.replace(Token::EOF);
+    tokens.into_iter().chain(std::iter::once(eof)).map(|token| {
+        let mut leading = vec![];
+        while let Some(t) = trivia.next_if(|t| t.end <= token.start) { leading.push(t) }
+        LosslessToken { leading, token }
+    }).collect()
+}
+
+// runs of ## lines right before a token (a # comment cuts them off), located up to that token
+fn doc_comments(s: &str, tokens: &[Located<Token>], eof: Located<()>, trivia: &[Located<Trivia>]) -> Vec<Located<String>> {
+    let mut docs = vec![];
+    let mut trivia = trivia.iter().peekable();
+    for next in tokens.iter().map(|t| t.start).chain(std::iter::once(eof.start)) {
+        let mut run: Option<(usize, Vec<&str>)> = None;
+        while let Some(t) = trivia.next_if(|t| t.end <= next) {
+            match t.value {
+                Trivia::DocComment => {
+                    let line = s[t.start + 2..t.end].trim_end();
+                    run.get_or_insert((t.start, vec![])).1.push(line.strip_prefix(' ').unwrap_or(line))
+                }
+                Trivia::Comment => run = None,
+                Trivia::Whitespace => {}
+            }
+        }
+        if let Some((start, lines)) = run {
+            docs.push(Located { start, end: next, value: lines.join("\n") })
+        }
+    }
+    docs
+}
+
+fn lex_with_trivia(s: &str) -> (Vec<Located<Token>>, Located<()>, Vec<Located<Trivia>>) {
+    Lexer { cs: CStream::new(s), tokens: vec![], keep_trivia: true, trivia: vec![] }.lex()
 }
 
 impl<'a> Lexer<'a> {
-    fn lex(mut self) -> (Vec<Located<Token>>, Located<()>) {
+    fn lex(mut self) -> (Vec<Located<Token>>, Located<()>, Vec<Located<Trivia>>) {
         while self.cs.any() {
             if self.whitespace() { continue; }
             if self.singleline_comment() { continue; }
@@ -137,24 +198,35 @@ impl<'a> Lexer<'a> {
             }
             // TODO: Add an invalid token
         }
-        (self.tokens, Located { start: self.cs.offset, end: self.cs.offset, value: () })
+        (self.tokens, Located { start: self.cs.offset, end: self.cs.offset, value: () }, self.trivia)
+    }
+
+    // comments get kept either way, for the sake of doc comments
+    fn push_trivia(&mut self, start: usize, trivia: Trivia) {
+        if self.keep_trivia || trivia != Trivia::Whitespace {
+            self.trivia.push(Located { start, end: self.cs.offset, value: trivia })
+        }
     }
 
     fn whitespace(&mut self) -> bool {
+        let start = self.cs.offset;
         let mut any = false;
         while self.cs.pop_ws() {
             any = true;
         }
+        if any { self.push_trivia(start, Trivia::Whitespace) }
         return any;
     }
 
     fn singleline_comment(&mut self) -> bool {
+        let start = self.cs.offset;
         if !self.cs.pop_char('#') {
             return false
         }
-        while let Some(c) = self.cs.pop_any() {
-            if c == '\n' { break; }
-        }
+        let doc = self.cs.pop_char('#');
+        // the newline is whitespace
+        while self.cs.pop_cpred(|c| c != '\n').is_some() {}
+        self.push_trivia(start, if doc { Trivia::DocComment } else { Trivia::Comment });
         true
     }
 
@@ -265,6 +337,13 @@ impl<'a> Lexer<'a> {
         else if self.cs.pop_string("/") { op = Operator::ODivide; }
         else if self.cs.pop_string(".") { op = Operator::ODot; }
         else if self.cs.pop_string(":=") { op = Operator::OAssignNew; }
+        else if self.cs.pop_string(":") { op = Operator::OColon; }
+        else if self.cs.pop_string("==") { op = Operator::OEqual; }
+        else if self.cs.pop_string("!=") { op = Operator::ONotEqual; }
+        else if self.cs.pop_string("<=") { op = Operator::OLessEqual; }
+        else if self.cs.pop_string(">=") { op = Operator::OGreaterEqual; }
+        else if self.cs.pop_string("<") { op = Operator::OLess; }
+        else if self.cs.pop_string(">") { op = Operator::OGreater; }
         else if self.cs.pop_string("=") { op = Operator::OAssign; }
         else { return false; }
 
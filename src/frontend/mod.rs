@@ -1,14 +1,57 @@
 pub mod ast;
+pub mod cst;
+mod format;
+mod incremental;
 mod keywords;
 mod located;
 mod lexer;
 mod parser;
 
+pub use self::format::format_module;
+pub use self::incremental::IncrementalModule;
 pub use self::located::Located;
-use self::parser::internal_ast::KupoParseError;
+pub use self::parser::internal_ast::KupoParseError;
 
 pub fn parse_module(s: &str) -> Result<Located<ast::Module>, Vec<Located<KupoParseError>>> {
-    let (ts, eof) = lexer::lex(s);
-    let internal_parse = parser::parse_module(&ts, eof);
+    let (ts, eof, docs) = lexer::lex(s);
+    let internal_parse = parser::parse_module(&ts, eof, &docs);
     ast::simplify_module(internal_parse)
-}
\ No newline at end of file
+}
+
+pub enum ReplInput {
+    Statements(Located<ast::Block>),
+    Query(Located<ast::QueryExpression>),
+    Incomplete(Vec<Located<KupoParseError>>),  // input ended inside a group: wait for another line
+}
+
+// statements if it parses as statements, otherwise a query. offset keeps locations clear of the module's
+pub fn parse_repl_input(s: &str, offset: usize) -> Result<ReplInput, Vec<Located<KupoParseError>>> {
+    let (ts, eof, _) = lex_at(s, offset);
+    let (block, unclosed) = parser::parse_repl_statements(&ts, eof);
+    let errors = match ast::simplify_block(block) {
+        Ok(block) => return Ok(ReplInput::Statements(block)),
+        Err(errors) => errors,
+    };
+    let (query, unclosed_query) = parser::parse_repl_query(&ts, eof);
+    match ast::simplify_query_expression(query) {
+        Ok(query) => Ok(ReplInput::Query(query)),
+        Err(_) if unclosed || unclosed_query => Ok(ReplInput::Incomplete(errors)),
+        Err(_) => Err(errors),
+    }
+}
+
+// lexes s as if it started offset bytes into something bigger
+fn lex_at(s: &str, offset: usize) -> (Vec<Located<lexer::Token>>, Located<()>, Vec<Located<String>>) {
+    let (mut ts, mut eof, mut docs) = lexer::lex(s);
+    for t in ts.iter_mut() {
+        t.start += offset;
+        t.end += offset;
+    }
+    for d in docs.iter_mut() {
+        d.start += offset;
+        d.end += offset;
+    }
+    eof.start += offset;
+    eof.end += offset;
+    (ts, eof, docs)
+}
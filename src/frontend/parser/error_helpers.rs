@@ -13,8 +13,9 @@ impl<'a> Parser<'a> {
         let mut loc: Located<()> = self.ts.location();
 
         loop {
-            if self.ts.peek_keyword("fn") { return loc; }
+            if self.ts.peek_keyword("def") { return loc; }
             if self.ts.peek_keyword("view") { return loc; }
+            if self.ts.peek_eq(&Token::EOF) { return loc; }
 
             let t = self.ts.pop_any();
             loc = loc.merge_l(&t).location();
@@ -44,6 +45,44 @@ impl<'a> Parser<'a> {
     }
 }
 
-pub fn kpe(s: &str) -> KupoParseError {
-    KupoParseError(s.to_string())
-}
\ No newline at end of file
+pub fn kpe(code: &'static str, message: &'static str) -> KupoParseError {
+    debug_assert!(PARSE_ERRORS.iter().any(|(c, _)| *c == code), "parse error code isn't in the catalogue: {}", code);
+    KupoParseError { code, message }
+}
+
+// every code kpe gets, with the message that goes with it.
+// tools match on these: never renumber or reuse a code (retired ones stay, commented out)
+const PARSE_ERRORS: &[(&str, &str)] = &[
+    ("P0001", "found EOF before end of group"),
+    ("P0002", "left paren expected"),
+    ("P0003", "right paren expected"),
+    ("P0004", "left bracket expected"),
+    ("P0005", "right bracket expected"),
+    ("P0006", "left brace expected"),
+    ("P0007", "right brace expected"),
+    ("P0008", "end of file"),
+    ("P0009", "end of input"),
+    ("P0010", "start of block"),
+    ("P0011", "unrecognized item: expected def or view"),
+    ("P0012", "expected def"),
+    ("P0013", "function name expected"),
+    ("P0014", "expected view"),
+    ("P0015", "expected in"),
+    ("P0016", "view name expected"),
+    ("P0017", "expected string"),
+    ("P0018", "expected type"),
+    ("P0019", "expected statement"),
+    ("P0020", "expected := or = for an assignment"),
+    ("P0021", "expected expression"),
+    ("P0022", "expected function name for call"),
+    ("P0023", "expected table after 'in'"),
+    ("P0024", "you can't use = in a query expression, only :="),
+    ("P0025", "unrecognized goal source: expected in or :="),
+    ("P0026", "can't use or here: write it as the head of a for"),
+    ("P0027", "expected one expression in parentheses"),
+    ("P0028", "expected : after the value to aggregate"),
+    ("P0029", "expected into after the row to insert"),
+    ("P0030", "expected from after the row to remove"),
+    ("P0031", "expected from after the key to expunge"),
+    ("P0032", "expected table to write to"),
+];
@@ -8,31 +8,54 @@ use super::internal_ast::*;
 
 impl<'a> Parser<'a> {
     pub fn parse_expression(&mut self) -> Parse<ASTExpression> {
-        let mut leaf = self.parse_leaf_expression();
+        self.parse_expression_up_to(usize::MAX)
+    }
+
+    // only takes operators at max_level or tighter
+    pub fn parse_expression_up_to(&mut self, max_level: usize) -> Parse<ASTExpression> {
+        let leaf = self.parse_leaf_expression();
+        self.continue_expression(leaf, max_level)
+    }
 
+    // the rest of an expression whose first leaf was already parsed
+    pub fn continue_expression(&mut self, mut leaf: Parse<ASTExpression>, max_level: usize) -> Parse<ASTExpression> {
         loop {
-            if self.ts.pop_eq(&Token::Operator(Operator::OAdd)).is_some() {
-                let leaf2 = self.parse_leaf_expression();
-                leaf = leaf.add_using_precedence(ASTBinOp::Add, leaf2);
-            }
-            else if self.ts.pop_eq(&Token::Operator(Operator::OSubtract)).is_some() {
-                let leaf2 = self.parse_leaf_expression();
-                leaf = leaf.add_using_precedence(ASTBinOp::Subtract, leaf2);
-            }
-            else if self.ts.pop_eq(&Token::Operator(Operator::OMultiply)).is_some() {
-                let leaf2 = self.parse_leaf_expression();
-                leaf = leaf.add_using_precedence(ASTBinOp::Multiply, leaf2);
-            }
-            else if self.ts.pop_eq(&Token::Operator(Operator::ODivide)).is_some() {
-                let leaf2 = self.parse_leaf_expression();
-                leaf = leaf.add_using_precedence(ASTBinOp::Divide, leaf2);
-            }
-            else {
-                return leaf;
-            }
+            let op = match self.peek_binop() {
+                Some(op) if op.precedence_level() <= max_level => op,
+                _ => return leaf,
+            };
+            self.ts.pop_any();
+            let leaf2 = self.parse_leaf_expression();
+            leaf = leaf.add_using_precedence(op, leaf2);
         }
     }
 
+    fn peek_binop(&self) -> Option<ASTBinOp> {
+        Some(match &self.ts.peek_any().value {
+            Token::Operator(Operator::OAdd) => ASTBinOp::Add,
+            Token::Operator(Operator::OSubtract) => ASTBinOp::Subtract,
+            Token::Operator(Operator::OMultiply) => ASTBinOp::Multiply,
+            Token::Operator(Operator::ODivide) => ASTBinOp::Divide,
+            Token::Operator(Operator::OEqual) => ASTBinOp::Equal,
+            Token::Operator(Operator::ONotEqual) => ASTBinOp::NotEqual,
+            Token::Operator(Operator::OLess) => ASTBinOp::Less,
+            Token::Operator(Operator::OLessEqual) => ASTBinOp::LessEqual,
+            Token::Operator(Operator::OGreater) => ASTBinOp::Greater,
+            Token::Operator(Operator::OGreaterEqual) => ASTBinOp::GreaterEqual,
+            Token::Keyword(k) if k == "and" => ASTBinOp::And,
+            Token::Keyword(k) if k == "or" && !self.or_ends_expression => ASTBinOp::Or,
+            _ => return None,
+        })
+    }
+
+    // `or` separates clauses in a for or if head, but it's boolean again inside parens (or a not's braces)
+    pub fn inside_parens<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let old = std::mem::replace(&mut self.or_ends_expression, false);
+        let result = f(self);
+        self.or_ends_expression = old;
+        result
+    }
+
     pub fn parse_leaf_expression(&mut self) -> Parse<ASTExpression> {
         self.located(|s| {
             // TODO: parse_expression_leaf and parse_expression_coda
@@ -46,6 +69,12 @@ impl<'a> Parser<'a> {
                     op: ASTUOp::Plus,
                     arg: Box::new(s.parse_leaf_expression()),
                 }
+            } else if s.ts.pop_keyword("not").is_some() {
+                // looser than comparisons, tighter than and: `not @x == 1 and @y` is `(not (@x == 1)) and @y`
+                ASTExpression::UOp {
+                    op: ASTUOp::Not,
+                    arg: Box::new(s.parse_expression_up_to(2)),
+                }
             } else if let Token::Integer(i) = &s.ts.peek_any().value {
                 let result = ASTExpression::IntegerLiteral { it: i.clone() };
                 s.ts.pop_any();
@@ -54,12 +83,27 @@ impl<'a> Parser<'a> {
                 let result = ASTExpression::StringLiteral { it: string.clone() };
                 s.ts.pop_any();
                 result
+            } else if let Some(name) = s.ts.pop_variable() {
+                ASTExpression::Variable { name: name.value }
             } else if s.ts.peek_identifier() {
                 let call = s.parse_call();
                 ASTExpression::Call { call }
+            } else if s.ts.peek_eq(&Token::Grouping(Grouping::LParen)) {
+                let mut delimit = DelimitedMany::parens_basis();
+                delimit.separator = Some(Token::Grouping(Grouping::Comma));
+                s.inside_parens(|s| s.group(
+                    delimit,
+                    |s| s.parse_expression(),
+                    |mut args| if args.len() == 1 {
+                        ASTExpression::Parens { arg: Box::new(args.pop().unwrap()) }
+                    } else {
+                        ASTExpression::Invalid(kpe("P0027", "expected one expression in parentheses"))
+                    },
+                    ASTExpression::Invalid,
+                )).value
             } else {
                 s.skip_to_end_of_expression();
-                ASTExpression::Invalid(kpe("expected expression"))
+                ASTExpression::Invalid(kpe("P0021", "expected expression"))
             }
         })
     }
@@ -82,18 +126,18 @@ impl<'a> Parser<'a> {
             let name = if let Some(name) = s.ts.pop_identifier() { 
                 name 
             } else {
-                return ASTCall::Invalid(kpe("expected function name for call"))
+                return ASTCall::Invalid(kpe("P0022", "expected function name for call"))
             };
 
             let mut delimit = DelimitedMany::parens_basis();
             delimit.separator = Some(Token::Grouping(Grouping::Comma));
 
-            let args = s.group(
+            let args = s.inside_parens(|s| s.group(
                 delimit,
                 |s| s.parse_expression(),
                 |args| ASTCallArgs::Args { args },
                 ASTCallArgs::Invalid
-            );
+            ));
 
             ASTCall::Call { name, args }
         })
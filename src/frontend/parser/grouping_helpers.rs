@@ -5,9 +5,11 @@ use super::{Parser, internal_ast::KupoParseError};
 pub struct DelimitedMany {
     pub can_be_bare: bool,
     pub consume_rhs: bool,
-    pub lhs: Option<(Token, &'static str)>,
-    pub rhs: (Token, &'static str),
+    pub lhs: Option<(Token, KupoParseError)>,
+    pub rhs: (Token, KupoParseError),
+    pub rhs_alternative: Option<Token>,  // also ends the group, but never gets consumed
     pub separator: Option<Token>,
+    pub separator_optional: bool,
 }
 
 impl DelimitedMany {
@@ -15,9 +17,11 @@ impl DelimitedMany {
         DelimitedMany {
             can_be_bare: false,
             consume_rhs: true,
-            lhs: Some((Token::Grouping(Grouping::LParen), "left paren expected")),
-            rhs: (Token::Grouping(Grouping::RParen), "right paren expected"),
+            lhs: Some((Token::Grouping(Grouping::LParen), kpe("P0002", "left paren expected"))),
+            rhs: (Token::Grouping(Grouping::RParen), kpe("P0003", "right paren expected")),
+            rhs_alternative: None,
             separator: None,
+            separator_optional: false,
         }
     }
 
@@ -25,9 +29,11 @@ impl DelimitedMany {
         DelimitedMany {
             can_be_bare: false,
             consume_rhs: true,
-            lhs: Some((Token::Grouping(Grouping::LBrack), "left bracket expected")),
-            rhs: (Token::Grouping(Grouping::RBrack), "right bracket expected"),
+            lhs: Some((Token::Grouping(Grouping::LBrack), kpe("P0004", "left bracket expected"))),
+            rhs: (Token::Grouping(Grouping::RBrack), kpe("P0005", "right bracket expected")),
+            rhs_alternative: None,
             separator: None,
+            separator_optional: false,
         }
     }
 
@@ -35,9 +41,11 @@ impl DelimitedMany {
         DelimitedMany {
             can_be_bare: false,
             consume_rhs: true,
-            lhs: Some((Token::Grouping(Grouping::LBrace), "left bracket expected")),
-            rhs: (Token::Grouping(Grouping::RBrace), "right bracket expected"),
+            lhs: Some((Token::Grouping(Grouping::LBrace), kpe("P0006", "left brace expected"))),
+            rhs: (Token::Grouping(Grouping::RBrace), kpe("P0007", "right brace expected")),
+            rhs_alternative: None,
             separator: None,
+            separator_optional: false,
         }
     }
 }
@@ -63,7 +71,7 @@ impl<'a> Parser<'a> {
                     if rules.can_be_bare {
                         return integrate(vec![parse(s)]);
                     }
-                    return fail(kpe(lmsg));
+                    return fail(lmsg);
                 }
             }
 
@@ -71,14 +79,20 @@ impl<'a> Parser<'a> {
 
             loop {
                 if s.ts.peek_eq(&rules.rhs.0) { break; }
-                if s.ts.peek_eq(&Token::EOF) { return fail(kpe("found EOF before end of group")); }
+                if let Some(alt) = &rules.rhs_alternative {
+                    if s.ts.peek_eq(alt) { return integrate(xs); }
+                }
+                if s.ts.peek_eq(&Token::EOF) { 
+                    s.unclosed = true;
+                    return fail(kpe("P0001", "found EOF before end of group")); 
+                }
 
                 let x = parse(s);
                 xs.push(x);
 
                 if let Some(sep) = &rules.separator {
                     let found_terminator = s.ts.pop_eq(&sep).is_some();
-                    if !found_terminator { 
+                    if !found_terminator && !rules.separator_optional { 
                         // println!("breaking: did not find: {:?}", sep);
                         break; 
                     }
@@ -90,11 +104,12 @@ impl<'a> Parser<'a> {
                 // println!("looking for: {:?}", rhs);
                 if s.ts.pop_eq(&rhs).is_none() {
                     // println!("did not find it");
-                    return fail(kpe(rmsg));
+                    return fail(rmsg);
                 };
             } else {
-                if !s.ts.peek_eq(&rhs) {
-                    return fail(kpe(rmsg));
+                let at_alternative = rules.rhs_alternative.as_ref().is_some_and(|alt| s.ts.peek_eq(alt));
+                if !s.ts.peek_eq(&rhs) && !at_alternative {
+                    return fail(rmsg);
                 };
             }
 
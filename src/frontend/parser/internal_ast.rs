@@ -1,7 +1,7 @@
 use super::super::located::Located;
 
 // == structural ==
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTModule {
     Module { 
         items: Vec<Located<ASTItem>>,
@@ -9,16 +9,17 @@ pub enum ASTModule {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTItem {
     Def(ASTDef),
     View(ASTView),
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTDef {
     Def {
+        docs: Option<Located<String>>,
         name: Located<String>,
         args: Located<ASTArgs>,
         return_type: Option<Located<ASTTypes>>,
@@ -26,9 +27,10 @@ pub enum ASTDef {
     },
     Invalid(KupoParseError),
 }
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTView {
     View {
+        docs: Option<Located<String>>,
         name: Located<String>,
         args: Located<ASTArgs>,
         clauses: Vec<Located<ASTQueryExpression>>,
@@ -36,7 +38,7 @@ pub enum ASTView {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTArgs {
     Args {
         args: Vec<Located<ASTArg>>,
@@ -45,7 +47,7 @@ pub enum ASTArgs {
 }
 
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTTypes {
     Types {
         types: Vec<Located<ASTType>>,
@@ -54,7 +56,7 @@ pub enum ASTTypes {
 }
 
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTArg {
     Arg {
         name: Located<String>,
@@ -63,14 +65,14 @@ pub enum ASTArg {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTType {
     Type { name: Located<String>, },
     Invalid(KupoParseError),
 }
 
 // == statement ==
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTBlock {
     Block {
         items: Vec<Located<ASTStatement>>
@@ -78,19 +80,19 @@ pub enum ASTBlock {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTStatement {
     For { 
-        arg: Located<ASTQueryExpression>, 
+        clauses: Vec<Located<ASTQueryExpression>>, 
         body: Located<ASTBlock>
     },
     If {
-        arg: Located<ASTQueryExpression>,
+        clauses: Vec<Located<ASTQueryExpression>>,
         body: Located<ASTBlock>,
         else_: Option<Located<ASTBlock>>,
     },
     Return {
-        arg: Located<ASTExpression>
+        args: Located<ASTAssignTarget>
     },
     Call { 
         call: Located<ASTCall>,
@@ -100,12 +102,22 @@ pub enum ASTStatement {
         variable: Located<ASTAssignTarget>,
         arg: Located<ASTExpression>,
     },
+    Write {
+        op: Located<ASTWrite>,
+        args: Located<ASTAssignTarget>,  // the row, or just the key for expunge
+        table: Located<String>,
+    },
     Invalid(KupoParseError),
     // TODO: continue/break 
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum ASTWrite {
+    Insert, Remove, Expunge,
+}
+
 // == query expression ==
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTQueryExpression {
     QExpression { 
         items: Vec<Located<ASTQueryGoal>>,
@@ -113,16 +125,27 @@ pub enum ASTQueryExpression {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTQueryGoal {
-    // TODO: Parens?
     Goal { 
         args: Located<ASTAssignTarget>,
         source: Located<ASTQueryGoalSource>,
     },
+    Filter {
+        // a bare expression: the row survives if it's true
+        condition: Located<ASTExpression>,
+    },
+    Not {
+        // the row survives if this has no solutions
+        body: Located<ASTQueryExpression>,
+    },
+    Disjunction {
+        clauses: Vec<Located<ASTQueryExpression>>,
+    },
+    Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTAssignTarget {
     Target {
         args: Vec<Located<ASTExpression>>,
@@ -130,23 +153,50 @@ pub enum ASTAssignTarget {
     Invalid(KupoParseError)
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTQueryGoalSource {
     In { from: Located<String>, },
     Assign { expression: Located<ASTExpression>, },
+    Aggregate {
+        op: Located<ASTAggregate>,
+        value: Located<ASTExpression>,  // before the colon
+        body: Located<ASTQueryExpression>,
+    },
     // TODO: Also allow = instead of := but explain that it is wrong.
-    // TODO: Allow arbitrary boolean expressions
     Invalid(KupoParseError),
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum ASTAggregate {
+    Count, Sum, Min, Max, Collect,
+}
+
+impl ASTAggregate {
+    pub fn named(name: &str) -> Option<ASTAggregate> {
+        match name {
+            "count" => Some(ASTAggregate::Count),
+            "sum" => Some(ASTAggregate::Sum),
+            "min" => Some(ASTAggregate::Min),
+            "max" => Some(ASTAggregate::Max),
+            "collect" => Some(ASTAggregate::Collect),
+            _ => None,
+        }
+    }
+}
+
 // == expression ==
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTExpression {
     StringLiteral { it: String },
     IntegerLiteral { it: u64 },
+    Variable { name: String },
     Call { 
         call: Located<ASTCall>,
     },
+    Parens {
+        // keeps add_using_precedence from reaching inside
+        arg: Box<Located<ASTExpression>>,
+    },
     UOp {
         op: ASTUOp,
         arg: Box<Located<ASTExpression>>
@@ -159,17 +209,19 @@ pub enum ASTExpression {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTUOp { 
-    Negate, Plus
+    Negate, Plus, Not
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTBinOp { 
-    Add, Subtract, Multiply, Divide
+    Add, Subtract, Multiply, Divide,
+    Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual,
+    And, Or,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTCall {
     Call {
         name: Located<String>,
@@ -178,7 +230,7 @@ pub enum ASTCall {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum ASTCallArgs {
     Args {
         args: Vec<Located<ASTExpression>>
@@ -186,5 +238,8 @@ pub enum ASTCallArgs {
     Invalid(KupoParseError),
 }
 
-#[derive(Debug)]
-pub struct KupoParseError(pub String);
\ No newline at end of file
+#[derive(Clone, Debug)]
+pub struct KupoParseError {
+    pub code: &'static str,  // see PARSE_ERRORS
+    pub message: &'static str,
+}
\ No newline at end of file
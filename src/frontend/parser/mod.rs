@@ -10,19 +10,56 @@ mod tstream;
 
 use tstream::*;
 
-use self::internal_ast::ASTModule;
 
-use super::{lexer::{Token}, located::Located};
+use self::error_helpers::kpe;
+use self::grouping_helpers::DelimitedMany;
+use self::internal_ast::{ASTBlock, ASTModule, ASTQueryExpression};
+
+use super::{lexer::{Grouping, Token}, located::Located};
 
 struct Parser<'a> {
     ts: TStream<'a>,
+    unclosed: bool,  // ran out of input in the middle of a group
+    docs: &'a [Located<String>],  // doc comments, each running up to the token it documents
+    or_ends_expression: bool,  // in a for or if head, or means the next clause
 }
 
 type Parse<T> = Located<T>;
 
-pub fn parse_module(ts: &[Located<Token>], eof: Located<()>) -> Parse<ASTModule> {
+pub fn parse_module(ts: &[Located<Token>], eof: Located<()>, docs: &[Located<String>]) -> Parse<ASTModule> {
     let ts = TStream::new(ts, eof);
-    Parser { ts }.parse_module()
+    Parser { ts, unclosed: false, docs, or_ends_expression: false }.parse_module()
+}
+
+// == REPL ==
+// these also say whether the input ran out inside a group (so the REPL waits for more)
+
+// statements up to the end of input, as if they were the inside of a block
+pub fn parse_repl_statements(ts: &[Located<Token>], eof: Located<()>) -> (Parse<ASTBlock>, bool) {
+    let mut parser = Parser { ts: TStream::new(ts, eof), unclosed: false, docs: &[], or_ends_expression: false };
+    let mut delimit = DelimitedMany::braces_basis();
+    delimit.lhs = None;
+    delimit.rhs = (Token::EOF, kpe("P0009", "end of input"));
+    delimit.separator = Some(Token::Grouping(Grouping::Semicolon));
+    delimit.separator_optional = true;
+
+    let block = parser.group(
+        delimit,
+        |s| s.parse_statement(),
+        |items| ASTBlock::Block { items },
+        ASTBlock::Invalid
+    );
+    (block, parser.unclosed)
+}
+
+pub fn parse_repl_query(ts: &[Located<Token>], eof: Located<()>) -> (Parse<ASTQueryExpression>, bool) {
+    let mut parser = Parser { ts: TStream::new(ts, eof), unclosed: false, docs: &[], or_ends_expression: false };
+    let query = parser.parse_plain_query_expression((Token::EOF, kpe("P0009", "end of input")));
+    if parser.ts.peek_eq(&Token::EOF) { return (query, parser.unclosed) }
+
+    // the only thing that stops a plain query short of the end is an or
+    let invalid = ASTQueryExpression::Invalid(kpe("P0026", "can't use or here: write it as the head of a for"));
+    (parser.ts.location().replace(invalid), parser.unclosed)
 }
 
 impl<'a> Parser<'a> {
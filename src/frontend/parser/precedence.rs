@@ -12,20 +12,21 @@ impl Located<ASTExpression> {
         let loc_overall = loc1.merge(loc2).location();
         let v_overall = match v1 {
             ASTExpression::StringLiteral { .. } | ASTExpression::IntegerLiteral { .. }  |
-            ASTExpression::Call { .. } | ASTExpression::UOp { .. } |
+            ASTExpression::Variable { .. } |
+            ASTExpression::Call { .. } | ASTExpression::Parens { .. } | ASTExpression::UOp { .. } |
             ASTExpression::Invalid(..)
             => {
                 ASTExpression::BinOp { arg1: Box::new(loc1.replace(v1)), op: op2, arg2: Box::new(loc2.replace(v2)) }
             }
             ASTExpression::BinOp { arg1, op: op1, arg2 } =>  {
                 if op1.tighter_than(&op2) {
-                    ASTExpression::BinOp { arg1, op: op1, arg2: Box::new(arg2.add_using_precedence(op2, loc2.replace(v2))) }
-                } else {
                     ASTExpression::BinOp { 
                         arg1: Box::new(loc1.replace(ASTExpression::BinOp { arg1, op: op1, arg2})),
                         op: op2,
                         arg2: Box::new(loc2.replace(v2))
                     }
+                } else {
+                    ASTExpression::BinOp { arg1, op: op1, arg2: Box::new(arg2.add_using_precedence(op2, loc2.replace(v2))) }
                 }
 
             }
@@ -46,19 +47,31 @@ impl ASTBinOp {
         }
     }
 
-    fn precedence_level(&self) -> usize {
+    // smaller binds tighter
+    // `not` is 3: see parse_leaf_expression
+    pub fn precedence_level(&self) -> usize {
         match self {
             ASTBinOp::Multiply | ASTBinOp::Divide => 0,
             ASTBinOp::Add | &ASTBinOp::Subtract => 1,
+            ASTBinOp::Equal | ASTBinOp::NotEqual |
+            ASTBinOp::Less | ASTBinOp::LessEqual |
+            ASTBinOp::Greater | ASTBinOp::GreaterEqual => 2,
+            ASTBinOp::And => 4,
+            ASTBinOp::Or => 5,
         }
     }
 
-    fn left_associative(&self) -> bool {
+    pub fn left_associative(&self) -> bool {
         match self {
             ASTBinOp::Add => true,
             ASTBinOp::Subtract => true,
             ASTBinOp::Multiply => true,
             ASTBinOp::Divide => true,
+            ASTBinOp::Equal | ASTBinOp::NotEqual => true,
+            ASTBinOp::Less | ASTBinOp::LessEqual => true,
+            ASTBinOp::Greater | ASTBinOp::GreaterEqual => true,
+            ASTBinOp::And => true,
+            ASTBinOp::Or => true,
         }
     }
 }
\ No newline at end of file
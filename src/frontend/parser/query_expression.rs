@@ -19,51 +19,160 @@ impl<'a> Parser<'a> {
         )
     }
 
-    pub fn parse_plain_query_expression(&mut self, rhs: (Token, &'static str)) -> Parse<ASTQueryExpression> {
+    // stops before rhs, or before an `or` that starts the next clause
+    pub fn parse_plain_query_expression(&mut self, rhs: (Token, KupoParseError)) -> Parse<ASTQueryExpression> {
         let mut delimit = DelimitedMany::braces_basis();
         delimit.separator = Some(Token::Grouping(Grouping::Comma));
 
         delimit.lhs = None;
         delimit.rhs = rhs;
+        delimit.rhs_alternative = Some(Token::Keyword("or".to_string()));
         delimit.consume_rhs = false;
 
-        self.group(
+        let old = std::mem::replace(&mut self.or_ends_expression, true);
+        let query = self.group(
             delimit,
             |s| s.parse_query_goal(),
             |items| ASTQueryExpression::QExpression { items },
             ASTQueryExpression::Invalid,
-        )
+        );
+        self.or_ends_expression = old;
+        query
+    }
+
+    // the head of a for or an if: clauses separated by or, up to the start of the block
+    pub fn parse_head_query_expressions(&mut self) -> Vec<Parse<ASTQueryExpression>> {
+        let rhs = (Token::Grouping(Grouping::LBrace), kpe("P0010", "start of block"));
+        let mut clauses = vec![self.parse_plain_query_expression(rhs.clone())];
+        while self.ts.pop_keyword("or").is_some() {
+            clauses.push(self.parse_plain_query_expression(rhs.clone()));
+        }
+        clauses
     }
 
     fn parse_query_goal(&mut self) -> Parse<ASTQueryGoal> {
+        if self.ts.peek_keyword("not") { return self.parse_not_goal() }
+        if self.peek_disjunction() { return self.parse_disjunction_goal() }
+
+        let bracketed = self.ts.peek_eq(&Token::Grouping(Grouping::LBrack));
         let args = self.parse_assign_target();
+
+        // no source after a bare expression: it's a filter
+        let has_source = 
+            self.ts.peek_keyword("in") ||
+            self.ts.peek_eq(&Token::Operator(Operator::OAssignNew)) ||
+            self.ts.peek_eq(&Token::Operator(Operator::OAssign));
+        if !bracketed && !has_source {
+            if let ASTAssignTarget::Target { args: conditions } = &args.value {
+                if let [condition] = conditions.as_slice() {
+                    return args.location().replace(ASTQueryGoal::Filter { condition: condition.clone() })
+                }
+            }
+        }
+
         let source = self.parse_goal_source();
 
         args.location().merge_l(&source).replace(ASTQueryGoal::Goal { args, source })
     }
 
+    // `not {`, `not [`, `not (` and `not @x in` are negated goals. anything else is a filter
+    fn parse_not_goal(&mut self) -> Parse<ASTQueryGoal> {
+        self.located(|s| {
+            let not = s.ts.pop_keyword("not").unwrap().location();
+            if s.ts.peek_eq(&Token::Grouping(Grouping::LBrace)) {
+                let body = s.inside_parens(|s| s.parse_block_query_expression());
+                return ASTQueryGoal::Not { body }
+            }
+            if s.ts.peek_eq(&Token::Grouping(Grouping::LBrack)) || s.peek_disjunction() {
+                let goal = s.parse_query_goal();
+                return ASTQueryGoal::Not { body: goal.location().replace(ASTQueryExpression::QExpression { items: vec![goal] }) }
+            }
+
+            let arg = s.parse_expression_up_to(2);
+            if s.ts.peek_keyword("in") {
+                let args = arg.location().replace(ASTAssignTarget::Target { args: vec![arg] });
+                let source = s.parse_goal_source();
+                let goal = args.location().merge_l(&source).replace(ASTQueryGoal::Goal { args, source });
+                return ASTQueryGoal::Not { body: goal.location().replace(ASTQueryExpression::QExpression { items: vec![goal] }) }
+            }
+
+            let leaf = not.merge_l(&arg).replace(ASTExpression::UOp { op: ASTUOp::Not, arg: Box::new(arg) });
+            ASTQueryGoal::Filter { condition: s.continue_expression(leaf, usize::MAX) }
+        })
+    }
+
+    // a paren followed by a brace: an expression can't start with a brace
+    fn peek_disjunction(&self) -> bool {
+        self.ts.peek_eq(&Token::Grouping(Grouping::LParen)) && 
+            self.ts.peek_second().value == Token::Grouping(Grouping::LBrace)
+    }
+
+    // ({ ... } or { ... }): one clause is fine too, for grouping
+    fn parse_disjunction_goal(&mut self) -> Parse<ASTQueryGoal> {
+        let mut delimit = DelimitedMany::parens_basis();
+        delimit.separator = Some(Token::Keyword("or".to_string()));
+
+        self.inside_parens(|s| s.group(
+            delimit,
+            |s| s.parse_block_query_expression(),
+            |clauses| ASTQueryGoal::Disjunction { clauses },
+            ASTQueryGoal::Invalid,
+        ))
+    }
+
+    // not keywords: only an aggregate right after := in a query
+    fn peek_aggregate(&self) -> Option<ASTAggregate> {
+        let op = match &self.ts.peek_any().value {
+            Token::Identifier(name) => ASTAggregate::named(name)?,
+            _ => return None,
+        };
+        if self.ts.peek_second().value == Token::Grouping(Grouping::LParen) { Some(op) } else { None }
+    }
+
+    // count(@x : @x in vampire, ...)
+    fn parse_aggregate(&mut self, op: ASTAggregate) -> ASTQueryGoalSource {
+        let op = self.ts.pop_any().replace(op);
+        self.ts.pop_any();
+        self.inside_parens(|s| {
+            let value = s.parse_expression();
+            if s.ts.pop_eq(&Token::Operator(Operator::OColon)).is_none() {
+                return ASTQueryGoalSource::Invalid(kpe("P0028", "expected : after the value to aggregate"))
+            }
+
+            let mut delimit = DelimitedMany::parens_basis();
+            delimit.lhs = None;
+            delimit.separator = Some(Token::Grouping(Grouping::Comma));
+            let body = s.group(
+                delimit,
+                |s| s.parse_query_goal(),
+                |items| ASTQueryExpression::QExpression { items },
+                ASTQueryExpression::Invalid,
+            );
+            ASTQueryGoalSource::Aggregate { op, value, body }
+        })
+    }
+
     fn parse_goal_source(&mut self) -> Parse<ASTQueryGoalSource> {
         return self.located(|s| {
             if s.ts.pop_keyword("in").is_some() {
                 let tbl = if let Some(ident) = s.ts.pop_identifier() { ident } else {
-                    return ASTQueryGoalSource::Invalid(kpe("expected table after 'in'"))
+                    return ASTQueryGoalSource::Invalid(kpe("P0023", "expected table after 'in'"))
                 };
                 ASTQueryGoalSource::In { from: tbl }
 
             } else if s.ts.pop_eq(&Token::Operator(Operator::OAssignNew)).is_some() {
+                if let Some(op) = s.peek_aggregate() { return s.parse_aggregate(op) }
                 let expression = s.parse_expression();
                 ASTQueryGoalSource::Assign { expression }
 
             } else if s.ts.pop_eq(&Token::Operator(Operator::OAssign)).is_some() {
                 s.parse_expression();
-                ASTQueryGoalSource::Invalid(kpe(
-                    "you can't use = in a query expression, only :="
-                ))
+                ASTQueryGoalSource::Invalid(kpe("P0024", "you can't use = in a query expression, only :="))
 
             } else {
                 s.ts.pop_any(); 
                 // TODO: Attempt to move all the way onto RHS delimiter
-                ASTQueryGoalSource::Invalid(kpe("unrecognized goal source: expected in or :="))
+                ASTQueryGoalSource::Invalid(kpe("P0025", "unrecognized goal source: expected in or :="))
             }
         })
     }
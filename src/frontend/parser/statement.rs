@@ -9,7 +9,8 @@ use super::internal_ast::*;
 impl<'a> Parser<'a> {
     pub fn parse_block(&mut self) -> Parse<ASTBlock> {
         let mut delimit = DelimitedMany::braces_basis();
-        delimit.separator = Some(Token::Grouping(Grouping::Semicolon));  // TODO: No semicolons?
+        delimit.separator = Some(Token::Grouping(Grouping::Semicolon));
+        delimit.separator_optional = true;
 
         self.group(
             delimit,
@@ -22,26 +23,25 @@ impl<'a> Parser<'a> {
     pub fn parse_statement(&mut self) -> Parse<ASTStatement> {
         self.located(|s| {
             if s.ts.pop_keyword("for").is_some() {
-                let arg = s.parse_plain_query_expression(
-                    (Token::Grouping(Grouping::LBrace), "start of block")
-                );
+                let clauses = s.parse_head_query_expressions();
                 let body = s.parse_block();
-                ASTStatement::For { arg, body }
+                ASTStatement::For { clauses, body }
             }
             else if s.ts.pop_keyword("if").is_some() {
-                let arg = s.parse_plain_query_expression(
-                    (Token::Grouping(Grouping::LBrace), "start of block")
-                );
+                let clauses = s.parse_head_query_expressions();
                 let body = s.parse_block();
                 if s.ts.pop_keyword("else").is_some() {
                     let else_ = s.parse_block();
-                    ASTStatement::If { arg, body, else_: Some(else_)}
+                    ASTStatement::If { clauses, body, else_: Some(else_)}
                 } else {
-                    ASTStatement::If { arg, body, else_: None }
+                    ASTStatement::If { clauses, body, else_: None }
                 }
             } else if s.ts.pop_keyword("return").is_some() {
-                let arg = s.parse_expression();
-               ASTStatement::Return { arg }
+                // like an assign target: one value, or several in brackets
+                let args = s.parse_assign_target();
+                ASTStatement::Return { args }
+            } else if let Some(op) = s.peek_write() {
+                s.parse_write(op)
             } else if s.ts.peek_identifier() {
                 let call = s.parse_call();
                 ASTStatement::Call { call }
@@ -56,15 +56,40 @@ impl<'a> Parser<'a> {
                     } else if s.ts.pop_eq(&Token::Operator(Operator::OAssignNew)).is_some() { 
                         true
                     } else {
-                        return ASTStatement::Invalid(kpe("expected := or = for an assignment"))
+                        return ASTStatement::Invalid(kpe("P0020", "expected := or = for an assignment"))
                     };
                 let arg = s.parse_expression();
                 ASTStatement::Assign { first, variable, arg }
             } else {
                 // TODO: Skip to the next likely statement start
                 s.skip_to_next_statement();
-                ASTStatement::Invalid(kpe("expected statement"))
+                ASTStatement::Invalid(kpe("P0019", "expected statement"))
             }
         })
     }
-} 
\ No newline at end of file
+
+    fn peek_write(&self) -> Option<ASTWrite> {
+        if self.ts.peek_keyword("insert") { Some(ASTWrite::Insert) }
+        else if self.ts.peek_keyword("remove") { Some(ASTWrite::Remove) }
+        else if self.ts.peek_keyword("expunge") { Some(ASTWrite::Expunge) }
+        else { None }
+    }
+
+    // insert [@x, @y] into table, remove [@x, @y] from table, expunge @x from table
+    fn parse_write(&mut self, op: ASTWrite) -> ASTStatement {
+        let op = self.ts.pop_any().replace(op);
+        let args = self.parse_assign_target();
+        let (preposition, error) = match op.value {
+            ASTWrite::Insert => ("into", kpe("P0029", "expected into after the row to insert")),
+            ASTWrite::Remove => ("from", kpe("P0030", "expected from after the row to remove")),
+            ASTWrite::Expunge => ("from", kpe("P0031", "expected from after the key to expunge")),
+        };
+        if self.ts.pop_keyword(preposition).is_none() {
+            return ASTStatement::Invalid(error)
+        }
+        let table = if let Some(table) = self.ts.pop_identifier() { table } else {
+            return ASTStatement::Invalid(kpe("P0032", "expected table to write to"))
+        };
+        ASTStatement::Write { op, args, table }
+    }
+}
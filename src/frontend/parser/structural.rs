@@ -13,7 +13,7 @@ impl<'a> Parser<'a> {
         let mut delimit = DelimitedMany::braces_basis();
         delimit.separator = None;
         delimit.lhs = None;
-        delimit.rhs = (Token::EOF, "end of file");
+        delimit.rhs = (Token::EOF, kpe("P0008", "end of file"));
 
         self.group(
             delimit,
@@ -34,22 +34,23 @@ impl<'a> Parser<'a> {
         } 
         else {
             self.skip_to_next_item().replace(
-                ASTItem::Invalid(kpe("unrecognized item: expected def or view"))
+                ASTItem::Invalid(kpe("P0011", "unrecognized item: expected def or view"))
             )
         } 
     }
 
     fn parse_def(&mut self) -> Parse<ASTDef> {
         self.located(|s| {
+            let docs = s.docs_before_next();
             if s.ts.pop_keyword("def").is_none() {
-                return ASTDef::Invalid(kpe("expected def"));
+                return ASTDef::Invalid(kpe("P0012", "expected def"));
             };
             let name = if let Some(fn_name) = s.ts.pop_identifier() { fn_name } else {
-                return ASTDef::Invalid(kpe("function name expected"));
+                return ASTDef::Invalid(kpe("P0013", "function name expected"));
             };
             let args = s.parse_args_parens();
 
-            let return_type = if s.ts.peek_eq(&Token::Grouping(Grouping::LBrack)) {
+            let return_type = if s.ts.peek_eq(&Token::Grouping(Grouping::LBrack)) || s.ts.peek_identifier() {
                 Some(s.parse_types_bracks())
             } else {
                 None
@@ -57,23 +58,24 @@ impl<'a> Parser<'a> {
 
             let body = s.parse_block();
 
-            ASTDef::Def { name, args, return_type, body }
+            ASTDef::Def { docs, name, args, return_type, body }
         })
     }
 
     fn parse_view(&mut self) -> Parse<ASTView> {
         self.located(|s| {
+            let docs = s.docs_before_next();
             if s.ts.pop_keyword("view").is_none() {
-                return ASTView::Invalid(kpe("expected view"));
+                return ASTView::Invalid(kpe("P0014", "expected view"));
             };
             let args = s.parse_args_bracks();
 
             if s.ts.pop_keyword("in").is_none() {
-                return ASTView::Invalid(kpe("expected in"));
+                return ASTView::Invalid(kpe("P0015", "expected in"));
             };
 
             let name = if let Some(name) = s.ts.pop_identifier() { name } else {
-                return ASTView::Invalid(kpe("view name expected"));
+                return ASTView::Invalid(kpe("P0016", "view name expected"));
             };
 
             let body = s.parse_block_query_expression();
@@ -83,10 +85,16 @@ impl<'a> Parser<'a> {
                 clauses.push(s.parse_block_query_expression());
             }
 
-            return ASTView::View { name, args, clauses }
+            return ASTView::View { docs, name, args, clauses }
         })
     }
 
+    // the doc comment right before the next token, if there is one
+    fn docs_before_next(&self) -> Option<Located<String>> {
+        let next = self.ts.location().start;
+        self.docs.iter().find(|d| d.end == next).cloned()
+    }
+
     fn parse_args_parens(&mut self) -> Parse<ASTArgs> {
         let mut delimit = DelimitedMany::parens_basis();
         delimit.separator = Some(Token::Grouping(Grouping::Comma));
@@ -113,7 +121,7 @@ impl<'a> Parser<'a> {
     fn parse_arg(&mut self) -> Located<ASTArg> {
         self.located(|s| {
             let name = if let Some(name) = s.ts.pop_variable() { name } else {
-                return ASTArg::Invalid(kpe("expected string"));
+                return ASTArg::Invalid(kpe("P0017", "expected string"));
             };
             let type_name = s.parse_optional_type();
             let loc = type_name.location();
@@ -127,6 +135,7 @@ impl<'a> Parser<'a> {
 
     fn parse_types_bracks(&mut self) -> Parse<ASTTypes> {
         let mut delimit = DelimitedMany::brackets_basis();
+        delimit.can_be_bare = true;
         delimit.separator = Some(Token::Grouping(Grouping::Comma));
         self.group(
             delimit,
@@ -142,7 +151,7 @@ impl<'a> Parser<'a> {
                 ASTType::Type { name }
             }
             else {
-                ASTType::Invalid(kpe("expected type"))
+                ASTType::Invalid(kpe("P0018", "expected type"))
             }
         })
     }
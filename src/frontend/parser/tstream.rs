@@ -112,4 +112,10 @@ impl<'a> TStream<'a> {
         if self.tokens.is_empty() { &self.eof }
         else { &self.tokens[0] }
     }
+
+    // the token after the next one
+    pub fn peek_second(&self) -> &Located<Token> {
+        if self.tokens.len() < 2 { &self.eof }
+        else { &self.tokens[1] }
+    }
 }
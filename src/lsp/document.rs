@@ -0,0 +1,238 @@
+use crate::codegen;
+use crate::diagnostics::{Diagnostic, SourceFile};
+use crate::frontend::{IncrementalModule, Located, ast};
+use crate::semantics::{self, Environment, Reference};
+
+// one open file. only parsing is incremental
+pub struct Document {
+    pub source: IncrementalModule,
+    pub file: SourceFile,
+    module: Option<ast::Module>,  // None if the text doesn't parse
+    references: Vec<Reference>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub views: Vec<String>,  // as of the last text that parsed, so completion works while you're typing
+}
+
+impl Document {
+    pub fn new(uri: &str, source: IncrementalModule, env: &Environment, views: Vec<String>) -> Document {
+        let file = SourceFile::new(uri, source.text());
+        let module = match source.module() {
+            Ok(m) => m.value,
+            Err(errors) => {
+                let diagnostics = errors.iter().map(|e| e.into()).collect();
+                return Document { source, file, module: None, references: vec![], diagnostics, views }
+            }
+        };
+
+        let views = module.items.iter().filter_map(|item| match &item.value {
+            ast::Item::View(v) => Some(v.name.value.clone()),
+            ast::Item::Def(_) => None,
+        }).collect();
+        let (references, unresolved) = semantics::resolve_module(&module, env);
+        let diagnostics = match semantics::check_resolved(&module, env, unresolved) {
+            Err(errors) => errors.iter().map(|e| e.into()).collect(),
+            Ok(typing) => match codegen::lower_module(&module, env, &typing) {
+                Err(errors) => errors.iter().map(|e| e.into()).collect(),
+                Ok(_) => vec![],
+            },
+        };
+        Document { source, file, module: Some(module), references, diagnostics, views }
+    }
+
+    pub fn items(&self) -> &[Located<ast::Item>] {
+        self.module.as_ref().map_or(&[], |m| &m.items)
+    }
+
+    // LSP counts lines from 0 and characters in UTF-16
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let (line, _) = self.file.line_col(offset);
+        let start = self.file.line_span(line).unwrap().start;
+        let character = self.file.text()[start..self.file.local(offset)].encode_utf16().count();
+        (line - 1, character)
+    }
+
+    pub fn offset(&self, line: usize, character: usize) -> Option<usize> {
+        offset(&self.file, line, character)
+    }
+
+    // the innermost name at offset
+    fn reference(&self, offset: usize) -> Option<Reference> {
+        self.references.iter()
+            .filter(|r| r.at.start <= offset && offset <= r.at.end)
+            .min_by_key(|r| r.at.end - r.at.start)
+            .copied()
+    }
+
+    pub fn definition(&self, offset: usize) -> Option<Located<()>> {
+        self.reference(offset).map(|r| r.target)
+    }
+
+    // the name being hovered, and what to say about it, in markdown
+    pub fn hover(&self, offset: usize) -> Option<(Located<()>, String)> {
+        let Reference { at, target } = self.reference(offset)?;
+
+        for item in self.items() {
+            if same(item.value.name(), target) {
+                let mut text = format!("```kupo\n{}\n```", signature(&item.value));
+                if let Some(docs) = item.value.docs() {
+                    text.push_str(&format!("\n\n{}", docs.value));
+                }
+                return Some((at, text))
+            }
+            // a view's clauses bind its args themselves, so those go by name
+            let name = &self.file.text()[self.file.local(target.start)..self.file.local(target.end)];
+            let arg = match &item.value {
+                ast::Item::Def(d) => d.args.iter().find(|a| same(&a.value.name, target)),
+                ast::Item::View(v) if item.start <= target.start && target.end <= item.end => {
+                    v.args.iter().find(|a| a.value.name.value == name)
+                }
+                ast::Item::View(_) => None,
+            };
+            if let Some(arg) = arg {
+                return Some((at, format!("```kupo\n{}\n```", describe_arg(&arg.value))))
+            }
+        }
+        None
+    }
+
+    // tables and views, if offset is right after `in`, or partway through the name that comes after it
+    pub fn completions(&self, offset: usize, env: &Environment) -> Vec<(String, &'static str)> {
+        let before = &self.file.text()[..self.file.local(offset)];
+        let before = before.trim_end_matches(is_name_char).trim_end();
+        let after_in = before.strip_suffix("in").is_some_and(|b| !b.ends_with(is_name_char));
+        if !after_in { return vec![] }
+
+        let tables = env.tables().iter().map(|t| (t.name.clone(), "table"));
+        let views = self.views.iter().map(|v| (v.clone(), "view"));
+        tables.chain(views).collect()
+    }
+}
+
+// a character past the end of its line means the end of the line
+pub fn offset(file: &SourceFile, line: usize, character: usize) -> Option<usize> {
+    let span = file.line_span(line + 1)?;
+    let mut units = 0;
+    for (i, c) in file.line_text(line + 1).char_indices() {
+        if units >= character { return Some(span.start + i) }
+        units += c.len_utf16();
+    }
+    Some(span.end)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// references only cover the name, so they end sooner
+fn same<T>(a: &Located<T>, b: Located<()>) -> bool {
+    a.start == b.start
+}
+
+// the item's header, the way you'd write it
+pub fn signature(item: &ast::Item) -> String {
+    let args = |args: &[Located<ast::Arg>]| args.iter().map(|a| describe_arg(&a.value)).collect::<Vec<_>>().join(", ");
+    match item {
+        ast::Item::Def(d) => {
+            // like kupo fmt writes it: one type on its own, anything else in brackets
+            let returns = match &d.return_type {
+                Some(types) if types.len() == 1 => format!(" {}", types[0].value.name.value),
+                Some(types) => format!(" [{}]", types.iter().map(|t| t.value.name.value.as_str()).collect::<Vec<_>>().join(", ")),
+                None => String::new(),
+            };
+            format!("def {}({}){}", d.name.value, args(&d.args), returns)
+        }
+        ast::Item::View(v) => format!("view [{}] in {}", args(&v.args), v.name.value),
+    }
+}
+
+fn describe_arg(arg: &ast::Arg) -> String {
+    match &arg.type_name {
+        Some(t) => format!("{} {}", arg.name.value, t.value.name.value),
+        None => arg.name.value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::demo;
+    use crate::frontend::IncrementalModule;
+
+    use super::Document;
+
+    const TEXT: &str = "\
+## lonely ones
+view [@x NPC] in lonely_vampire {
+    @x in vampire,
+    @x in lonely,
+}
+
+def twice(@n Integer) Integer {
+    return @n + @n
+}
+
+def pair() [Integer, Integer] {
+    return [twice(1), 2]
+}
+
+def main() {
+    for @x in lonely_vampire { print(@x) }
+}
+";
+
+    fn document(text: &str) -> Document {
+        Document::new("file:///test.kupo", IncrementalModule::new(text), &demo::environment(), vec![])
+    }
+
+    // where the nth copy of needle starts
+    fn at(text: &str, needle: &str, nth: usize) -> usize {
+        text.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    #[test]
+    fn definition_is_just_the_name() {
+        let d = document(TEXT);
+        let target = d.definition(at(TEXT, "print(@x", 0) + 6).unwrap();
+        let declared = at(TEXT, "for @x", 0) + 4;
+        assert_eq!((target.start, target.end), (declared, declared + 2));
+
+        let target = d.definition(at(TEXT, "twice(1)", 0)).unwrap();
+        assert_eq!(&TEXT[target.start..target.end], "twice");
+        let target = d.definition(at(TEXT, "in lonely_vampire", 1) + 3).unwrap();
+        assert_eq!(target.start, at(TEXT, "lonely_vampire", 0));
+    }
+
+    #[test]
+    fn hover() {
+        let d = document(TEXT);
+        let hover = |offset| d.hover(offset).map(|(at, text)| (TEXT[at.start..at.end].to_string(), text));
+
+        // a view's argument, where it's declared
+        let (name, text) = hover(at(TEXT, "[@x NPC", 0) + 2).unwrap();
+        assert_eq!((name.as_str(), text.as_str()), ("@x", "```kupo\n@x NPC\n```"));
+
+        let (name, text) = hover(at(TEXT, "lonely_vampire", 1)).unwrap();
+        assert_eq!(name, "lonely_vampire");
+        assert_eq!(text, "```kupo\nview [@x NPC] in lonely_vampire\n```\n\nlonely ones");
+
+        // one return type is written on its own
+        assert_eq!(hover(at(TEXT, "twice(1)", 0)).unwrap().1, "```kupo\ndef twice(@n Integer) Integer\n```");
+        assert_eq!(hover(at(TEXT, "pair", 0)).unwrap().1, "```kupo\ndef pair() [Integer, Integer]\n```");
+        assert_eq!(hover(at(TEXT, "@n + @n", 0)).unwrap().1, "```kupo\n@n Integer\n```");
+    }
+
+    #[test]
+    fn diagnostics() {
+        assert!(document(TEXT).diagnostics.is_empty());
+
+        // names that do resolve can still be followed
+        let text = "def main() {\n    @x := 1\n    print(@y)\n    print(@x)\n}\n";
+        let d = document(text);
+        assert_eq!(d.diagnostics.iter().map(|d| d.code).collect::<Vec<_>>(), vec![Some("C0018")]);
+        let target = d.definition(at(text, "print(@x", 0) + 6).unwrap();
+        assert_eq!(target.start, at(text, "@x", 0));
+
+        let d = document("def main( {");
+        assert!(!d.diagnostics.is_empty() && d.diagnostics.iter().all(|d| d.code.is_some_and(|c| c.starts_with('P'))));
+        assert!(d.items().is_empty());
+    }
+}
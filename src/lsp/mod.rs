@@ -0,0 +1,210 @@
+mod document;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::diagnostics::{Diagnostic, Json, SourceFile};
+use crate::frontend::{IncrementalModule, Located, ast};
+use crate::semantics::Environment;
+
+use self::document::{Document, offset, signature};
+
+// over stdin and stdout, checking against the same environment `kupo <file>` uses
+pub fn serve(env: Environment) {
+    let mut server = Server { env, documents: HashMap::new(), shutdown: false };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    while let Some(message) = read_message(&mut input) {
+        match Json::parse(&message) {
+            Some(message) => server.handle(&message),
+            None => send(&error_response(&Json::Null, -32700, "couldn't parse that as JSON")),
+        }
+    }
+}
+
+struct Server {
+    env: Environment,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn handle(&mut self, message: &Json) {
+        let id = message.get("id");
+        let params = message.get("params");
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or("");
+        let result = match message.get("method").as_str().unwrap_or("") {
+            "initialize" => capabilities(),
+            "shutdown" => { self.shutdown = true; Json::Null }
+            "exit" => std::process::exit(if self.shutdown { 0 } else { 1 }),
+
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").get("text").as_str().unwrap_or("");
+                return self.update(uri, IncrementalModule::new(text), vec![])
+            }
+            "textDocument/didChange" => {
+                let document = if let Some(d) = self.documents.remove(uri) { d } else { return };
+                let mut source = document.source;
+                // each change's range is in terms of the text after the last change
+                for change in params.get("contentChanges").as_array() {
+                    let text = change.get("text").as_str().unwrap_or("");
+                    let range = change.get("range");
+                    if *range == Json::Null {
+                        source = IncrementalModule::new(text);
+                        continue
+                    }
+                    let file = SourceFile::new(uri, source.text());
+                    let position = |p: &Json| offset(&file, p.get("line").as_usize()?, p.get("character").as_usize()?);
+                    if let (Some(start), Some(end)) = (position(range.get("start")), position(range.get("end"))) {
+                        source.edit(start, end.max(start), text)
+                    }
+                }
+                return self.update(uri, source, document.views)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return publish(uri, vec![])
+            }
+
+            method => {
+                let document = self.documents.get(uri);
+                let offset = document.and_then(|d| {
+                    let position = params.get("position");
+                    d.offset(position.get("line").as_usize()?, position.get("character").as_usize()?)
+                });
+                match (method, document, offset) {
+                    ("textDocument/definition", Some(d), Some(offset)) => match d.definition(offset) {
+                        Some(target) => location(d, uri, target),
+                        None => Json::Null,
+                    },
+                    ("textDocument/hover", Some(d), Some(offset)) => match d.hover(offset) {
+                        Some((at, text)) => Json::object(vec![
+                            ("contents", Json::object(vec![
+                                ("kind", Json::string("markdown")),
+                                ("value", Json::String(text)),
+                            ])),
+                            ("range", range(d, at)),
+                        ]),
+                        None => Json::Null,
+                    },
+                    ("textDocument/completion", Some(d), Some(offset)) => {
+                        Json::Array(d.completions(offset, &self.env).into_iter().map(|(name, kind)| Json::object(vec![
+                            ("label", Json::String(name)),
+                            ("kind", Json::number(22)),  // struct
+                            ("detail", Json::string(kind)),
+                        ])).collect())
+                    }
+                    ("textDocument/documentSymbol", Some(d), _) => {
+                        Json::Array(d.items().iter().map(|item| symbol(d, item)).collect())
+                    }
+                    ("textDocument/definition" | "textDocument/hover" | "textDocument/completion", _, _) => Json::Null,
+                    _ if *id != Json::Null => return send(&error_response(id, -32601, &format!("unsupported method: {}", method))),
+                    _ => return,  // notifications we don't care about
+                }
+            }
+        };
+        send(&Json::object(vec![("jsonrpc", Json::string("2.0")), ("id", id.clone()), ("result", result)]))
+    }
+
+    fn update(&mut self, uri: &str, source: IncrementalModule, views: Vec<String>) {
+        let document = Document::new(uri, source, &self.env, views);
+        publish(uri, document.diagnostics.iter().map(|d| diagnostic(&document, uri, d)).collect());
+        self.documents.insert(uri.to_string(), document);
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        ("capabilities", Json::object(vec![
+            ("textDocumentSync", Json::number(2)),  // incremental
+            ("definitionProvider", Json::Bool(true)),
+            ("hoverProvider", Json::Bool(true)),
+            ("documentSymbolProvider", Json::Bool(true)),
+            ("completionProvider", Json::object(vec![])),
+        ])),
+        ("serverInfo", Json::object(vec![("name", Json::string("kupo"))])),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) {
+    send(&Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("method", Json::string("textDocument/publishDiagnostics")),
+        ("params", Json::object(vec![("uri", Json::string(uri)), ("diagnostics", Json::Array(diagnostics))])),
+    ]))
+}
+
+fn diagnostic(document: &Document, uri: &str, d: &Diagnostic) -> Json {
+    let at = d.at.unwrap_or(Located { value: (), start: 0, end: 0 });
+    let mut message = d.message.clone();
+    for note in &d.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    Json::object(vec![
+        ("range", range(document, at)),
+        ("severity", Json::number(1)),  // error
+        ("code", d.code.map_or(Json::Null, Json::string)),
+        ("source", Json::string("kupo")),
+        ("message", Json::String(message)),
+        ("relatedInformation", Json::Array(d.labels.iter().map(|l| Json::object(vec![
+            ("location", location(document, uri, l.location())),
+            ("message", Json::string(&l.value)),
+        ])).collect())),
+    ])
+}
+
+fn symbol(document: &Document, item: &Located<ast::Item>) -> Json {
+    let name = item.value.name();
+    let kind = match item.value { ast::Item::Def(_) => 12, ast::Item::View(_) => 23 };  // function, struct
+    Json::object(vec![
+        ("name", Json::string(&name.value)),
+        ("detail", Json::String(signature(&item.value))),
+        ("kind", Json::number(kind)),
+        ("range", range(document, item.location())),
+        ("selectionRange", range(document, name.location())),
+    ])
+}
+
+fn location(document: &Document, uri: &str, at: Located<()>) -> Json {
+    Json::object(vec![("uri", Json::string(uri)), ("range", range(document, at))])
+}
+
+fn range(document: &Document, at: Located<()>) -> Json {
+    let position = |offset| {
+        let (line, character) = document.position(offset);
+        Json::object(vec![("line", Json::number(line)), ("character", Json::number(character))])
+    };
+    Json::object(vec![("start", position(at.start)), ("end", position(at.end))])
+}
+
+fn error_response(id: &Json, code: i32, message: &str) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id.clone()),
+        ("error", Json::object(vec![("code", Json::Number(code as f64)), ("message", Json::string(message))])),
+    ])
+}
+
+// None at the end of input, or if the framing's broken
+fn read_message(input: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 { return None }
+        let line = line.trim_end();
+        if line.is_empty() { break }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") { length = value.trim().parse().ok() }
+        }
+    }
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+fn send(message: &Json) {
+    let body = message.to_string();
+    let mut stdout = io::stdout();
+    write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    stdout.flush().unwrap();
+}
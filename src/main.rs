@@ -1,14 +1,22 @@
 #![feature(type_alias_impl_trait)]
 
 mod codegen;
+mod demo;
+mod diagnostics;
 mod runtime;
 mod frontend;
+mod lsp;
+mod repl;
+mod semantics;
+#[cfg(test)]
+mod testing;
 
 use std::fmt::Debug;
 
 use crate::codegen::*;
+use crate::diagnostics::{Diagnostic, Format, SourceFile, Style};
 use crate::runtime::{UntaggedValue, VM};
-use crate::frontend::parse_module;
+use crate::frontend::{cst, format_module, parse_module};
 
 fn main_old() {
     let mut args = codegen::StructBuilder::new();
@@ -21,10 +29,17 @@ fn main_old() {
     let program = codegen::Program {
         procedures: vec![
             Procedure{
+                name: "main".to_string(),
                 args, locals,
+                returns: vec![],
                 code: Bytecode { instructions: vec![
                     Instruction::RustCallRef { rust_fn: 0, arg: Register::Arg(0) },
                 ] },
+                debug: DebugInfo::default(),
+                rows: vec![],
+                iterators: 0,
+                emits: None,
+                aggregates: vec![],
             }
         ],
         ffi_ref: vec![
@@ -34,6 +49,9 @@ fn main_old() {
             }
         ],
         ffi_mut: vec![],
+        strings: vec![],
+        tables: vec![],
+        components: vec![],
     };
 
     let args = &program.procedures[0].args;
@@ -41,10 +59,120 @@ fn main_old() {
     untagged.mut_field(args,0).cast::<&'static str>().initialize("Hello, world!");
 
     let vm = VM::new(program);
-    vm.call(0, untagged);
+    vm.call(0, untagged).unwrap();
 }
 
+// parses, checks and lowers a file, reporting anything that goes wrong
+fn compile(path: &str, format: Format) -> Option<(SourceFile, Program)> {
+    let source = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => { println!("couldn't read {}: {}", path, e); return None }
+    };
+    let file = SourceFile::new(path, &source);
+    let report = |d: Diagnostic| print!("{}", format.show(&d, &[&file]));
+    let module = match parse_module(&source) {
+        Ok(m) => m,
+        Err(errors) => { for e in &errors { report(e.into()) }; return None }
+    };
+
+    let env = demo::environment();
+    let typing = match semantics::check_module(&module.value, &env) {
+        Ok(t) => t,
+        Err(errors) => { for e in &errors { report(e.into()) }; return None }
+    };
+    let program = match codegen::lower_module(&module.value, &env, &typing) {
+        Ok(p) => p,
+        Err(errors) => { for e in &errors { report(e.into()) }; return None }
+    };
+    Some((file, program))
+}
+
+// prints the file formatted, or whatever's stopping it from parsing
+fn fmt(path: &str, format: Format) -> bool {
+    let source = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => { println!("couldn't read {}: {}", path, e); return false }
+    };
+    let file = SourceFile::new(path, &source);
+    match format_module(&source) {
+        Ok(formatted) => { print!("{}", formatted); true }
+        Err(errors) => { for e in &errors { print!("{}", format.show(&e.into(), &[&file])) }; false }
+    }
+}
+
+// prints the file's syntax tree, trivia and all
+fn syntax(path: &str) -> bool {
+    match std::fs::read_to_string(path) {
+        Ok(source) => { print!("{}", cst::parse_syntax_tree(&source).outline()); true }
+        Err(e) => { println!("couldn't read {}: {}", path, e); false }
+    }
+}
+
+// runs main, or if view is given, prints every row of that view instead
+fn run(path: &str, view: Option<&str>, format: Format) {
+    let (file, program) = if let Some(compiled) = compile(path, format) { compiled } else { return };
+    let report = |d: Diagnostic| print!("{}", format.show(&d, &[&file]));
+
+    if let Some(view) = view {
+        let procedure = match program.procedure_named(view) {
+            Some(p) if program.procedures[p].emits.is_some() => p,
+            _ => { println!("no view named {}", view); return }
+        };
+        let vm = VM::new(program);
+        match vm.evaluate_view(procedure) {
+            Ok(rows) => {
+                for r in 0..rows.len() {
+                    let row: Vec<_> = (0..rows.arity()).map(|c| rows.column_type(c).debug(rows.get(r, c))).collect();
+                    println!("{:?}", row)
+                }
+            }
+            Err(e) => report((&e).into()),
+        }
+        return
+    }
+
+    let main = if let Some(main) = program.procedure_named("main") { main } else {
+        println!("no main procedure"); return
+    };
+    let args = &program.procedures[main].args;
+    if !args.fields.is_empty() {
+        println!("main can't take arguments"); return
+    }
+    let args = UntaggedValue::instantiate(args);
+
+    let vm = VM::new(program);
+    if let Err(e) = vm.call(main, args) {
+        report((&e).into())
+    }
+}
+
+// kupo <file> [view], kupo check|fmt|syntax <file>, kupo repl [file], kupo lsp. --json anywhere for JSON diagnostics
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let json = args.iter().any(|a| a == "--json");
+    args.retain(|a| a != "--json");
+    let format = if json { Format::Json } else { Format::Text(Style::for_stdout()) };
+
+    match args.iter().map(|a| a.as_str()).collect::<Vec<_>>().as_slice() {
+        ["lsp"] => return lsp::serve(demo::environment()),
+        ["repl", rest @ ..] => return repl::repl(rest.first().copied(), demo::environment()),
+        // nothing gets run: the exit code says whether the file's ok
+        ["check", path] => {
+            if compile(path, format).is_none() { std::process::exit(1) }
+            return
+        }
+        ["fmt", path] => {
+            if !fmt(path, format) { std::process::exit(1) }
+            return
+        }
+        ["syntax", path] => {
+            if !syntax(path) { std::process::exit(1) }
+            return
+        }
+        [path, rest @ ..] => return run(path, rest.first().copied(), format),
+        [] => {}
+    }
+
     main_old();
     println!("{:?}", parse_module("
     view [@x NPC] in lonely_vampire {}
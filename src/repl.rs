@@ -0,0 +1,295 @@
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::codegen::{self, Scratch};
+use crate::diagnostics::{Diagnostic, SourceFile, Style, render};
+use crate::frontend::{Located, ReplInput, ast, parse_module, parse_repl_input};
+use crate::runtime::{Debugger, Paused, Step, Tuples, UntaggedValue, VM};
+use crate::semantics::{self, Environment};
+
+// the def each input gets wrapped in: no one can write this name, so it can't collide
+const SCRATCH: &str = "%repl";
+
+// each input is compiled along with the module. := variables get passed to the next input as args
+pub struct Repl {
+    module: ast::Module,
+    file: Rc<SourceFile>,
+    input: SourceFile,  // the input being run right now
+    env: Environment,
+    offset: usize,  // past the end of the module's source, so input locations never collide with it
+    breakpoints: Vec<Located<()>>,
+    kept: Vec<String>,
+    values: Tuples,  // exactly one row: the value of each kept variable
+}
+
+impl Repl {
+    pub fn new(module: ast::Module, file: SourceFile, env: Environment) -> Repl {
+        let mut values = Tuples::new(&[]);
+        values.push(&[]);
+        let offset = file.text().len() + 1;
+        let input = SourceFile::at("<input>", "", offset);
+        Repl { module, file: Rc::new(file), input, env, offset, breakpoints: vec![], kept: vec![], values }
+    }
+
+    // false if the input isn't finished yet. give_up says to report why instead of waiting for more
+    fn enter(&mut self, text: &str, give_up: bool) -> bool {
+        self.input = SourceFile::at("<input>", text, self.offset);
+        match parse_repl_input(text, self.offset) {
+            Ok(ReplInput::Incomplete(_)) if !give_up => return false,
+            Ok(ReplInput::Incomplete(errors)) | Err(errors) => for e in &errors { self.report(e.into()) },
+            Ok(input) => self.run(input),
+        }
+        true
+    }
+
+    fn report(&self, diagnostic: Diagnostic) {
+        print!("{}", render(&diagnostic, &[&self.file, &self.input], Style::for_stdout()))
+    }
+
+    // lines that start with a colon talk to the REPL instead of running anything
+    fn command(&mut self, command: &str) {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            [":break", line] => {
+                match line.parse().ok().and_then(|l| self.file.line_span(l)) {
+                    Some(span) => self.breakpoints.push(span),
+                    None => println!("there's no line {} in the module", line),
+                }
+            }
+            [":delete"] => self.breakpoints.clear(),
+            [":doc", name] => match (self.module.item(name), self.module.docs(name)) {
+                (None, _) => println!("there's no def or view named {}", name),
+                (Some(_), None) => println!("{} doesn't have a doc comment", name),
+                (Some(_), Some(docs)) => println!("{}", docs),
+            },
+            _ => println!("commands: :break <line>, :delete, :doc <name>"),
+        }
+    }
+
+    fn run(&mut self, input: ReplInput) {
+        let (body, scratch) = match input {
+            ReplInput::Statements(block) => (block, Scratch::Statements),
+            ReplInput::Query(query) => {
+                let loc = query.location();
+                let for_ = ast::Statement::For { clauses: vec![query], body: loc.replace(ast::Block { items: vec![] }) };
+                (loc.replace(ast::Block { items: vec![loc.replace(for_)] }), Scratch::Solutions)
+            }
+            ReplInput::Incomplete(_) => return,
+        };
+
+        let loc = body.location();
+        let args = self.kept.iter().enumerate().map(|(i, name)| {
+            let type_name = self.env.type_name(self.values.column_type(i)).to_string();
+            loc.replace(ast::Arg {
+                name: loc.replace(name.clone()),
+                type_name: Some(loc.replace(ast::Type { name: loc.replace(type_name) })),
+            })
+        }).collect();
+        let def = ast::Def { docs: None, name: loc.replace(SCRATCH.to_string()), args, return_type: None, body };
+
+        self.module.items.push(loc.replace(ast::Item::Def(def)));
+        let result = self.evaluate(scratch);
+        self.module.items.pop();
+        let (columns, rows) = if let Some(r) = result { r } else { return };
+
+        match scratch {
+            Scratch::Statements => {
+                for (c, name) in columns.iter().enumerate().skip(self.kept.len()) {
+                    println!("{} = {:?}", name, rows.column_type(c).debug(rows.get(0, c)));
+                }
+                self.kept = columns;
+                self.values = rows;
+            }
+            Scratch::Solutions => {
+                if rows.len() == 0 { println!("no") }
+                for r in 0..rows.len() {
+                    if columns.is_empty() { println!("yes"); continue }
+                    let row: Vec<String> = columns.iter().enumerate().map(|(c, name)|
+                        format!("{} = {:?}", name, rows.column_type(c).debug(rows.get(r, c)))
+                    ).collect();
+                    println!("{}", row.join(", "));
+                }
+            }
+        }
+    }
+
+    fn evaluate(&self, scratch: Scratch) -> Option<(Vec<String>, Tuples)> {
+        let typing = match semantics::check_module(&self.module, &self.env) {
+            Ok(t) => t,
+            Err(errors) => { for e in &errors { self.report(e.into()) }; return None }
+        };
+        let (program, columns) = match codegen::lower_scratch(&self.module, &self.env, &typing, SCRATCH, scratch) {
+            Ok(p) => p,
+            Err(errors) => { for e in &errors { self.report(e.into()) }; return None }
+        };
+
+        let procedure = program.procedure_named(SCRATCH).unwrap();
+        let args = {
+            let args = &program.procedures[procedure].args;
+            let mut untagged = UntaggedValue::instantiate(args);
+            for (i, field) in args.fields.iter().enumerate() {
+                field.type_data.clone_into(self.values.get(0, i), untagged.mut_field(args, i));
+            }
+            untagged
+        };
+
+        let vm = if self.breakpoints.is_empty() { VM::new(program) } else {
+            VM::debug(program, Debugger::new(self.breakpoints.clone(), pause_prompt(self.file.clone())))
+        };
+        match vm.evaluate(procedure, args) {
+            Ok(rows) => Some((columns, rows)),
+            Err(mut e) => {
+                for entry in &mut e.trace {
+                    if entry.procedure == SCRATCH { entry.procedure = "your input".to_string() }
+                }
+                self.report((&e).into());
+                None
+            }
+        }
+    }
+}
+
+// reads input a line at a time, waiting for more lines while a group is still open
+pub fn repl(path: Option<&str>, env: Environment) {
+    let source = match path.map(std::fs::read_to_string) {
+        None => String::new(),
+        Some(Ok(s)) => s,
+        Some(Err(e)) => { println!("couldn't read {}: {}", path.unwrap(), e); return }
+    };
+    let file = SourceFile::new(path.unwrap_or("<empty>"), &source);
+    let report = |d: Diagnostic| print!("{}", render(&d, &[&file], Style::for_stdout()));
+    let module = match parse_module(&source) {
+        Ok(m) => m,
+        Err(errors) => { for e in &errors { report(e.into()) }; return }
+    };
+    if let Err(errors) = semantics::check_module(&module.value, &env) {
+        for e in &errors { report(e.into()) }; return
+    }
+    let mut repl = Repl::new(module.value, file, env);
+
+    let stdin = io::stdin();
+    let mut buffer = String::new();
+    loop {
+        print!("{}", if buffer.is_empty() { "> " } else { "| " });
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => buffer.push_str(&line),
+            Err(e) => { println!("couldn't read input: {}", e); break }
+        }
+        if buffer.trim().is_empty() { buffer.clear(); continue }
+        // a blank line ends the input, finished or not
+        if buffer.starts_with(':') {
+            repl.command(buffer.trim());
+            buffer.clear();
+            continue
+        }
+
+        if repl.enter(&buffer, line.trim().is_empty()) { buffer.clear() }
+    }
+}
+
+// reads debugger commands while the program's paused
+fn pause_prompt(file: Rc<SourceFile>) -> impl FnMut(&Paused<'_>) -> Step {
+    move |paused| {
+        let top = paused.frame(0);
+        println!("paused in {} at {}", top.procedure(), describe(&file, top.location()));
+        loop {
+            print!("(paused) ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = io::stdin().lock().read_line(&mut line) { return Step::Stop }
+
+            match line.trim() {
+                "" => {}
+                "step" | "s" => return Step::Into,
+                "next" | "n" => return Step::Over,
+                "out" | "o" => return Step::Out,
+                "continue" | "c" => return Step::Continue,
+                "stop" | "q" => return Step::Stop,
+                "where" | "bt" => {
+                    for n in 0..paused.depth() {
+                        let frame = paused.frame(n);
+                        println!("#{} {} at {}", n, frame.procedure(), describe(&file, frame.location()));
+                    }
+                }
+                "locals" => {
+                    for (name, value) in top.variables() {
+                        println!("{} = {:?}", name, value);
+                    }
+                }
+                name if name.starts_with('@') => match top.variable(name) {
+                    Some(value) => println!("{} = {:?}", name, value),
+                    None => println!("{} doesn't have a value here", name),
+                },
+                _ => println!("commands: step, next, out, continue, stop, where, locals, @variable"),
+            }
+        }
+    }
+}
+
+// the line of the module a location is on, or a note that it's in the REPL's own input
+fn describe(file: &SourceFile, location: Option<Located<()>>) -> String {
+    match location {
+        Some(l) if file.contains(l.start) => {
+            let (line, _) = file.line_col(l.start);
+            format!("line {}: {}", line, file.line_text(line).trim())
+        }
+        _ => "your input".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo;
+
+    fn repl(source: &str) -> Repl {
+        let module = parse_module(source).unwrap().value;
+        Repl::new(module, SourceFile::new("test.kupo", source), demo::environment())
+    }
+
+    fn kept(repl: &Repl) -> Vec<String> {
+        repl.kept.iter().enumerate().map(|(c, name)| {
+            format!("{} = {:?}", name, repl.values.column_type(c).debug(repl.values.get(0, c)))
+        }).collect()
+    }
+
+    #[test]
+    fn keeps_variables() {
+        let mut repl = repl("");
+        assert!(repl.enter("@x := 1 + 2\n", false));
+        assert!(repl.enter("@y := @x * 2\n@x = 4\n", false));
+        assert_eq!(kept(&repl), vec!["@x = 4", "@y = 6"]);
+        // nothing changes if the input doesn't compile or doesn't finish
+        assert!(repl.enter("@z := @nope\n", false));
+        assert!(repl.enter("@z := 1 / 0\n", false));
+        assert_eq!(kept(&repl), vec!["@x = 4", "@y = 6"]);
+        // a query doesn't keep anything either
+        assert!(repl.enter("@v in vampire\n", false));
+        assert_eq!(kept(&repl), vec!["@x = 4", "@y = 6"]);
+    }
+
+    #[test]
+    fn waits_for_the_rest() {
+        let mut repl = repl("");
+        assert!(!repl.enter("for @v in vampire {\n", false));
+        assert!(!repl.enter("for @v in vampire {\n    @n := 1\n", false));
+        assert!(repl.enter("for @v in vampire {\n    @n := 1\n}\n", false));
+        // unless it's told to give up
+        assert!(repl.enter("for @v in vampire {\n", true));
+    }
+
+    #[test]
+    fn commands() {
+        let mut repl = repl("def f() {\n    print(1)\n}\n");
+        repl.command(":break 2");
+        repl.command(":break 40");
+        assert_eq!(repl.breakpoints.len(), 1);
+        assert!(repl.file.contains(repl.breakpoints[0].start));
+        repl.command(":delete");
+        assert!(repl.breakpoints.is_empty());
+    }
+}
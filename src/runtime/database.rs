@@ -0,0 +1,187 @@
+use std::{cell::{Cell, RefCell}, fmt::Debug, rc::Rc};
+
+use moogle::{IdLike, Set};
+
+use crate::codegen::TypeData;
+
+use super::dynamics::{DynToMany, DynToOne};
+use super::{KupoRuntimeError, Relation, id_type, kre};
+
+// the host's tables, by name. registering checks the declared types against what the structure holds, like swapping does
+// tables can be swapped for ones with the same column types; code compiled before that has to be recompiled
+pub struct Database {
+    tables: Vec<Table>,
+}
+
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<TypeData>,
+    live: Rc<Live>,
+}
+
+struct Live {
+    relation: RefCell<Rc<dyn Relation>>,
+    version: Cell<usize>,  // how many times it's been swapped
+}
+
+// a table as it was when some code was compiled against it
+pub struct Pinned {
+    name: String,
+    live: Rc<Live>,
+    version: usize,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Database { tables: vec![] }
+    }
+
+    pub fn add_set<A: IdLike+Debug>(&mut self, name: &str, key: TypeData, set: Set<A>) -> Result<(), String> {
+        expect_type(name, "keys", key, id_type::<A>())?;
+        self.add(name, vec![key], Rc::new(set))
+    }
+
+    pub fn add_to_one(&mut self, name: &str, key: TypeData, value: TypeData, relation: impl DynToOne + 'static) -> Result<(), String> {
+        expect_type(name, "keys", key, relation.key_type())?;
+        expect_type(name, "values", value, relation.value_type())?;
+        self.add(name, vec![key, value], Rc::new(Box::new(relation) as Box<dyn DynToOne>))
+    }
+
+    pub fn add_to_many(&mut self, name: &str, key: TypeData, value: TypeData, relation: impl DynToMany + 'static) -> Result<(), String> {
+        expect_type(name, "keys", key, relation.key_type())?;
+        expect_type(name, "values", value, relation.value_type())?;
+        self.add(name, vec![key, value], Rc::new(Box::new(relation) as Box<dyn DynToMany>))
+    }
+
+    // for anything that isn't a moogle structure: it says what its own columns are
+    pub fn add_table(&mut self, name: &str, relation: Rc<dyn Relation>) -> Result<(), String> {
+        self.add(name, relation.columns(), relation)
+    }
+
+    fn add(&mut self, name: &str, columns: Vec<TypeData>, relation: Rc<dyn Relation>) -> Result<(), String> {
+        if self.table_named(name).is_some() {
+            return Err(format!("there's already a table named {}", name))
+        }
+        let live = Rc::new(Live { relation: RefCell::new(relation), version: Cell::new(0) });
+        self.tables.push(Table { name: name.to_string(), columns, live });
+        Ok(())
+    }
+
+    // hands back the old relation. The new one has to have exactly the same column types
+    pub fn swap(&self, name: &str, relation: Rc<dyn Relation>) -> Result<Rc<dyn Relation>, String> {
+        let table = if let Some(t) = self.table_named(name) { t } else { return Err(format!("unknown table: {}", name)) };
+        let columns = relation.columns();
+        if columns.len() != table.columns.len() {
+            return Err(format!("{} has {} column(s), but its replacement has {}", name, table.columns.len(), columns.len()))
+        }
+        for (i, (old, new)) in table.columns.iter().zip(&columns).enumerate() {
+            if old != new {
+                return Err(format!("column {} of {} holds {}, but its replacement's holds {}", i + 1, name, old.rust_name, new.rust_name))
+            }
+        }
+        table.live.version.set(table.live.version.get() + 1);
+        Ok(table.live.relation.replace(relation))
+    }
+
+    pub fn table_named(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|t| t.name == name)
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+}
+
+impl Table {
+    pub fn relation(&self) -> Rc<dyn Relation> {
+        self.live.relation.borrow().clone()
+    }
+
+    pub fn pin(&self) -> Pinned {
+        Pinned { name: self.name.clone(), live: self.live.clone(), version: self.live.version.get() }
+    }
+}
+
+impl Pinned {
+    pub fn relation(&self) -> Result<Rc<dyn Relation>, KupoRuntimeError> {
+        if self.live.version.get() != self.version {
+            return Err(kre(&format!("{} was swapped for a new table after this code was compiled: it has to be recompiled", self.name)))
+        }
+        Ok(self.live.relation.borrow().clone())
+    }
+}
+
+fn expect_type(name: &str, what: &str, registered: TypeData, holds: TypeData) -> Result<(), String> {
+    if registered == holds { return Ok(()) }
+    Err(format!("{} was registered with {} of type {}, but it holds {}", name, what, registered.rust_name, holds.rust_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use moogle::{Set, ToOne};
+
+    use crate::codegen;
+    use crate::demo;
+    use crate::frontend::parse_module;
+    use crate::runtime::dynamics::DynToOne;
+    use crate::runtime::{UntaggedValue, VM, id_type};
+    use crate::semantics;
+    use crate::testing::run;
+
+    use super::Database;
+
+    #[test]
+    fn registering() {
+        let mut db = Database::new();
+        let (small, big) = (id_type::<u8>(), id_type::<u64>());
+        assert!(db.add_set("a", small, Set::<u8>::new()).is_ok());
+        assert_eq!(db.table_named("a").unwrap().columns, vec![small]);
+
+        // mismatches are the host's mistake, but they shouldn't take the process down
+        assert_eq!(db.add_set("b", big, Set::<u8>::new()), Err("b was registered with keys of type u64, but it holds u8".to_string()));
+        assert!(db.add_to_one("c", small, big, ToOne::<u8, u8>::new()).unwrap_err().contains("values of type u64"));
+        assert_eq!(db.add_set("a", small, Set::<u8>::new()), Err("there's already a table named a".to_string()));
+        assert_eq!(db.tables().len(), 1);
+    }
+
+    #[test]
+    fn swapping() {
+        let mut db = Database::new();
+        db.add_set("a", id_type::<u8>(), Set::<u8>::new()).unwrap();
+        let pinned = db.table_named("a").unwrap().pin();
+
+        let to_one = Rc::new(Box::new(ToOne::<u8, u8>::new()) as Box<dyn DynToOne>);
+        assert_eq!(db.swap("a", to_one).err(), Some("a has 1 column(s), but its replacement has 2".to_string()));
+        assert_eq!(db.swap("a", Rc::new(Set::<u64>::new())).err(), Some("column 1 of a holds u8, but its replacement's holds u64".to_string()));
+        assert_eq!(db.swap("b", Rc::new(Set::<u8>::new())).err(), Some("unknown table: b".to_string()));
+        // none of that changed anything
+        assert!(pinned.relation().is_ok());
+
+        assert!(db.swap("a", Rc::new(Set::<u8>::new())).is_ok());
+        assert!(pinned.relation().err().unwrap().message.contains("has to be recompiled"));
+        assert!(db.table_named("a").unwrap().pin().relation().is_ok());
+    }
+
+    #[test]
+    fn recompiling() {
+        let source = "def main() {\n    new_month(\"May\")\n}\n\
+            view [@x NPC, @s String] in monthly { [@x, @s] in lonely_vampires_monthly }";
+        assert!(run(source, "monthly").unwrap_err().contains("lonely_vampires_monthly was swapped for a new table"));
+
+        // compiling it again against the same database picks up the new table
+        let env = demo::environment();
+        let module = parse_module(source).unwrap().value;
+        let compile = || codegen::lower_module(&module, &env, &semantics::check_module(&module, &env).unwrap()).ok().unwrap();
+        let program = compile();
+        let main = program.procedure_named("main").unwrap();
+        let args = UntaggedValue::instantiate(&program.procedures[main].args);
+        VM::new(program).call(main, args).unwrap();
+
+        let program = compile();
+        let monthly = program.procedure_named("monthly").unwrap();
+        let rows = VM::new(program).evaluate_view(monthly).unwrap();
+        assert_eq!(format!("{:?}", rows.column_type(1).debug(rows.get(0, 1))), "\"Vlad, May\"");
+    }
+}
@@ -0,0 +1,54 @@
+use moogle::IdLike;
+
+use super::super::MutToUnknown;
+use super::write;
+
+// like Rows: out has an entry per column, and each one that's Some gets filled in
+pub struct DynIter<'a>(Box<dyn Erased + 'a>);
+
+impl<'a> DynIter<'a> {
+    pub(super) fn pairs<K: IdLike, V: IdLike>(iter: impl DoubleEndedIterator<Item=(K, V)> + 'a) -> DynIter<'a> {
+        DynIter(Box::new(Pairs(iter)))
+    }
+
+    pub(super) fn singles<A: IdLike>(iter: impl DoubleEndedIterator<Item=A> + 'a) -> DynIter<'a> {
+        DynIter(Box::new(Singles(iter)))
+    }
+
+    pub fn next(&mut self, out: &mut [Option<MutToUnknown<'_>>]) -> bool {
+        self.0.next(false, out)
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]  // for hosts: relations only read forwards
+    pub fn next_back(&mut self, out: &mut [Option<MutToUnknown<'_>>]) -> bool {
+        self.0.next(true, out)
+    }
+}
+
+trait Erased {
+    fn next(&mut self, back: bool, out: &mut [Option<MutToUnknown<'_>>]) -> bool;
+}
+
+struct Pairs<I>(I);
+
+impl<K: IdLike, V: IdLike, I: DoubleEndedIterator<Item=(K, V)>> Erased for Pairs<I> {
+    fn next(&mut self, back: bool, out: &mut [Option<MutToUnknown<'_>>]) -> bool {
+        let item = if back { self.0.next_back() } else { self.0.next() };
+        match item {
+            Some((k, v)) => { write(&mut out[0], k); write(&mut out[1], v); true }
+            None => false,
+        }
+    }
+}
+
+struct Singles<I>(I);
+
+impl<A: IdLike, I: DoubleEndedIterator<Item=A>> Erased for Singles<I> {
+    fn next(&mut self, back: bool, out: &mut [Option<MutToUnknown<'_>>]) -> bool {
+        let item = if back { self.0.next_back() } else { self.0.next() };
+        match item {
+            Some(a) => { write(&mut out[0], a); true }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+mod iter;
+mod to_many;
+mod to_one;
+
+pub use self::iter::DynIter;
+pub use self::to_many::DynToMany;
+pub use self::to_one::DynToOne;
+
+use std::{any::Any, rc::Rc};
+
+use super::{MutToUnknown, RefToUnknown};
+
+// moogle structures with their types erased, so queries over them get compiled once.
+// keys and values go through RefToUnknown and MutToUnknown, so dynamism does the type checks
+
+// one side of a junction: the two sides are different kinds of structure, but share their contents
+pub struct Forward<J>(pub Rc<J>);
+pub struct Backward<J>(pub Rc<J>);
+
+pub(crate) fn read<A: Any+Copy>(ptr: RefToUnknown<'_>) -> A {
+    *ptr.cast::<A>().get()
+}
+
+// for out params that are None when the caller doesn't want that column
+pub(crate) fn write<A: Any>(out: &mut Option<MutToUnknown<'_>>, a: A) {
+    if let Some(ptr) = out.take() {
+        ptr.cast::<A>().initialize(a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use moogle::{OneToMany, SharedAnyToMany, SharedAnyToOne, ToOne};
+
+    use crate::runtime::{Tuples, id_type};
+
+    use super::{Backward, DynIter, DynToMany, DynToOne, Forward, read};
+
+    // a one-column table, so there's something to point a RefToUnknown at
+    fn value(x: usize) -> Tuples {
+        let mut t = Tuples::new(&[id_type::<usize>()]);
+        t.push_with(|_, ptr| ptr.cast::<usize>().initialize(x));
+        t
+    }
+
+    fn drain(arity: usize, mut iter: DynIter<'_>, back: bool) -> Vec<Vec<usize>> {
+        let mut rows = Tuples::new(&vec![id_type::<usize>(); arity]);
+        while rows.push_from(|out| if back { iter.next_back(out) } else { iter.next(out) }) {}
+        (0..rows.len()).map(|r| (0..arity).map(|c| read::<usize>(rows.get(r, c))).collect()).collect()
+    }
+
+    fn both_ways<'a>(arity: usize, iter: impl Fn() -> DynIter<'a>) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        (drain(arity, iter(), false), drain(arity, iter(), true))
+    }
+
+    #[test]
+    fn to_one() {
+        let lives_in = ToOne::<usize, usize>::new();
+        for (k, v) in [(3, 30), (1, 10), (2, 10)] { lives_in.fwd().insert(k, v); }
+        let lives_in: &dyn DynToOne = &lives_in;
+
+        assert_eq!(lives_in.len(), 3);
+        assert!(lives_in.contains_key(value(2).get(0, 0)));
+        assert!(!lives_in.contains_key(value(4).get(0, 0)));
+
+        let (fwd, back) = both_ways(2, || lives_in.iter());
+        assert_eq!(fwd, vec![vec![1, 10], vec![2, 10], vec![3, 30]]);
+        assert_eq!(back, fwd.iter().rev().cloned().collect::<Vec<_>>());
+        assert_eq!(both_ways(1, || lives_in.keys()), (vec![vec![1], vec![2], vec![3]], vec![vec![3], vec![2], vec![1]]));
+        assert_eq!(both_ways(1, || lives_in.values()), (vec![vec![10], vec![10], vec![30]], vec![vec![30], vec![10], vec![10]]));
+    }
+
+    #[test]
+    fn to_many() {
+        let sired = Rc::new(OneToMany::<usize, usize>::new());
+        for (k, v) in [(0, 3), (0, 1), (3, 2)] { sired.fwd().insert(k, v); }
+        let (fwd, bwd) = (Forward(sired.clone()), Backward(sired));
+        let (fwd, bwd): (&dyn DynToMany, &dyn DynToOne) = (&fwd, &bwd);
+
+        assert_eq!((fwd.len(), fwd.keys_len()), (3, 2));
+        assert!(fwd.contains_key(value(3).get(0, 0)));
+        assert!(!fwd.contains_key(value(1).get(0, 0)));
+
+        let (pairs, back) = both_ways(2, || fwd.iter());
+        assert_eq!(pairs, vec![vec![0, 1], vec![0, 3], vec![3, 2]]);
+        assert_eq!(back, pairs.iter().rev().cloned().collect::<Vec<_>>());
+        assert_eq!(both_ways(1, || fwd.keys()), (vec![vec![0], vec![3]], vec![vec![3], vec![0]]));
+        assert_eq!(both_ways(1, || fwd.values()), (vec![vec![1], vec![3], vec![2]], vec![vec![2], vec![3], vec![1]]));
+
+        // the other side of the junction sees the same pairs, turned around
+        assert_eq!(bwd.len(), 3);
+        assert_eq!(both_ways(2, || bwd.iter()).0, vec![vec![1, 0], vec![2, 3], vec![3, 0]]);
+        assert_eq!(both_ways(1, || bwd.keys()).1, vec![vec![3], vec![2], vec![1]]);
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Debug;
+
+use moogle::{IdLike, ManyToMany, ManyToOne, OneToMany, SharedAnySet, SharedAnyToMany, ToMany};
+use moogle::{many_to_many, many_to_one, one_to_many, shared_to_many};
+
+use crate::codegen::TypeData;
+use crate::runtime::id_type;
+
+use super::super::RefToUnknown;
+use super::{Backward, DynIter, Forward, read};
+
+// Every key has a set of values.
+#[cfg_attr(not(test), allow(dead_code))]  // for hosts: relations only need get, contains and iter
+pub trait DynToMany {
+    fn key_type(&self) -> TypeData;
+    fn value_type(&self) -> TypeData;
+
+    // k's values, in order
+    fn get(&self, k: RefToUnknown<'_>) -> DynIter<'_>;
+    fn contains_key(&self, k: RefToUnknown<'_>) -> bool;
+    fn contains(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool;
+    fn len(&self) -> usize;  // how many pairs
+    fn keys_len(&self) -> usize;
+
+    // in key order, then value order
+    fn iter(&self) -> DynIter<'_>;
+    fn keys(&self) -> DynIter<'_>;
+    fn values(&self) -> DynIter<'_>;
+
+    // each one is true if anything changed
+    fn insert(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool;
+    fn expunge(&self, k: RefToUnknown<'_>) -> bool;  // every one of k's values
+    fn remove(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool;
+}
+
+// a moogle structure (or one side of a junction) where every key has a set of values
+pub trait AsToMany {
+    type K: IdLike+Debug;
+    type V: IdLike+Debug;
+    type View<'a>: SharedAnyToMany<'a, Self::K, Self::V> where Self: 'a;
+
+    fn as_to_many(&self) -> Self::View<'_>;
+}
+
+impl<T: AsToMany> DynToMany for T {
+    fn key_type(&self) -> TypeData { id_type::<T::K>() }
+    fn value_type(&self) -> TypeData { id_type::<T::V>() }
+
+    fn get(&self, k: RefToUnknown<'_>) -> DynIter<'_> { DynIter::singles(self.as_to_many().get(read(k)).iter()) }
+    fn contains_key(&self, k: RefToUnknown<'_>) -> bool { self.as_to_many().contains_key(read(k)) }
+    fn contains(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool { self.as_to_many().get(read(k)).contains(read(v)) }
+    fn len(&self) -> usize { self.as_to_many().len() }
+    fn keys_len(&self) -> usize { self.as_to_many().keys_len() }
+
+    fn iter(&self) -> DynIter<'_> { DynIter::pairs(self.as_to_many().iter()) }
+    fn keys(&self) -> DynIter<'_> { DynIter::singles(self.as_to_many().keys()) }
+    fn values(&self) -> DynIter<'_> { DynIter::singles(self.as_to_many().values()) }
+
+    fn insert(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool {
+        // moogle only hands back the value if it was already there
+        self.as_to_many().insert(read(k), read(v)).is_none()
+    }
+
+    // what moogle hands back differs from structure to structure
+    fn expunge(&self, k: RefToUnknown<'_>) -> bool {
+        let (view, k) = (self.as_to_many(), read(k));
+        let had = view.contains_key(k);
+        view.expunge(k);
+        had
+    }
+
+    fn remove(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool { self.as_to_many().remove(read(k), read(v)).is_some() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToMany for ToMany<A, B> {
+    type K = A;
+    type V = B;
+    type View<'a> = shared_to_many::Fwd<'a, A, B>;
+
+    fn as_to_many(&self) -> Self::View<'_> { self.fwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToMany for Forward<OneToMany<A, B>> {
+    type K = A;
+    type V = B;
+    type View<'a> = one_to_many::Fwd<'a, A, B>;
+
+    fn as_to_many(&self) -> Self::View<'_> { self.0.fwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToMany for Backward<ManyToOne<A, B>> {
+    type K = B;
+    type V = A;
+    type View<'a> = many_to_one::Bwd<'a, A, B>;
+
+    fn as_to_many(&self) -> Self::View<'_> { self.0.bwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToMany for Forward<ManyToMany<A, B>> {
+    type K = A;
+    type V = B;
+    type View<'a> = many_to_many::Fwd<'a, A, B>;
+
+    fn as_to_many(&self) -> Self::View<'_> { self.0.fwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToMany for Backward<ManyToMany<A, B>> {
+    type K = B;
+    type V = A;
+    type View<'a> = many_to_many::Bwd<'a, A, B>;
+
+    fn as_to_many(&self) -> Self::View<'_> { self.0.bwd() }
+}
@@ -0,0 +1,110 @@
+use std::fmt::Debug;
+
+use moogle::{IdLike, ManyToOne, OneToMany, OneToOne, SharedAnyToOne, ToOne};
+use moogle::{many_to_one, one_to_many, one_to_one, shared_to_one};
+
+use crate::codegen::TypeData;
+use crate::runtime::id_type;
+
+use super::super::{MutToUnknown, RefToUnknown};
+use super::{Backward, DynIter, Forward, read};
+
+// Every key has at most one value.
+#[cfg_attr(not(test), allow(dead_code))]  // for hosts: relations only need get, contains and iter
+pub trait DynToOne {
+    fn key_type(&self) -> TypeData;
+    fn value_type(&self) -> TypeData;
+
+    // fills in out (which must be uninitialized) if k has a value
+    fn get(&self, k: RefToUnknown<'_>, out: MutToUnknown<'_>) -> bool;
+    fn contains_key(&self, k: RefToUnknown<'_>) -> bool;
+    fn contains(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool;
+    fn len(&self) -> usize;
+
+    // in key order
+    fn iter(&self) -> DynIter<'_>;
+    fn keys(&self) -> DynIter<'_>;
+    fn values(&self) -> DynIter<'_>;
+
+    // each one is true if anything changed
+    fn insert(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool;  // replaces k's old value
+    fn expunge(&self, k: RefToUnknown<'_>) -> bool;
+    fn remove(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool;  // only if v is k's value
+}
+
+// a moogle structure (or one side of a junction) where every key has at most one value
+pub trait AsToOne {
+    type K: IdLike+Debug;
+    type V: IdLike+Debug;
+    type View<'a>: SharedAnyToOne<'a, Self::K, Self::V> where Self: 'a;
+
+    fn as_to_one(&self) -> Self::View<'_>;
+}
+
+impl<T: AsToOne> DynToOne for T {
+    fn key_type(&self) -> TypeData { id_type::<T::K>() }
+    fn value_type(&self) -> TypeData { id_type::<T::V>() }
+
+    fn get(&self, k: RefToUnknown<'_>, out: MutToUnknown<'_>) -> bool {
+        match self.as_to_one().get(read(k)) {
+            Some(v) => { out.cast::<T::V>().initialize(v); true }
+            None => false,
+        }
+    }
+
+    fn contains_key(&self, k: RefToUnknown<'_>) -> bool { self.as_to_one().contains_key(read(k)) }
+    fn contains(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool { self.as_to_one().contains(read(k), read(v)) }
+    fn len(&self) -> usize { self.as_to_one().len() }
+
+    fn iter(&self) -> DynIter<'_> { DynIter::pairs(self.as_to_one().iter()) }
+    fn keys(&self) -> DynIter<'_> { DynIter::singles(self.as_to_one().keys()) }
+    fn values(&self) -> DynIter<'_> { DynIter::singles(self.as_to_one().values()) }
+
+    fn insert(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool {
+        let v = read(v);
+        self.as_to_one().insert(read(k), v) != Some(v)
+    }
+
+    fn expunge(&self, k: RefToUnknown<'_>) -> bool { self.as_to_one().expunge(read(k)).is_some() }
+    fn remove(&self, k: RefToUnknown<'_>, v: RefToUnknown<'_>) -> bool { self.as_to_one().remove(read(k), read(v)).is_some() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToOne for ToOne<A, B> {
+    type K = A;
+    type V = B;
+    type View<'a> = shared_to_one::Fwd<'a, A, B>;
+
+    fn as_to_one(&self) -> Self::View<'_> { self.fwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToOne for Forward<OneToOne<A, B>> {
+    type K = A;
+    type V = B;
+    type View<'a> = one_to_one::Fwd<'a, A, B>;
+
+    fn as_to_one(&self) -> Self::View<'_> { self.0.fwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToOne for Backward<OneToOne<A, B>> {
+    type K = B;
+    type V = A;
+    type View<'a> = one_to_one::Bwd<'a, A, B>;
+
+    fn as_to_one(&self) -> Self::View<'_> { self.0.bwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToOne for Forward<ManyToOne<A, B>> {
+    type K = A;
+    type V = B;
+    type View<'a> = many_to_one::Fwd<'a, A, B>;
+
+    fn as_to_one(&self) -> Self::View<'_> { self.0.fwd() }
+}
+
+impl<A: IdLike+Debug, B: IdLike+Debug> AsToOne for Backward<OneToMany<A, B>> {
+    type K = B;
+    type V = A;
+    type View<'a> = one_to_many::Bwd<'a, A, B>;
+
+    fn as_to_one(&self) -> Self::View<'_> { self.0.bwd() }
+}
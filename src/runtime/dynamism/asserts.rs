@@ -1,6 +1,7 @@
 // TODO: Get rid of RefCell, it's checked elsewhere and I'm afraid I'll accidentally Copy it
 use std::{any::{Any, TypeId}, cell::{Ref, RefCell, RefMut}, mem::{MaybeUninit, size_of, swap, transmute}};
 
+#[derive(Clone, Copy)]
 pub struct RefToUnknown<'a>(&'a [u8]);
 pub struct MutToUnknown<'a>(&'a mut [u8]);
 
@@ -44,6 +45,11 @@ impl<'a> MutToUnknown<'a> {
     pub(crate) fn downgrade(self) -> RefToUnknown<'a> {
         RefToUnknown(self.0)
     }
+
+    // Copy types only
+    pub(crate) fn copy_from(&mut self, other: RefToUnknown<'_>) {
+        self.0.copy_from_slice(other.0)
+    }
 }
 
 impl<T> InPlace<T> {
@@ -1,5 +1,6 @@
-use std::{any::Any, mem::{MaybeUninit, swap, transmute}};
+use std::{any::{Any, TypeId}, mem::{MaybeUninit, swap, transmute}};
 
+#[derive(Clone, Copy)]
 pub struct RefToUnknown<'a>(&'a [u8]);
 pub struct MutToUnknown<'a>(&'a mut [u8]);
 
@@ -26,9 +27,16 @@ impl<'a> MutToUnknown<'a> {
         s
     }
 
+    pub(crate) fn initialize_asserts(&mut self, _type_id: TypeId) { }
+
     pub(crate) fn downgrade(self) -> RefToUnknown<'a> {
         RefToUnknown(self.0)
     }
+
+    // Copy types only
+    pub(crate) fn copy_from(&mut self, other: RefToUnknown<'_>) {
+        self.0.copy_from_slice(other.0)
+    }
 }
 
 impl<T> InPlace<T> {
@@ -1,5 +1,10 @@
 pub(crate) mod dynamism;
+pub mod dynamics;
+mod database;
+mod relations;
 mod vm;
 
+pub use database::*;
 pub use dynamism::*;
+pub use relations::*;
 pub use vm::*;
\ No newline at end of file
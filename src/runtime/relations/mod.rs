@@ -0,0 +1,34 @@
+mod moogle;
+mod tuples;
+
+pub use self::moogle::id_type;
+pub use self::tuples::{KupoSet, Tuples};
+
+use crate::codegen::TypeData;
+
+use super::{MutToUnknown, RefToUnknown};
+
+// anything a query can read rows out of. rows are snapshots: they don't see later writes
+pub trait Relation {
+    fn columns(&self) -> Vec<TypeData>;
+
+    // bound has one entry per column: Some for each column the query already knows.
+    // Only rows matching all of those are produced.
+    fn open(&self, bound: &[Option<RefToUnknown<'_>>]) -> Box<dyn Rows>;
+
+    // most relations are read-only: typeck won't let kupo code write to those
+    fn writable(&self) -> bool { false }
+
+    // row has one entry per column
+    fn insert(&self, _row: &[RefToUnknown<'_>]) { panic!("relation isn't writable (the type checker should have caught this)") }
+    fn remove(&self, _row: &[RefToUnknown<'_>]) { panic!("relation isn't writable (the type checker should have caught this)") }
+
+    // drops every row whose first column is key
+    fn expunge(&self, _key: RefToUnknown<'_>) { panic!("relation isn't writable (the type checker should have caught this)") }
+}
+
+pub trait Rows {
+    // Fills in each column that's Some in out, which must be uninitialized.
+    // Returns false once there are no more rows.
+    fn next(&mut self, out: &mut [Option<MutToUnknown<'_>>]) -> bool;
+}
@@ -0,0 +1,143 @@
+use std::{fmt::Debug, rc::Rc, vec};
+
+use moogle::{IdLike, Set, SharedAnySet};
+
+use crate::codegen::TypeData;
+use crate::runtime::dynamics::{DynToMany, DynToOne, read, write};
+use crate::runtime::{MutToUnknown, RefToUnknown};
+
+use super::{Relation, Rows, Tuples};
+
+// The TypeData for anything that can be stored in a moogle structure
+pub fn id_type<A: IdLike+Debug>() -> TypeData {
+    TypeData::new_copy::<A>(
+        |ptr, dbg| ptr.cast::<A>().get().fmt(dbg).unwrap(),
+    ).ordered::<A>()
+}
+
+struct SetRows<A>(vec::IntoIter<A>);
+
+impl<A: IdLike> Rows for SetRows<A> {
+    fn next(&mut self, out: &mut [Option<MutToUnknown<'_>>]) -> bool {
+        match self.0.next() {
+            Some(a) => { write(&mut out[0], a); true }
+            None => false,
+        }
+    }
+}
+
+impl<A: IdLike+Debug> Relation for Set<A> {
+    fn columns(&self) -> Vec<TypeData> {
+        vec![id_type::<A>()]
+    }
+
+    fn open(&self, bound: &[Option<RefToUnknown<'_>>]) -> Box<dyn Rows> {
+        let rows = match bound[0] {
+            Some(a) => {
+                let a = read::<A>(a);
+                if self.fwd().contains(a) { vec![a] } else { vec![] }
+            }
+            None => self.fwd().iter().collect(),
+        };
+        Box::new(SetRows(rows.into_iter()))
+    }
+
+    fn writable(&self) -> bool { true }
+
+    fn insert(&self, row: &[RefToUnknown<'_>]) {
+        self.fwd().insert(read::<A>(row[0]));
+    }
+
+    fn remove(&self, row: &[RefToUnknown<'_>]) {
+        self.fwd().remove(read::<A>(row[0]));
+    }
+
+    fn expunge(&self, key: RefToUnknown<'_>) {
+        self.fwd().remove(read::<A>(key));
+    }
+}
+
+// rows get copied out on open, since moogle's iterators see later writes. only keys are indexed
+impl Relation for Box<dyn DynToOne> {
+    fn columns(&self) -> Vec<TypeData> {
+        vec![self.key_type(), self.value_type()]
+    }
+
+    fn open(&self, bound: &[Option<RefToUnknown<'_>>]) -> Box<dyn Rows> {
+        let to_one: &dyn DynToOne = &**self;
+        let mut rows = Tuples::new(&self.columns());
+        match (bound[0], bound[1]) {
+            (Some(k), Some(v)) => if to_one.contains(k, v) { rows.push(&[k, v]) },
+            (Some(k), None) => {
+                rows.push_from(|out| {
+                    if !to_one.get(k, out[1].take().unwrap()) { return false }
+                    to_one.key_type().clone_into(k, out[0].take().unwrap());
+                    true
+                });
+            }
+            (None, _) => {
+                let mut iter = to_one.iter();
+                while rows.push_from(|out| iter.next(out)) {}
+                rows.retain_matching(bound);
+            }
+        }
+        Rc::new(rows).open(&[None, None])
+    }
+
+    fn writable(&self) -> bool { true }
+
+    // a key only has one value, so this replaces whatever it had before
+    fn insert(&self, row: &[RefToUnknown<'_>]) {
+        (**self).insert(row[0], row[1]);
+    }
+
+    fn remove(&self, row: &[RefToUnknown<'_>]) {
+        (**self).remove(row[0], row[1]);
+    }
+
+    fn expunge(&self, key: RefToUnknown<'_>) {
+        (**self).expunge(key);
+    }
+}
+
+impl Relation for Box<dyn DynToMany> {
+    fn columns(&self) -> Vec<TypeData> {
+        vec![self.key_type(), self.value_type()]
+    }
+
+    fn open(&self, bound: &[Option<RefToUnknown<'_>>]) -> Box<dyn Rows> {
+        let to_many: &dyn DynToMany = &**self;
+        let mut rows = Tuples::new(&self.columns());
+        match (bound[0], bound[1]) {
+            (Some(k), Some(v)) => if to_many.contains(k, v) { rows.push(&[k, v]) },
+            (Some(k), None) => {
+                let mut values = to_many.get(k);
+                while rows.push_from(|out| {
+                    if !values.next(&mut out[1..]) { return false }
+                    to_many.key_type().clone_into(k, out[0].take().unwrap());
+                    true
+                }) {}
+            }
+            (None, _) => {
+                let mut iter = to_many.iter();
+                while rows.push_from(|out| iter.next(out)) {}
+                rows.retain_matching(bound);
+            }
+        }
+        Rc::new(rows).open(&[None, None])
+    }
+
+    fn writable(&self) -> bool { true }
+
+    fn insert(&self, row: &[RefToUnknown<'_>]) {
+        (**self).insert(row[0], row[1]);
+    }
+
+    fn remove(&self, row: &[RefToUnknown<'_>]) {
+        (**self).remove(row[0], row[1]);
+    }
+
+    fn expunge(&self, key: RefToUnknown<'_>) {
+        (**self).expunge(key);
+    }
+}
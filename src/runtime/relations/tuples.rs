@@ -0,0 +1,280 @@
+use std::{cell::RefCell, cmp::Ordering, fmt, rc::Rc, vec};
+
+use crate::codegen::{Struct, StructBuilder, TypeData};
+use crate::runtime::{MutToUnknown, RefToUnknown, UntaggedValue};
+
+use super::{Relation, Rows};
+
+// rows of any kupo values: what views evaluate to, and what hosts use for things moogle can't hold
+pub struct Tuples {
+    row: Struct,
+    rows: Vec<UntaggedValue>,
+}
+
+impl Tuples {
+    pub fn new(columns: &[TypeData]) -> Tuples {
+        let mut row = StructBuilder::new();
+        for (i, c) in columns.iter().enumerate() {
+            row.push(i.to_string(), *c);
+        }
+        Tuples { row: row.build(), rows: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn arity(&self) -> usize {
+        self.row.fields.len()
+    }
+
+    pub fn column_type(&self, column: usize) -> &TypeData {
+        &self.row.fields[column].type_data
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> RefToUnknown<'_> {
+        self.rows[row].ref_field(&self.row, column)
+    }
+
+    // values are cloned, so the caller keeps ownership of them
+    pub fn push(&mut self, values: &[RefToUnknown<'_>]) {
+        assert_eq!(values.len(), self.arity());
+        let mut row = UntaggedValue::instantiate(&self.row);
+        for (i, value) in values.iter().enumerate() {
+            self.row.fields[i].type_data.clone_into(*value, row.mut_field(&self.row, i));
+        }
+        self.rows.push(row);
+    }
+
+    // for hosts: init gets called once per column, and has to initialize it
+    pub fn push_with(&mut self, mut init: impl FnMut(usize, MutToUnknown<'_>)) {
+        let mut row = UntaggedValue::instantiate(&self.row);
+        for i in 0..self.arity() {
+            init(i, row.mut_field(&self.row, i));
+        }
+        self.rows.push(row);
+    }
+
+    // views are sets. (unless some column isn't ordered: then rows can't be told apart)
+    pub(crate) fn dedup(&mut self) {
+        if !self.row.fields.iter().all(|f| f.type_data.is_ordered()) { return }
+
+        let row = &self.row;
+        self.rows.sort_by(|a, b| compare_rows(row, a, b));
+
+        let mut rows: Vec<UntaggedValue> = vec![];
+        for r in std::mem::take(&mut self.rows) {
+            if let Some(last) = rows.last() {
+                if compare_rows(row, last, &r) == Ordering::Equal {
+                    drop_row(row, r);
+                    continue
+                }
+            }
+            rows.push(r)
+        }
+        self.rows = rows;
+    }
+
+    // for recursive views: both sides have to be deduped already
+
+    // drops every row that's already in known
+    pub(crate) fn remove_known(&mut self, known: &Tuples) {
+        let row = &self.row;
+        for r in std::mem::take(&mut self.rows) {
+            if known.rows.binary_search_by(|k| compare_rows(row, k, &r)).is_ok() {
+                drop_row(row, r);
+            } else {
+                self.rows.push(r);
+            }
+        }
+    }
+
+    // adds a copy of every row in other
+    pub(crate) fn extend(&mut self, other: &Tuples) {
+        for r in 0..other.len() {
+            self.push(&other.row(r));
+        }
+        self.dedup();
+    }
+
+    pub(crate) fn row(&self, row: usize) -> Vec<RefToUnknown<'_>> {
+        (0..self.arity()).map(|c| self.get(row, c)).collect()
+    }
+
+    fn copy(&self) -> Tuples {
+        let columns: Vec<TypeData> = self.row.fields.iter().map(|f| f.type_data).collect();
+        let mut copy = Tuples::new(&columns);
+        for r in 0..self.len() {
+            copy.push(&self.row(r));
+        }
+        copy
+    }
+
+    // for relations that hand out rows through out params: fill has to initialize every column
+    // and return true, or leave them all alone and return false
+    pub(crate) fn push_from(&mut self, fill: impl FnOnce(&mut [Option<MutToUnknown<'_>>]) -> bool) -> bool {
+        let mut row = UntaggedValue::instantiate(&self.row);
+        let mut out: Vec<Option<MutToUnknown<'_>>> = row.mut_fields(&self.row).into_iter().map(Some).collect();
+        if !fill(&mut out) { return false }
+        drop(out);
+        self.rows.push(row);
+        true
+    }
+
+    // these both need an ordering on the bound columns (see Relation::open for bound)
+    fn remove_matching(&mut self, bound: &[Option<RefToUnknown<'_>>]) {
+        self.filter(bound, false)
+    }
+
+    pub(crate) fn retain_matching(&mut self, bound: &[Option<RefToUnknown<'_>>]) {
+        self.filter(bound, true)
+    }
+
+    fn filter(&mut self, bound: &[Option<RefToUnknown<'_>>], matching: bool) {
+        let keep: Vec<bool> = (0..self.len()).map(|r| self.matches(r, bound) == matching).collect();
+        for (r, keep) in std::mem::take(&mut self.rows).into_iter().zip(keep) {
+            if keep { self.rows.push(r) } else { drop_row(&self.row, r) }
+        }
+    }
+
+    fn matches(&self, row: usize, bound: &[Option<RefToUnknown<'_>>]) -> bool {
+        bound.iter().enumerate().all(|(c, b)| match b {
+            Some(b) => self.column_type(c).compare(self.get(row, c), *b) == Ordering::Equal,
+            None => true,
+        })
+    }
+}
+
+fn compare_rows(row: &Struct, a: &UntaggedValue, b: &UntaggedValue) -> Ordering {
+    for (i, field) in row.fields.iter().enumerate() {
+        match field.type_data.compare(a.ref_field(row, i), b.ref_field(row, i)) {
+            Ordering::Equal => {}
+            o => return o,
+        }
+    }
+    Ordering::Equal
+}
+
+fn drop_row(row: &Struct, mut value: UntaggedValue) {
+    for (i, field) in row.fields.iter().enumerate() {
+        if let Some(drop) = field.type_data.drop_callback {
+            drop(value.mut_field(row, i))
+        }
+    }
+}
+
+impl Drop for Tuples {
+    fn drop(&mut self) {
+        for r in std::mem::take(&mut self.rows) {
+            drop_row(&self.row, r)
+        }
+    }
+}
+
+// what collect gives you. sets of different things compare too: by column type, then size, then rows
+#[derive(Clone)]
+pub struct KupoSet(pub Rc<Tuples>);
+
+impl KupoSet {
+    fn column(&self) -> &TypeData {
+        self.0.column_type(0)
+    }
+}
+
+impl Ord for KupoSet {
+    fn cmp(&self, other: &KupoSet) -> Ordering {
+        let (a, b) = (&self.0, &other.0);
+        self.column().rust_type.cmp(&other.column().rust_type)
+            .then(a.len().cmp(&b.len()))
+            .then_with(|| a.rows.iter().zip(&b.rows).map(|(x, y)| compare_rows(&a.row, x, y)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal))
+    }
+}
+
+impl PartialOrd for KupoSet {
+    fn partial_cmp(&self, other: &KupoSet) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for KupoSet {
+    fn eq(&self, other: &KupoSet) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for KupoSet {}
+
+impl fmt::Debug for KupoSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries((0..self.0.len()).map(|r| self.column().debug(self.0.get(r, 0)))).finish()
+    }
+}
+
+struct TuplesRows {
+    tuples: Rc<Tuples>,
+    rows: vec::IntoIter<usize>,
+}
+
+impl Rows for TuplesRows {
+    fn next(&mut self, out: &mut [Option<MutToUnknown<'_>>]) -> bool {
+        let row = if let Some(row) = self.rows.next() { row } else { return false };
+        for (c, o) in out.iter_mut().enumerate() {
+            if let Some(o) = o.take() {
+                self.tuples.column_type(c).clone_into(self.tuples.get(row, c), o)
+            }
+        }
+        true
+    }
+}
+
+impl Relation for Rc<Tuples> {
+    fn columns(&self) -> Vec<TypeData> {
+        self.row.fields.iter().map(|f| f.type_data).collect()
+    }
+
+    fn open(&self, bound: &[Option<RefToUnknown<'_>>]) -> Box<dyn Rows> {
+        let rows: Vec<usize> = (0..self.len()).filter(|r| self.matches(*r, bound)).collect();
+        Box::new(TuplesRows { tuples: self.clone(), rows: rows.into_iter() })
+    }
+}
+
+// writable. a write while rows are still being read goes to a fresh copy
+impl Relation for RefCell<Rc<Tuples>> {
+    fn columns(&self) -> Vec<TypeData> {
+        self.borrow().columns()
+    }
+
+    fn open(&self, bound: &[Option<RefToUnknown<'_>>]) -> Box<dyn Rows> {
+        self.borrow().open(bound)
+    }
+
+    fn writable(&self) -> bool { true }
+
+    // without an ordering on every column, there's no telling if the row is already there
+    fn insert(&self, row: &[RefToUnknown<'_>]) {
+        let mut tuples = self.borrow_mut();
+        let bound: Vec<Option<RefToUnknown<'_>>> = row.iter().map(|v| Some(*v)).collect();
+        let ordered = tuples.row.fields.iter().all(|f| f.type_data.is_ordered());
+        if ordered && (0..tuples.len()).any(|r| tuples.matches(r, &bound)) { return }
+        unshared(&mut tuples).push(row)
+    }
+
+    fn remove(&self, row: &[RefToUnknown<'_>]) {
+        let bound: Vec<Option<RefToUnknown<'_>>> = row.iter().map(|v| Some(*v)).collect();
+        unshared(&mut self.borrow_mut()).remove_matching(&bound)
+    }
+
+    fn expunge(&self, key: RefToUnknown<'_>) {
+        let mut tuples = self.borrow_mut();
+        let mut bound = vec![None; tuples.arity()];
+        bound[0] = Some(key);
+        unshared(&mut tuples).remove_matching(&bound)
+    }
+}
+
+fn unshared(tuples: &mut Rc<Tuples>) -> &mut Tuples {
+    if Rc::get_mut(tuples).is_none() {
+        *tuples = Rc::new(tuples.copy());
+    }
+    Rc::get_mut(tuples).unwrap()
+}
@@ -0,0 +1,109 @@
+use std::{cmp::Ordering, ops::Range, rc::Rc};
+
+use crate::codegen::{Aggregate, Aggregation};
+
+use super::{KupoRuntimeError, kre};
+use super::super::{KupoSet, MutToUnknown, Tuples};
+
+// dedup sorts, so each group's rows are together. with no groups there's always one (min/max of nothing fail)
+pub(super) fn aggregate(mut rows: Tuples, aggregation: &Aggregation) -> Result<Tuples, KupoRuntimeError> {
+    rows.dedup();
+    let groups = aggregation.groups;
+    let value = rows.arity() - 1;
+
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let mut start = 0;
+    for r in 1..=rows.len() {
+        let same = r < rows.len() && (0..groups).all(|c| rows.column_type(c).compare(rows.get(start, c), rows.get(r, c)) == Ordering::Equal);
+        if !same {
+            ranges.push(start..r);
+            start = r;
+        }
+    }
+    if groups == 0 && ranges.is_empty() { ranges.push(0..0) }
+
+    let mut columns = aggregation.columns[..groups].to_vec();
+    columns.push(aggregation.result);
+    let mut out = Tuples::new(&columns);
+    for range in ranges {
+        let first = range.start;
+        match aggregation.op {
+            Aggregate::Count => {
+                let n = range.len() as i64;
+                out.push_with(|c, into| if c < groups { copy(&rows, first, c, into) } else { into.cast::<i64>().initialize(n) });
+            }
+            Aggregate::Sum => {
+                let mut n: i64 = 0;
+                for r in range {
+                    n = n.checked_add(*rows.get(r, value).cast::<i64>().get()).ok_or_else(|| kre("sum overflowed"))?;
+                }
+                out.push_with(|c, into| if c < groups { copy(&rows, first, c, into) } else { into.cast::<i64>().initialize(n) });
+            }
+            Aggregate::Min | Aggregate::Max => {
+                let wanted = if aggregation.op == Aggregate::Min { Ordering::Less } else { Ordering::Greater };
+                let best = range.reduce(|best, r| {
+                    if rows.column_type(value).compare(rows.get(r, value), rows.get(best, value)) == wanted { r } else { best }
+                });
+                let best = if let Some(best) = best { best } else { continue };
+                out.push_with(|c, into| copy(&rows, if c < groups { first } else { best }, if c < groups { c } else { value }, into));
+            }
+            Aggregate::Collect => {
+                let mut set = Tuples::new(&[*rows.column_type(value)]);
+                for r in range { set.push(&[rows.get(r, value)]) }
+                set.dedup();
+                let mut set = Some(KupoSet(Rc::new(set)));
+                out.push_with(|c, into| if c < groups { copy(&rows, first, c, into) } else { into.cast::<KupoSet>().initialize(set.take().unwrap()) });
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn copy(rows: &Tuples, row: usize, column: usize, into: MutToUnknown<'_>) {
+    rows.column_type(column).clone_into(rows.get(row, column), into)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::run;
+
+    #[test]
+    fn groups_by_everything_else() {
+        // @x isn't in the value, so each vampire is a group of its own
+        let source = "view [@n Integer] in v { @n := sum(@v : @x in vampire, @v := 1) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[1]"]);
+        let source = "view [@x NPC, @n Integer] in v { @n := sum(@v : @x in vampire, @v := 1) }";
+        assert_eq!(run(source, "v").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn equal_values() {
+        // the same value from different variables counts each time, from the same ones only once
+        let source = "view [@c Castle, @n Integer] in v { @n := sum(@v : [@x, @c] in lives_in, @v := 1) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[Castle(Bran), 1]", "[Castle(Karnstein), 1]"]);
+        let source = "view [@c Castle, @n Integer] in v { @n := count(@x : [@x, @c] in lives_in) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[Castle(Bran), 3]", "[Castle(Karnstein), 1]"]);
+    }
+
+    // views that the tests below aggregate over
+    const NS: &str = "view [@n Integer] in ns { @n := count(@y : [@x, @y] in sired) }\nview [@x NPC] in friendly { [@x, @s] in friendly_bat }\n";
+
+    #[test]
+    fn empty() {
+        // with no groups, there's a row even if the body finds nothing
+        let source = format!("{}view [@n Integer] in v {{ @n := count(@x : @x in vampire, @x in friendly) }}", NS);
+        assert_eq!(run(&source, "v").unwrap(), vec!["[0]"]);
+        let source = format!("{}view [@n Integer] in v {{ @n := sum(@n : @n in ns, @n > 5) }}", NS);
+        assert_eq!(run(&source, "v").unwrap(), vec!["[0]"]);
+        // except for min and max, which have nothing to give
+        let source = format!("{}view [@n NPC] in v {{ @n := min(@x : @x in vampire, @x in friendly) }}", NS);
+        assert_eq!(run(&source, "v").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn ops() {
+        assert_eq!(run(NS, "ns").unwrap(), vec!["[1]", "[2]"]);
+        let source = format!("{}view [@a Integer, @b Integer, @c Integer] in v {{\n    @a := sum(@n : @n in ns),\n    @b := min(@n : @n in ns),\n    @c := max(@n : @n in ns),\n}}", NS);
+        assert_eq!(run(&source, "v").unwrap(), vec!["[3, 1, 2]"]);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::codegen::{DebugUnknown, Procedure, Program, Register};
+use crate::frontend::Located;
+
+use super::{Frame, KupoRuntimeError, kre};
+
+// what to do after a pause
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Continue,  // until the next breakpoint
+    Into,  // until the next statement, wherever it is
+    Over,  // until the next statement in this frame or a caller
+    Out,  // until the next statement in a caller
+    Stop,  // give up on the program with an error
+}
+
+// the VM checks in at each new statement or goal; on_pause decides what happens at a stop
+pub struct Debugger {
+    pub breakpoints: Vec<Located<()>>,  // pause at anything that starts inside one of these
+    on_pause: Box<dyn FnMut(&Paused<'_>) -> Step>,
+    step: Step,
+    step_depth: usize,  // how many frames there were when we last paused
+}
+
+impl Debugger {
+    pub fn new(breakpoints: Vec<Located<()>>, on_pause: impl FnMut(&Paused<'_>) -> Step + 'static) -> Debugger {
+        Debugger { breakpoints, on_pause: Box::new(on_pause), step: Step::Continue, step_depth: 0 }
+    }
+
+    pub(super) fn visit(&mut self, program: &Program, stack: &[Frame], at: Located<()>) -> Result<(), KupoRuntimeError> {
+        let depth = stack.len();
+        let hit = self.breakpoints.iter().any(|b| b.start <= at.start && at.start < b.end);
+        let stepped = match self.step {
+            Step::Continue | Step::Stop => false,
+            Step::Into => true,
+            Step::Over => depth <= self.step_depth,
+            Step::Out => depth < self.step_depth,
+        };
+        if !hit && !stepped { return Ok(()) }
+
+        self.step = (self.on_pause)(&Paused { program, stack });
+        self.step_depth = depth;
+        if self.step == Step::Stop {
+            return Err(kre("stopped by the debugger"))
+        }
+        Ok(())
+    }
+}
+
+// a paused view can't see whoever opened it: it has its own stack
+pub struct Paused<'a> {
+    program: &'a Program,
+    stack: &'a [Frame],
+}
+
+impl<'a> Paused<'a> {
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // 0 is the innermost frame: the one that's paused
+    pub fn frame(&self, n: usize) -> PausedFrame<'a> {
+        let frame = &self.stack[self.stack.len() - 1 - n];
+        // callers have already moved past their Call
+        let ip = if n == 0 { frame.ip } else { frame.ip - 1 };
+        PausedFrame { proc: &self.program.procedures[frame.procedure], frame, ip }
+    }
+}
+
+pub struct PausedFrame<'a> {
+    proc: &'a Procedure,
+    frame: &'a Frame,
+    ip: usize,
+}
+
+impl<'a> PausedFrame<'a> {
+    pub fn procedure(&self) -> &'a str {
+        &self.proc.name
+    }
+
+    pub fn location(&self) -> Option<Located<()>> {
+        self.proc.debug.span(self.ip)
+    }
+
+    // args, then every local that holds a variable and has a value
+    pub fn variables(&self) -> Vec<(&'a str, DebugUnknown<'a>)> {
+        let args = (0..self.proc.args.fields.len()).map(Register::Arg);
+        let locals = (0..self.proc.locals.fields.len()).map(Register::Local);
+        args.chain(locals).filter_map(|r| self.value(r)).collect()
+    }
+
+    // if a name got reused, the latest local with a value wins
+    pub fn variable(&self, name: &str) -> Option<DebugUnknown<'a>> {
+        self.variables().into_iter().rev().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    fn value(&self, register: Register) -> Option<(&'a str, DebugUnknown<'a>)> {
+        if !self.frame.is_initialized(register) { return None }
+        let name = match register {
+            Register::Arg(a) => &self.proc.args.fields[a].name,
+            Register::Local(_) => self.proc.debug.variable(register)?,
+        };
+        Some((name, self.proc.type_of(register).debug(self.frame.ref_register(self.proc, register))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::diagnostics::SourceFile;
+    use crate::runtime::{UntaggedValue, VM};
+    use crate::testing::compile;
+
+    const DOUBLE: &str = "def double(@n Integer) Integer {\n    @m := @n * 2\n    return @m\n}\n\
+        def main() {\n    @a := double(1)\n    @b := double(@a)\n    print(@b)\n}\n";
+
+    // runs main, pausing at line and then taking each of steps in turn (then continuing).
+    // gives each pause as procedure:line and the variables it could see
+    fn pauses(line: usize, steps: &[Step]) -> (Vec<String>, Result<(), String>) {
+        let file = Rc::new(SourceFile::new("test.kupo", DOUBLE));
+        let program = compile(DOUBLE).unwrap();
+        let main = program.procedure_named("main").unwrap();
+        let seen = Rc::new(RefCell::new(vec![]));
+        let on_pause = {
+            let (file, seen) = (file.clone(), seen.clone());
+            let mut steps: Vec<Step> = steps.iter().rev().copied().collect();
+            move |paused: &Paused<'_>| {
+                let top = paused.frame(0);
+                let (line, _) = file.line_col(top.location().unwrap().start);
+                let variables: Vec<_> = top.variables().iter().map(|(n, v)| format!("{}={:?}", n, v)).collect();
+                seen.borrow_mut().push(format!("{}:{} {}", top.procedure(), line, variables.join(" ")).trim().to_string());
+                steps.pop().unwrap_or(Step::Continue)
+            }
+        };
+        let breakpoint = file.line_span(line).unwrap();
+        let vm = VM::debug(program, Debugger::new(vec![breakpoint], on_pause));
+        let result = vm.call(main, UntaggedValue::instantiate(&vm.program.procedures[main].args)).map_err(|e| e.message);
+        let seen = seen.borrow().clone();
+        (seen, result)
+    }
+
+    #[test]
+    fn breakpoints() {
+        let (seen, result) = pauses(3, &[]);
+        assert_eq!(seen, vec!["double:3 @n=1 @m=2", "double:3 @n=2 @m=4"]);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn stepping() {
+        let (seen, _) = pauses(6, &[Step::Into, Step::Into, Step::Into]);
+        assert_eq!(seen, vec!["main:6", "double:2 @n=1", "double:3 @n=1 @m=2", "main:7 @a=2"]);
+        let (seen, _) = pauses(6, &[Step::Over, Step::Over]);
+        assert_eq!(seen, vec!["main:6", "main:7 @a=2", "main:8 @a=2 @b=4"]);
+        let (seen, _) = pauses(2, &[Step::Out]);
+        assert_eq!(seen, vec!["double:2 @n=1", "main:7 @a=2", "double:2 @n=2"]);
+    }
+
+    #[test]
+    fn stop() {
+        let (seen, result) = pauses(2, &[Step::Stop]);
+        assert_eq!(seen, vec!["double:2 @n=1"]);
+        assert_eq!(result.unwrap_err(), "stopped by the debugger");
+    }
+}
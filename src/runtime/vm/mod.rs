@@ -1,44 +1,386 @@
+mod aggregate;
+mod debug;
 mod values;
+mod writes;
 
-use crate::codegen::{Instruction, Procedure, Program, Register};
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
 
+use crate::codegen::{BinOp, Comparison, Component, Instruction, Procedure, Program, Register, Source, TypeData, UOp};
+use crate::frontend::Located;
+
+pub use self::debug::{Debugger, Paused, Step};
 pub use self::values::UntaggedValue;
+use self::writes::Writes;
+
+use super::{MutToUnknown, RefToUnknown, Relation, Rows, Tuples};
+
+// trace is innermost frame first
+#[derive(Debug)]
+pub struct KupoRuntimeError {
+    pub message: String,
+    pub trace: Vec<TraceEntry>,
+}
+
+#[derive(Debug)]
+pub struct TraceEntry {
+    pub procedure: String,
+    pub location: Option<Located<()>>,
+}
+
+pub fn kre(s: &str) -> KupoRuntimeError {
+    KupoRuntimeError { message: s.to_string(), trace: vec![] }
+}
 
-use super::{MutToUnknown, RefToUnknown};
+const MAX_FRAMES: usize = 10000;
 
 pub struct VM {
-    program: Program
+    program: Program,
+    debugger: Option<RefCell<Debugger>>,
+    fixpoints: RefCell<Vec<Fixpoint>>,  // one per view in each component being evaluated
+    writes: RefCell<Writes>,
+}
+
+// the rows of a recursive view, partway through evaluating its component
+struct Fixpoint {
+    view: usize,
+    full: Rc<Tuples>,
+    delta: Rc<Tuples>,
 }
 
 impl VM {
     pub fn new(program: Program) -> VM {
-        VM {program}
+        VM {program, debugger: None, fixpoints: RefCell::new(vec![]), writes: RefCell::default()}
+    }
+
+    pub fn debug(program: Program, debugger: Debugger) -> VM {
+        VM {program, debugger: Some(RefCell::new(debugger)), fixpoints: RefCell::new(vec![]), writes: RefCell::default()}
     }
 
-    pub fn call(&self, procedure: usize, args: UntaggedValue) {
+    pub fn call(&self, procedure: usize, args: UntaggedValue) -> Result<(), KupoRuntimeError> {
+        self.program.check_tables()?;
         let proc = &self.program.procedures[procedure];
-        let mut frame = Frame { 
-            procedure, 
-            ip: 0,
-            args, 
-            locals: UntaggedValue::instantiate(&proc.locals) ,
-        };
-        // interpret(proc.code
-        self.interpret(&mut frame);
+        let mut stack = vec![Frame::new(procedure, proc, args)];
+        let result = self.interpret(&mut stack).map_err(|e| self.trace(e, &stack));
+        self.unwind(stack);
+        result.map(|_| ())
+    }
+
+    pub fn evaluate_view(&self, procedure: usize) -> Result<Tuples, KupoRuntimeError> {
+        // a def the view calls can't change it partway through, even between the rounds of a fixpoint
+        self.holding_writes(|| {
+            if let Some(component) = self.program.components.iter().find(|c| c.views.contains(&procedure)) {
+                let i = component.views.iter().position(|v| *v == procedure).unwrap();
+                return Ok(self.evaluate_component(component)?.swap_remove(i))
+            }
+            let proc = &self.program.procedures[procedure];
+            self.evaluate(procedure, UntaggedValue::instantiate(&proc.args))
+        })
+    }
+
+    // semi-naive: run the delta procedures until a round finds nothing new
+    fn evaluate_component(&self, component: &Component) -> Result<Vec<Tuples>, KupoRuntimeError> {
+        let mut full = vec![];
+        for v in &component.views {
+            let proc = &self.program.procedures[*v];
+            full.push(Rc::new(self.evaluate(*v, UntaggedValue::instantiate(&proc.args))?));
+        }
+        // the first round builds on everything
+        let mut delta: Vec<Rc<Tuples>> = full.clone();
+
+        loop {
+            let mark = self.fixpoints.borrow().len();
+            self.fixpoints.borrow_mut().extend(component.views.iter().zip(full.iter().zip(&delta)).map(|(v, (f, d))| {
+                Fixpoint { view: *v, full: f.clone(), delta: d.clone() }
+            }));
+            let found: Result<Vec<Tuples>, KupoRuntimeError> = component.deltas.iter().map(|d| {
+                let proc = &self.program.procedures[*d];
+                self.evaluate(*d, UntaggedValue::instantiate(&proc.args))
+            }).collect();
+            self.fixpoints.borrow_mut().truncate(mark);
+
+            delta.clear();
+            for (f, mut new) in full.iter_mut().zip(found?) {
+                new.remove_known(f);
+                Rc::get_mut(f).expect("finished rounds don't hold on to their rows").extend(&new);
+                delta.push(Rc::new(new));
+            }
+            if delta.iter().all(|d| d.len() == 0) { break }
+        }
+        Ok(full.into_iter().map(|f| Rc::try_unwrap(f).ok().expect("finished rounds don't hold on to their rows")).collect())
+    }
+
+    fn fixpoint(&self, view: usize, delta: bool) -> Rc<Tuples> {
+        let fixpoints = self.fixpoints.borrow();
+        let f = fixpoints.iter().rev().find(|f| f.view == view).expect("only delta procedures read from a fixpoint");
+        if delta { f.delta.clone() } else { f.full.clone() }
+    }
+
+    // runs a procedure that emits rows (a view's, or the REPL's) to completion, collecting every row
+    pub fn evaluate(&self, procedure: usize, args: UntaggedValue) -> Result<Tuples, KupoRuntimeError> {
+        self.program.check_tables()?;
+        let proc = &self.program.procedures[procedure];
+        let columns = proc.emits.as_ref().expect("not a view");
+        let mut frame = Frame::new(procedure, proc, args);
+        frame.output = Some(Tuples::new(columns));
+
+        let mut stack = vec![frame];
+        let result = self.holding_writes(|| self.interpret(&mut stack)).map_err(|e| self.trace(e, &stack));
+        self.unwind(stack);
+
+        let mut output = result?.unwrap();
+        output.dedup();
+        Ok(output)
+    }
+
+    fn holding_writes<T>(&self, run: impl FnOnce() -> Result<T, KupoRuntimeError>) -> Result<T, KupoRuntimeError> {
+        self.writes.borrow_mut().hold();
+        let result = run();
+        match result {
+            Ok(_) => self.release_writes(1)?,
+            Err(_) => self.writes.borrow_mut().abandon(1),
+        }
+        result
+    }
+
+    fn release_writes(&self, holds: usize) -> Result<(), KupoRuntimeError> {
+        let ready = self.writes.borrow_mut().release(holds);
+        for w in ready {
+            self::writes::write(&*self.program.tables[w.table].relation()?, w.write, &w.row.row(0));
+        }
+        Ok(())
     }
 
-    fn interpret(&self, frame: &mut Frame) -> () {
-        let proc = &self.program.procedures[frame.procedure];
+    fn trace(&self, mut error: KupoRuntimeError, stack: &[Frame]) -> KupoRuntimeError {
+        for (i, frame) in stack.iter().enumerate().rev() {
+            let proc = &self.program.procedures[frame.procedure];
+            // callers have already moved past their Call, and a frame that ran off the end is past everything
+            let ip = if i + 1 == stack.len() { frame.ip } else { frame.ip - 1 };
+            let location = proc.debug.span(ip).or_else(|| proc.debug.spans.last().copied());
+            error.trace.push(TraceEntry { procedure: proc.name.clone(), location });
+        }
+        error
+    }
+
+    // whatever's left on the stack after an error
+    fn unwind(&self, mut stack: Vec<Frame>) {
+        while let Some(frame) = stack.pop() {
+            let proc = &self.program.procedures[frame.procedure];
+            self.writes.borrow_mut().abandon(frame.holds);
+            frame.teardown(proc);
+        }
+    }
+
+    // lets the debugger know when the top frame moves on to a new statement
+    fn check_in(&self, debugger: &RefCell<Debugger>, stack: &mut [Frame]) -> Result<(), KupoRuntimeError> {
+        let frame = stack.last_mut().unwrap();
+        let at = if let Some(at) = self.program.procedures[frame.procedure].debug.span(frame.ip) { at } else { return Ok(()) };
+        if frame.at.is_some_and(|a| (a.start, a.end) == (at.start, at.end)) { return Ok(()) }
+        frame.at = Some(at);
+        debugger.borrow_mut().visit(&self.program, stack, at)
+    }
+
+    // until the bottom frame returns. views get stacks of their own
+    fn interpret(&self, stack: &mut Vec<Frame>) -> Result<Option<Tuples>, KupoRuntimeError> {
+        loop {
+            if let Some(debugger) = &self.debugger {
+                self.check_in(debugger, stack)?;
+            }
+            let depth = stack.len();
+            let frame = stack.last_mut().unwrap();
+            let proc = &self.program.procedures[frame.procedure];
+
+            if frame.ip >= proc.code.instructions.len() {
+                if !proc.returns.is_empty() {
+                    return Err(kre(&format!("{} finished without returning a value", proc.name)))
+                }
+                let mut frame = stack.pop().unwrap();
+                let output = frame.output.take();
+                let released = self.release_writes(frame.holds);
+                frame.teardown(proc);
+                released?;
+                if stack.is_empty() { return Ok(output) }
+                continue
+            }
 
-        while frame.ip < proc.code.instructions.len() {
             match proc.code.instructions[frame.ip] {
                 Instruction::RustCallMut { rust_fn, arg, out } => {
+                    frame.clear(proc, out);
                     let (rr, mr) = frame.mut_register2(proc, arg, out);
-                    (self.program.ffi_mut[rust_fn])(rr.downgrade(), mr)
+                    (self.program.ffi_mut[rust_fn])(rr.downgrade(), mr);
+                    frame.mark_initialized(out);
                 }
                 Instruction::RustCallRef { rust_fn, arg } => {
                     (self.program.ffi_ref[rust_fn])(frame.ref_register(proc, arg))
                 }
+                Instruction::LoadInteger { value, out } => {
+                    frame.set::<i64>(proc, out, value)
+                }
+                Instruction::LoadString { string, out } => {
+                    frame.set::<String>(proc, out, self.program.strings[string].clone())
+                }
+                Instruction::Copy { from, out } => {
+                    if from != out {
+                        frame.clear(proc, out);
+                        let type_data = proc.type_of(out);
+                        let (from_ref, out_mut) = frame.mut_register2(proc, from, out);
+                        type_data.clone_into(from_ref.downgrade(), out_mut);
+                        frame.mark_initialized(out);
+                    }
+                }
+                Instruction::IntegerUOp { op, arg, out } => {
+                    let arg = frame.get::<i64>(proc, arg);
+                    let result = match op {
+                        UOp::Negate => arg.checked_neg(),
+                    };
+                    let result = result.ok_or_else(|| kre("integer overflow"))?;
+                    frame.set::<i64>(proc, out, result)
+                }
+                Instruction::IntegerBinOp { arg1, op, arg2, out } => {
+                    let arg1 = frame.get::<i64>(proc, arg1);
+                    let arg2 = frame.get::<i64>(proc, arg2);
+                    if let (BinOp::Divide, 0) = (op, arg2) {
+                        return Err(kre("division by zero"));
+                    }
+                    let result = match op {
+                        BinOp::Add => arg1.checked_add(arg2),
+                        BinOp::Subtract => arg1.checked_sub(arg2),
+                        BinOp::Multiply => arg1.checked_mul(arg2),
+                        BinOp::Divide => arg1.checked_div(arg2),
+                    };
+                    let result = result.ok_or_else(|| kre("integer overflow"))?;
+                    frame.set::<i64>(proc, out, result)
+                }
+                Instruction::StringConcat { arg1, arg2, out } => {
+                    let mut result = frame.get::<String>(proc, arg1);
+                    result.push_str(&frame.get::<String>(proc, arg2));
+                    frame.set::<String>(proc, out, result)
+                }
+                Instruction::Compare { arg1, op, arg2, out } => {
+                    let type_data = proc.type_of(arg1);
+                    let ordering = type_data.compare(frame.ref_register(proc, arg1), frame.ref_register(proc, arg2));
+                    let result = match op {
+                        Comparison::Equal => ordering.is_eq(),
+                        Comparison::NotEqual => ordering.is_ne(),
+                        Comparison::Less => ordering.is_lt(),
+                        Comparison::LessEqual => ordering.is_le(),
+                        Comparison::Greater => ordering.is_gt(),
+                        Comparison::GreaterEqual => ordering.is_ge(),
+                    };
+                    frame.set::<bool>(proc, out, result)
+                }
+                Instruction::BoolNot { arg, out } => {
+                    let arg = frame.get::<bool>(proc, arg);
+                    frame.set::<bool>(proc, out, !arg)
+                }
+                Instruction::Print { arg } => {
+                    let type_data = proc.type_of(arg);
+                    println!("{:?}", type_data.debug(frame.ref_register(proc, arg)))
+                }
+                Instruction::Jump { to } => {
+                    frame.ip = to;
+                    continue
+                }
+                Instruction::Call { procedure, args, .. } => {
+                    if depth >= MAX_FRAMES {
+                        return Err(kre("stack overflow"))
+                    }
+                    let callee = &self.program.procedures[procedure];
+                    let mut values = UntaggedValue::instantiate(&callee.args);
+                    for (i, r) in proc.rows[args].iter().enumerate() {
+                        let type_data = callee.args.fields[i].type_data;
+                        type_data.clone_into(frame.ref_register(proc, *r), values.mut_field(&callee.args, i));
+                    }
+                    // the callee returns to the instruction after this one
+                    frame.ip += 1;
+                    stack.push(Frame::new(procedure, callee, values));
+                    continue
+                }
+                Instruction::Return { values } => {
+                    let frame = stack.pop().unwrap();
+                    if let Some(caller) = stack.last_mut() {
+                        caller.receive(&self.program, proc, &frame, &proc.rows[values]);
+                    }
+                    // returning from inside a for
+                    let released = self.release_writes(frame.holds);
+                    frame.teardown(proc);
+                    released?;
+                    if stack.is_empty() { return Ok(None) }
+                    continue
+                }
+                Instruction::JumpUnlessEqual { arg1, arg2, to } => {
+                    let type_data = proc.type_of(arg1);
+                    let ordering = type_data.compare(frame.ref_register(proc, arg1), frame.ref_register(proc, arg2));
+                    if ordering != Ordering::Equal {
+                        frame.ip = to;
+                        continue
+                    }
+                }
+                Instruction::JumpIf { arg, to } => {
+                    if frame.get::<bool>(proc, arg) {
+                        frame.ip = to;
+                        continue
+                    }
+                }
+                Instruction::JumpUnless { arg, to } => {
+                    if !frame.get::<bool>(proc, arg) {
+                        frame.ip = to;
+                        continue
+                    }
+                }
+                Instruction::Open { iter, source, row, bound } => {
+                    // taking an aggregate's rows starts it over, so do that before anything borrows the frame
+                    let aggregated = match source {
+                        Source::Aggregate(a) => Some(frame.aggregate(proc, a)?),
+                        _ => None,
+                    };
+                    let registers = &proc.rows[row];
+                    let rows = {
+                        let bound: Vec<Option<RefToUnknown>> = registers.iter().enumerate().map(|(i, r)| {
+                            if bound & (1 << i) != 0 { Some(frame.ref_register(proc, *r)) } else { None }
+                        }).collect();
+                        match source {
+                            Source::Table(t) => self.program.tables[t].relation()?.open(&bound),
+                            Source::View(v) => Rc::new(self.evaluate_view(v)?).open(&bound),
+                            Source::Full(v) => self.fixpoint(v, false).open(&bound),
+                            Source::Delta(v) => self.fixpoint(v, true).open(&bound),
+                            Source::Aggregate(_) => Rc::new(aggregated.unwrap()).open(&bound),
+                        }
+                    };
+                    frame.iterators[iter] = Some(rows);
+                }
+                Instruction::Next { iter, row, bound, done } => {
+                    if !frame.next_row(proc, iter, &proc.rows[row], bound) {
+                        frame.ip = done;
+                        continue
+                    }
+                }
+                Instruction::Close { iter } => {
+                    frame.iterators[iter] = None;
+                }
+                Instruction::Emit { row } => {
+                    frame.emit(proc, &proc.rows[row])
+                }
+                Instruction::Accumulate { aggregate, row } => {
+                    frame.accumulate(proc, aggregate, &proc.rows[row])
+                }
+                Instruction::Write { write, table, row } => {
+                    let registers = &proc.rows[row];
+                    let values: Vec<RefToUnknown> = registers.iter().map(|r| frame.ref_register(proc, *r)).collect();
+                    if self.writes.borrow().holding() {
+                        let columns: Vec<TypeData> = registers.iter().map(|r| *proc.type_of(*r)).collect();
+                        self.writes.borrow_mut().defer(write, table, &columns, &values);
+                    } else {
+                        self::writes::write(&*self.program.tables[table].relation()?, write, &values);
+                    }
+                }
+                Instruction::HoldWrites => {
+                    frame.holds += 1;
+                    self.writes.borrow_mut().hold();
+                }
+                Instruction::ReleaseWrites => {
+                    frame.holds -= 1;
+                    self.release_writes(1)?;
+                }
             }
             frame.ip += 1;
         }
@@ -50,9 +392,30 @@ pub struct Frame {
     ip: usize,
     args: UntaggedValue,
     locals: UntaggedValue,
+    initialized: Vec<bool>,  // per local: args are always initialized
+    iterators: Vec<Option<Box<dyn Rows>>>,
+    output: Option<Tuples>,  // only when evaluating a view
+    accumulators: Vec<Tuples>,  // one per aggregate in the procedure
+    holds: usize,  // HoldWrites that haven't been released yet
+    at: Option<Located<()>>,  // for the debugger: where this frame last checked in
 }
 
 impl Frame {
+    fn new(procedure: usize, proc: &Procedure, args: UntaggedValue) -> Frame {
+        Frame { 
+            procedure, 
+            ip: 0,
+            args, 
+            locals: UntaggedValue::instantiate(&proc.locals),
+            initialized: vec![false; proc.locals.fields.len()],
+            iterators: (0..proc.iterators).map(|_| None).collect(),
+            output: None,
+            accumulators: proc.aggregates.iter().map(|a| Tuples::new(&a.columns)).collect(),
+            holds: 0,
+            at: None,
+        }
+    }
+
     fn ref_register<'a>(&'a self, proc: &'a Procedure, reg: Register) -> RefToUnknown<'a> {
         match reg {
             Register::Arg(a) => { self.args.ref_field(&proc.args, a) }
@@ -74,4 +437,149 @@ impl Frame {
         let m2_part = unsafe{&mut *ptr}.mut_register(proc, m2);
         (m1_part, m2_part)
     }
-}
\ No newline at end of file
+
+    fn get<T: Clone+'static>(&self, proc: &Procedure, reg: Register) -> T {
+        self.ref_register(proc, reg).cast::<T>().get().clone()
+    }
+
+    fn set<T: 'static>(&mut self, proc: &Procedure, reg: Register, value: T) {
+        self.clear(proc, reg);
+        self.mut_register(proc, reg).cast::<T>().initialize(value);
+        self.mark_initialized(reg);
+    }
+
+    fn is_initialized(&self, reg: Register) -> bool {
+        match reg {
+            Register::Arg(_) => true,
+            Register::Local(l) => self.initialized[l],
+        }
+    }
+
+    fn mark_initialized(&mut self, reg: Register) {
+        if let Register::Local(l) = reg { self.initialized[l] = true; }
+    }
+
+    // drop whatever is in the register, leaving it ready to be initialized again
+    fn clear(&mut self, proc: &Procedure, reg: Register) {
+        if !self.is_initialized(reg) { return; }
+        let type_data = proc.type_of(reg);
+        if let Some(drop) = type_data.drop_callback {
+            drop(self.mut_register(proc, reg));
+        }
+        self.mut_register(proc, reg).initialize_asserts(type_data.rust_type);
+        if let Register::Local(l) = reg { self.initialized[l] = false; }
+    }
+
+    // fills in the unbound registers of row from the iterator, closing it if it's empty
+    fn next_row(&mut self, proc: &Procedure, iter: usize, row: &[Register], bound: u32) -> bool {
+        let free: Vec<usize> = (0..row.len()).filter(|i| bound & (1 << i) == 0).collect();
+        for i in &free {
+            self.clear(proc, row[*i]);
+        }
+
+        // unbound columns each get a fresh local, so these never alias
+        let locals = &mut self.locals as *mut UntaggedValue;
+        let mut out: Vec<Option<MutToUnknown>> = row.iter().enumerate().map(|(i, r)| {
+            if bound & (1 << i) != 0 { return None }
+            match r {
+                Register::Local(l) => Some(unsafe { &mut *locals }.mut_field(&proc.locals, *l)),
+                Register::Arg(_) => unreachable!("queries only write to locals"),
+            }
+        }).collect();
+
+        let found = self.iterators[iter].as_mut().expect("iterator isn't open").next(&mut out);
+        drop(out);
+        if found {
+            for i in free { self.mark_initialized(row[i]) }
+        } else {
+            self.iterators[iter] = None;
+        }
+        found
+    }
+
+    fn emit(&mut self, proc: &Procedure, row: &[Register]) {
+        let (args, locals) = (&self.args, &self.locals);
+        let values: Vec<RefToUnknown> = row.iter().map(|r| match r {
+            Register::Arg(a) => args.ref_field(&proc.args, *a),
+            Register::Local(l) => locals.ref_field(&proc.locals, *l),
+        }).collect();
+        self.output.as_mut().expect("only views can emit rows").push(&values);
+    }
+
+    fn accumulate(&mut self, proc: &Procedure, aggregate: usize, row: &[Register]) {
+        let (args, locals) = (&self.args, &self.locals);
+        let values: Vec<RefToUnknown> = row.iter().map(|r| match r {
+            Register::Arg(a) => args.ref_field(&proc.args, *a),
+            Register::Local(l) => locals.ref_field(&proc.locals, *l),
+        }).collect();
+        self.accumulators[aggregate].push(&values);
+    }
+
+    // everything the aggregate's body found since it was last opened, one row per group
+    fn aggregate(&mut self, proc: &Procedure, aggregate: usize) -> Result<Tuples, KupoRuntimeError> {
+        let aggregation = &proc.aggregates[aggregate];
+        let rows = std::mem::replace(&mut self.accumulators[aggregate], Tuples::new(&aggregation.columns));
+        self::aggregate::aggregate(rows, aggregation)
+    }
+
+    // copies a callee's return values into the out registers of the Call that's waiting for them
+    fn receive(&mut self, program: &Program, callee_proc: &Procedure, callee: &Frame, values: &[Register]) {
+        let proc = &program.procedures[self.procedure];
+        let out = match proc.code.instructions[self.ip - 1] {
+            Instruction::Call { out, .. } => &proc.rows[out],
+            _ => unreachable!("returned to something that isn't a call"),
+        };
+        for (from, to) in values.iter().zip(out) {
+            self.clear(proc, *to);
+            let type_data = callee_proc.type_of(*from);
+            type_data.clone_into(callee.ref_register(callee_proc, *from), self.mut_register(proc, *to));
+            self.mark_initialized(*to);
+        }
+    }
+
+    fn teardown(mut self, proc: &Procedure) {
+        for a in 0..proc.args.fields.len() {
+            self.clear(proc, Register::Arg(a));
+        }
+        for l in 0..proc.locals.fields.len() {
+            self.clear(proc, Register::Local(l));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::run;
+
+    #[test]
+    fn calls() {
+        let source = "def even(@n Integer) Bool {\n    if @n == 0 { return @n == 0 }\n    return odd(@n - 1)\n}\n\
+            def odd(@n Integer) Bool {\n    if @n == 0 { return @n != 0 }\n    return even(@n - 1)\n}\n\
+            view [@a Bool, @b Bool] in v { @a := even(10), @b := odd(7) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[true, true]"]);
+        let source = "def divmod(@a Integer, @b Integer) [Integer, Integer] {\n    return [@a / @b, @a - @a / @b * @b]\n}\n\
+            view [@q Integer, @r Integer] in v { [@q, @r] := divmod(17, 5) }";
+        assert_eq!(run(source, "v").unwrap(), vec!["[3, 2]"]);
+    }
+
+    #[test]
+    fn errors() {
+        let source = "def forever(@n Integer) Integer {\n    return forever(@n + 1)\n}\nview [@n Integer] in v { @n := forever(0) }";
+        assert_eq!(run(source, "v").unwrap_err(), "stack overflow");
+        // an error in a callee stops the caller too
+        let source = "def f(@n Integer) Integer {\n    return 1 / @n\n}\ndef main() {\n    print(f(0))\n}\nview [@n Integer] in v { @n := 1 }";
+        assert_eq!(run(source, "v").unwrap_err(), "division by zero");
+    }
+
+    #[test]
+    fn fixpoints() {
+        let source = include_str!("../../../samplecode/vampires.kupo");
+        assert_eq!(run(source, "bloodline").unwrap(), vec![
+            "[NPC(0), NPC(1)]", "[NPC(0), NPC(2)]", "[NPC(0), NPC(3)]", "[NPC(3), NPC(2)]",
+        ]);
+        let source = "view [@n Integer] in even { @n := 0 } or { @m in odd, @n := @m + 1, @n < 10 }\n\
+            view [@n Integer] in odd { @m in even, @n := @m + 1, @n < 10 }";
+        assert_eq!(run(source, "even").unwrap(), vec!["[0]", "[2]", "[4]", "[6]", "[8]"]);
+        assert_eq!(run(source, "odd").unwrap(), vec!["[1]", "[3]", "[5]", "[7]", "[9]"]);
+    }
+}
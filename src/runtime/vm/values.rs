@@ -9,9 +9,12 @@ pub struct UntaggedValue {
 
 impl UntaggedValue {
     pub(crate) fn instantiate(structure: &Struct) -> Self {
-        let ptr = unsafe { std::alloc::alloc(structure.overall_layout) };
         let len = structure.overall_layout.size();
-        let data = unsafe { Box::from_raw(slice::from_raw_parts_mut(ptr, len)) };
+        // alloc() can't do zero-sized layouts
+        let data = if len == 0 { Box::new([]) as Box<[u8]> } else {
+            let ptr = unsafe { std::alloc::alloc(structure.overall_layout) };
+            unsafe { Box::from_raw(slice::from_raw_parts_mut(ptr, len)) }
+        };
 
         let mut value = UntaggedValue { data };
 
@@ -40,4 +43,19 @@ impl UntaggedValue {
         let len = field.type_data.layout.size();
         MutToUnknown::from(&mut self.data[offset..offset + len])
     }
-}
\ No newline at end of file
+
+    // every field at once, for filling in a whole row
+    pub(crate) fn mut_fields(&mut self, structure: &Struct) -> Vec<MutToUnknown<'_>> {
+        let mut rest: &mut [u8] = &mut self.data;
+        let mut start = 0;
+        let mut fields = vec![];
+        for field in &structure.fields {
+            let (_, here) = std::mem::take(&mut rest).split_at_mut(field.offset - start);
+            let (here, after) = here.split_at_mut(field.type_data.layout.size());
+            fields.push(MutToUnknown::from(here));
+            start = field.offset + field.type_data.layout.size();
+            rest = after;
+        }
+        fields
+    }
+}
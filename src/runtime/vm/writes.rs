@@ -0,0 +1,103 @@
+use crate::codegen::{TypeData, Write};
+
+use super::super::{RefToUnknown, Relation, Tuples};
+
+// writes made while anything's holding them (an unfinished for, a view) wait here until the last hold
+// is released, across every stack. if the program fails first, they never happen
+#[derive(Default)]
+pub(super) struct Writes {
+    holds: usize,
+    pending: Vec<PendingWrite>,
+}
+
+pub(super) struct PendingWrite {
+    pub write: Write,
+    pub table: usize,
+    pub row: Tuples,  // exactly one row: the registers it came from may be gone by the time it happens
+}
+
+impl Writes {
+    pub(super) fn holding(&self) -> bool {
+        self.holds > 0
+    }
+
+    pub(super) fn hold(&mut self) {
+        self.holds += 1
+    }
+
+    // hands back whatever can happen now
+    #[must_use]
+    pub(super) fn release(&mut self, holds: usize) -> Vec<PendingWrite> {
+        self.holds -= holds;
+        if self.holds > 0 { return vec![] }
+        std::mem::take(&mut self.pending)
+    }
+
+    pub(super) fn abandon(&mut self, holds: usize) {
+        self.holds -= holds;
+        if self.holds == 0 { self.pending.clear() }
+    }
+
+    pub(super) fn defer(&mut self, write: Write, table: usize, columns: &[TypeData], row: &[RefToUnknown<'_>]) {
+        let mut copy = Tuples::new(columns);
+        copy.push(row);
+        self.pending.push(PendingWrite { write, table, row: copy });
+    }
+}
+
+pub(super) fn write(table: &dyn Relation, write: Write, row: &[RefToUnknown<'_>]) {
+    match write {
+        Write::Insert => table.insert(row),
+        Write::Remove => table.remove(row),
+        Write::Expunge => table.expunge(row[0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::{UntaggedValue, VM};
+    use crate::testing::{compile, run};
+
+    const VAMPIRES: &str = include_str!("../../../samplecode/vampires.kupo");
+
+    #[test]
+    fn writes() {
+        let source = format!("{}\ndef main() {{\n    for @s in vampire, true_name(@s) == \"Vlad\" {{ turn_the_lonely(@s) }}\n}}\n\
+            view [@x NPC] in still_lonely {{ @x in lonely }}\n\
+            view [@x NPC] in turned_by_vlad {{ [@y, @x] in sired, true_name(@y) == \"Vlad\" }}", VAMPIRES);
+        assert_eq!(run(&source, "still_lonely").unwrap(), Vec::<String>::new());
+        assert_eq!(run(&source, "turned_by_vlad").unwrap(), vec!["[NPC(0)]", "[NPC(2)]", "[NPC(4)]"]);
+        let source = "def main() {\n    for @x in lonely, @x in vampire { expunge @x from lives_in }\n}\n\
+            view [@x NPC] in housed { [@x, @c] in lives_in }";
+        assert_eq!(run(source, "housed").unwrap(), vec!["[NPC(1)]", "[NPC(3)]"]);
+    }
+
+    #[test]
+    fn held_until_the_for_ends() {
+        // 4 would get found as another vampire if it went in straight away
+        let grow = "def grow() Integer {\n    @n := 0\n    for @x in vampire, @y in lonely {\n        insert @y into vampire\n        @n = @n + 1\n    }\n    return @n\n}\n";
+        let source = format!("{}view [@n Integer] in v {{ @n := grow() }}", grow);
+        assert_eq!(run(&source, "v").unwrap(), vec!["[12]"]);
+        let source = format!("{}def main() {{\n    print(grow())\n}}\nview [@x NPC] in v {{ @x in vampire }}", grow);
+        assert_eq!(run(&source, "v").unwrap().len(), 5);
+    }
+
+    #[test]
+    fn abandoned_on_error() {
+        let source = "def main() {\n    for @x in lonely {\n        remove @x from lonely\n        print(1 / 0)\n    }\n}\n\
+            view [@x NPC] in v { @x in lonely }";
+        let program = compile(source).unwrap();
+        let (main, v) = (program.procedure_named("main").unwrap(), program.procedure_named("v").unwrap());
+        let args = UntaggedValue::instantiate(&program.procedures[main].args);
+        let vm = VM::new(program);
+        assert_eq!(vm.call(main, args).unwrap_err().message, "division by zero");
+        assert_eq!(vm.evaluate_view(v).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        let codes = |source| compile(source).err().unwrap_or_default();
+        assert_eq!(codes("view [@x NPC] in v { @x in vampire }\ndef main() { for @x in lonely { insert @x into v } }"), vec!["C0015"]);
+        assert_eq!(codes("def main() { insert 1 into vampire }"), vec!["C0028"]);
+    }
+}
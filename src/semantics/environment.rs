@@ -0,0 +1,114 @@
+use std::{fmt::Debug, rc::Rc};
+
+use crate::codegen::TypeData;
+use crate::runtime::{Database, KupoSet, MutToUnknown, RefToUnknown, Table};
+
+// The stuff a kupo program can see that it didn't define itself:
+// types, tables and Rust functions provided by whoever is hosting the program
+pub struct Environment {
+    types: Vec<(String, TypeData)>,
+    database: Rc<Database>,
+    functions: Vec<HostFunction>,
+}
+
+pub struct HostFunction {
+    pub name: String,
+    pub arg: TypeData,
+    pub kind: HostFunctionKind,
+}
+
+#[derive(Clone, Copy)]
+pub enum HostFunctionKind {
+    Ref { rust_fn: fn(RefToUnknown) },
+    Mut { rust_fn: fn(RefToUnknown, MutToUnknown), returns: TypeData },
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        let mut env = Environment { types: vec![], database: Rc::new(Database::new()), functions: vec![] };
+        env.add_type("Integer", TypeData::new_copy::<i64>(
+            |ptr, dbg| ptr.cast::<i64>().get().fmt(dbg).unwrap(),
+        ).ordered::<i64>());
+        env.add_type("String", TypeData::new_clone::<String>(
+            |from, to| to.cast::<String>().initialize(from.cast::<String>().get().clone()),
+            |ptr, dbg| ptr.cast::<String>().get().fmt(dbg).unwrap(),
+            Some(|ptr| { ptr.cast::<String>().extract(); }),
+        ).ordered::<String>());
+        env.add_type("Bool", TypeData::new_copy::<bool>(
+            |ptr, dbg| ptr.cast::<bool>().get().fmt(dbg).unwrap(),
+        ).ordered::<bool>());
+        // what collect gives you
+        env.add_type("Set", TypeData::new_clone::<KupoSet>(
+            |from, to| to.cast::<KupoSet>().initialize(from.cast::<KupoSet>().get().clone()),
+            |ptr, dbg| ptr.cast::<KupoSet>().get().fmt(dbg).unwrap(),
+            Some(|ptr| { ptr.cast::<KupoSet>().extract(); }),
+        ).ordered::<KupoSet>());
+        env
+    }
+
+    pub fn add_type(&mut self, name: &str, type_data: TypeData) {
+        assert!(self.type_named(name).is_none(), "type already exists: {}", name);
+        self.types.push((name.to_string(), type_data))
+    }
+
+    // the tables kupo code can use: every column's type has to be added first, so kupo code can name it.
+    // The host can hang on to the database to swap tables later
+    pub fn use_database(&mut self, database: Rc<Database>) {
+        for table in database.tables() {
+            for c in &table.columns {
+                assert!(self.types.iter().any(|(_, t)| t == c), "table {} has a column of an unknown type: {}", table.name, c.rust_name);
+            }
+        }
+        self.database = database
+    }
+
+    pub fn add_function_ref(&mut self, name: &str, arg: &str, rust_fn: fn(RefToUnknown)) {
+        let arg = self.expect_type(arg);
+        self.add_function(HostFunction { name: name.to_string(), arg, kind: HostFunctionKind::Ref { rust_fn } })
+    }
+
+    pub fn add_function_mut(&mut self, name: &str, arg: &str, returns: &str, rust_fn: fn(RefToUnknown, MutToUnknown)) {
+        let arg = self.expect_type(arg);
+        let returns = self.expect_type(returns);
+        self.add_function(HostFunction { name: name.to_string(), arg, kind: HostFunctionKind::Mut { rust_fn, returns } })
+    }
+
+    fn add_function(&mut self, function: HostFunction) {
+        assert!(self.function_named(&function.name).is_none(), "function already exists: {}", function.name);
+        self.functions.push(function)
+    }
+
+    fn expect_type(&self, name: &str) -> TypeData {
+        match self.type_named(name) {
+            Some(t) => t,
+            None => panic!("unknown type: {}", name),
+        }
+    }
+
+    pub fn type_named(&self, name: &str) -> Option<TypeData> {
+        self.types.iter().find(|(n, _)| n == name).map(|(_, t)| *t)
+    }
+
+    pub fn type_name(&self, type_data: &TypeData) -> &str {
+        match self.types.iter().find(|(_, t)| t.rust_type == type_data.rust_type) {
+            Some((n, _)) => n,
+            None => "<unknown type>",
+        }
+    }
+
+    pub fn table_named(&self, name: &str) -> Option<&Table> {
+        self.database.table_named(name)
+    }
+
+    pub fn function_named(&self, name: &str) -> Option<(usize, &HostFunction)> {
+        self.functions.iter().enumerate().find(|(_, f)| f.name == name)
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        self.database.tables()
+    }
+
+    pub fn functions(&self) -> &[HostFunction] {
+        &self.functions
+    }
+}
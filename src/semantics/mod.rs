@@ -0,0 +1,90 @@
+mod environment;
+mod resolve;
+mod stratify;
+mod typeck;
+
+use crate::frontend::{Located, ast};
+
+pub use self::environment::{Environment, HostFunctionKind};
+pub use self::resolve::{Reference, resolve_module};
+pub use self::stratify::stratify_module;
+pub use self::typeck::{Typing, typecheck_module};
+
+// anything that goes wrong after parsing
+#[derive(Debug)]
+pub struct KupoCompileError {
+    pub code: Option<&'static str>,  // see COMPILE_ERRORS
+    pub message: String,
+}
+
+pub type Errors = Vec<Located<KupoCompileError>>;
+
+pub fn kce(code: &'static str, message: &str) -> KupoCompileError {
+    debug_assert!(COMPILE_ERRORS.iter().any(|(c, _)| *c == code), "compile error code isn't in the catalogue: {}", code);
+    KupoCompileError { code: Some(code), message: message.to_string() }
+}
+
+// every code kce gets, with the message it goes with ({} is whatever gets filled in).
+// tools match on these: never renumber or reuse a code (retired ones stay, commented out)
+const COMPILE_ERRORS: &[(&str, &str)] = &[
+    ("C0001", "duplicate item: {}"),
+    ("C0002", "there's already a table named {}"),
+    ("C0003", "there's already a function named {}"),
+    ("C0004", "duplicate argument: {}"),
+    ("C0005", "{} already exists: use = to assign to it"),
+    ("C0006", "{} was never introduced: use := to introduce it"),
+    ("C0007", "can only assign to a variable"),
+    ("C0008", "{} is only bound inside the {} before this"),
+    ("C0009", "{} has to be bound before the not that uses it"),
+    ("C0010", "can't bind {} inside a not"),
+    ("C0011", "{} is already bound"),
+    ("C0012", "can't {} over {}: it's already bound"),
+    ("C0013", "{} is a def, not a table or view"),
+    ("C0014", "unknown table: {}"),
+    ("C0015", "{} is a view: only tables can be written to"),
+    ("C0016", "{} is a def, not a table"),
+    ("C0017", "{} is a host function, not a table"),
+    ("C0018", "unknown variable: {}"),
+    ("C0019", "{} is a view: query it with `in {}`"),
+    ("C0020", "unknown function: {}"),
+    ("C0021", "unknown type: {}"),
+    ("C0022", "expected {}, found {}"),
+    ("C0023", "{} needs a type"),
+    ("C0024", "can't infer the types of {}: give its arguments types"),
+    ("C0025", "this clause makes {} {}, but it should be {}"),
+    ("C0026", "expected to return {}, found {}"),
+    ("C0027", "{} can't be written to"),
+    ("C0028", "expected {} to {} {} {}, found {}"),
+    ("C0029", "can't {} {} {}: values of type {} can't be compared"),
+    ("C0030", "can't assign {} value(s) to {} variable(s)"),
+    ("C0031", "{} has {} column(s), found {}"),
+    ("C0032", "can't {} values of type {}"),
+    ("C0033", "integer literal too large: {}"),
+    ("C0034", "can't apply {:?} to {} and {}"),
+    ("C0035", "expected a single value, found {}"),
+    ("C0036", "print takes exactly one argument"),
+    ("C0037", "{} takes {} argument(s), found {}"),
+    ("C0038", "{} depends on itself through this {}"),
+    ("C0039", "{} depends on itself through {}, but only views can be recursive"),
+    ("C0040", "{} is recursive, so every column has to be comparable"),
+    ("C0041", "can't query more than 32 columns at once"),
+    ("C0042", "{} can't be matched against itself: its type can't be compared"),
+    ("C0043", "can't group by more than 31 variables at once"),
+    ("C0044", "can't aggregate over values that can't be compared"),
+    ("C0045", "{} is only bound inside the {} in this clause"),
+    ("C0046", "{} isn't bound by this clause"),
+    ("C0047", "{} doesn't return a value on every path"),
+];
+
+pub fn check_module(module: &ast::Module, env: &Environment) -> Result<Typing, Errors> {
+    check_resolved(module, env, resolve_module(module, env).1)
+}
+
+// for when resolve_module's already been run. typeck assumes every name resolves
+pub fn check_resolved(module: &ast::Module, env: &Environment, unresolved: Errors) -> Result<Typing, Errors> {
+    if !unresolved.is_empty() { return Err(unresolved) }
+    let components = stratify_module(module)?;
+    let mut typing = typecheck_module(module, env)?;
+    typing.components = components;
+    Ok(typing)
+}
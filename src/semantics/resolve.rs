@@ -0,0 +1,439 @@
+use crate::frontend::{Located, ast};
+
+use super::{Environment, Errors, kce};
+
+// only checks that each name means something: whether it's the right kind of thing is up to typeck.
+// for editors, it also gives every use of a variable, def or view that resolves (tables aren't in the module)
+pub fn resolve_module(module: &ast::Module, env: &Environment) -> (Vec<Reference>, Errors) {
+    let mut resolver = Resolver { env, items: vec![], scopes: vec![], aggregated: vec![], references: vec![], errors: vec![] };
+    resolver.resolve_module(module);
+    (resolver.references, resolver.errors)
+}
+
+// a name, and the place it was introduced: both cover just the name. a declaration refers to itself
+#[derive(Clone, Copy, Debug)]
+pub struct Reference {
+    pub at: Located<()>,
+    pub target: Located<()>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ItemKind { Def, View }
+
+struct Resolver<'e> {
+    env: &'e Environment,
+    items: Vec<(Located<String>, ItemKind)>,
+    scopes: Vec<Vec<Located<String>>>,
+    aggregated: Vec<(String, &'static str, usize)>,  // variables that belonged to an aggregate, what it was and how deep
+    references: Vec<Reference>,
+    errors: Errors,
+}
+
+impl<'e> Resolver<'e> {
+    fn error<T>(&mut self, loc: &Located<T>, code: &'static str, s: &str) {
+        self.errors.push(loc.replace(kce(code, s)))
+    }
+
+    fn item(&self, name: &str) -> Option<ItemKind> {
+        self.items.iter().find(|(n, _)| n.value == name).map(|(_, k)| *k)
+    }
+
+    fn refer<T>(&mut self, at: &Located<T>, name: &str) {
+        let target = self.items.iter().find(|(n, _)| n.value == name).map(|(n, _)| span(n, &n.value));
+        if let Some(target) = target {
+            self.references.push(Reference { at: span(at, name), target })
+        }
+    }
+
+    fn refer_to_self(&mut self, name: &Located<String>) {
+        let at = span(name, &name.value);
+        // the same variable gets declared again in each scope it's visible in
+        if self.references.iter().any(|r| r.at.start == at.start) { return }
+        self.references.push(Reference { at, target: at })
+    }
+
+    // == scopes ==
+    fn lookup(&self, name: &str) -> Option<&Located<String>> {
+        self.scopes.iter().rev().flat_map(|s| s.iter().rev()).find(|v| v.value == name)
+    }
+
+    // like lookup, but remembers where at's name came from
+    fn lookup_from<T>(&mut self, at: &Located<T>, name: &str) -> bool {
+        let target = match self.lookup(name) { Some(v) => span(v, &v.value), None => return false };
+        self.references.push(Reference { at: span(at, name), target });
+        true
+    }
+
+    fn declare(&mut self, name: &Located<String>) {
+        self.refer_to_self(name);
+        self.scopes.last_mut().unwrap().push(name.clone())
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(vec![]);
+        f(self);
+        self.scopes.pop();
+        let depth = self.scopes.len();
+        self.aggregated.retain(|(_, _, d)| *d <= depth);
+    }
+
+    // for a better error than "unknown variable" when a variable is used after the aggregate it belonged to
+    fn aggregated_by(&self, name: &str) -> Option<&'static str> {
+        self.aggregated.iter().rev().find(|(n, _, _)| n == name).map(|(_, op, _)| *op)
+    }
+
+    // == structural ==
+    fn resolve_module(&mut self, module: &ast::Module) {
+        for item in &module.items {
+            let (name, kind) = match &item.value {
+                ast::Item::Def(d) => (&d.name, ItemKind::Def),
+                ast::Item::View(v) => (&v.name, ItemKind::View),
+            };
+            if self.item(&name.value).is_some() {
+                self.error(name, "C0001", &format!("duplicate item: {}", name.value));
+            } else if kind == ItemKind::View && self.env.table_named(&name.value).is_some() {
+                self.error(name, "C0002", &format!("there's already a table named {}", name.value));
+            } else if kind == ItemKind::Def && self.env.function_named(&name.value).is_some() {
+                self.error(name, "C0003", &format!("there's already a function named {}", name.value));
+            } else {
+                self.refer_to_self(name);
+                self.items.push((name.clone(), kind));
+            }
+        }
+
+        for item in &module.items {
+            self.scopes = vec![vec![]];
+            self.aggregated.clear();
+            match &item.value {
+                ast::Item::Def(d) => self.resolve_def(d),
+                ast::Item::View(v) => self.resolve_view(v),
+            }
+        }
+    }
+
+    fn resolve_def(&mut self, def: &ast::Def) {
+        for arg in &def.args {
+            if self.lookup(&arg.value.name.value).is_some() {
+                self.error(&arg.value.name, "C0004", &format!("duplicate argument: {}", arg.value.name.value));
+                continue
+            }
+            self.declare(&arg.value.name);
+        }
+        self.resolve_block(&def.body);
+    }
+
+    fn resolve_view(&mut self, view: &ast::View) {
+        for (i, arg) in view.args.iter().enumerate() {
+            let name = &arg.value.name;
+            if view.args[..i].iter().any(|a| a.value.name.value == name.value) {
+                self.error(name, "C0004", &format!("duplicate argument: {}", name.value));
+            } else {
+                self.refer_to_self(name);
+            }
+        }
+
+        // each clause has to bind every argument on its own
+        for clause in &view.clauses {
+            self.scoped(|s| {
+                s.resolve_query_expression(clause);
+                for arg in &view.args {
+                    let name = &arg.value.name.value;
+                    if s.lookup(name).is_some() { continue }
+                    match s.aggregated_by(name) {
+                        Some(op) => s.error(clause, "C0045", &format!("{} is only bound inside the {} in this clause", name, op)),
+                        None => s.error(clause, "C0046", &format!("{} isn't bound by this clause", name)),
+                    }
+                }
+            })
+        }
+    }
+
+    // == statement ==
+    fn resolve_block(&mut self, block: &Located<ast::Block>) {
+        self.scoped(|s| {
+            for statement in &block.value.items {
+                s.resolve_statement(statement);
+            }
+        })
+    }
+
+    fn resolve_statement(&mut self, statement: &Located<ast::Statement>) {
+        match &statement.value {
+            ast::Statement::For { clauses, body } => {
+                self.resolve_head(clauses, body)
+            }
+            ast::Statement::If { clauses, body, else_ } => {
+                self.resolve_head(clauses, body);
+                if let Some(else_) = else_ {
+                    self.resolve_block(else_);
+                }
+            }
+            ast::Statement::Return { args } => {
+                for arg in &args.value.args {
+                    self.resolve_expression(arg);
+                }
+            }
+            ast::Statement::Call { call } => {
+                self.resolve_call(call);
+            }
+            ast::Statement::Assign { first, variable, arg } => {
+                self.resolve_expression(arg);
+                for target in self.assign_targets(variable) {
+                    if *first {
+                        if self.lookup(&target.value).is_some() {
+                            self.error(&target, "C0005", &format!("{} already exists: use = to assign to it", target.value));
+                        } else {
+                            self.declare(&target);
+                        }
+                    } else if !self.lookup_from(&target, &target.value) {
+                        self.error(&target, "C0006", &format!("{} was never introduced: use := to introduce it", target.value));
+                    }
+                }
+            }
+            ast::Statement::Write { args, table, .. } => {
+                for arg in &args.value.args {
+                    self.resolve_expression(arg);
+                }
+                self.resolve_written_table(table);
+            }
+        }
+    }
+
+    // the body only gets to see variables that every clause binds
+    fn resolve_head(&mut self, clauses: &[Located<ast::QueryExpression>], body: &Located<ast::Block>) {
+        let bound = self.resolve_clauses(clauses, false);
+        self.scoped(|s| {
+            for v in bound { s.declare(&v) }
+            s.resolve_block(body);
+        })
+    }
+
+    // the variables that every clause binds
+    fn resolve_clauses(&mut self, clauses: &[Located<ast::QueryExpression>], negated: bool) -> Vec<Located<String>> {
+        let mut bound: Option<Vec<Located<String>>> = None;
+        for clause in clauses {
+            self.scoped(|s| {
+                for goal in &clause.value.items { s.resolve_goal(goal, negated) }
+                let here = s.scopes.last().unwrap();
+                bound = Some(match bound.take() {
+                    None => here.clone(),
+                    Some(b) => b.into_iter().filter(|v| here.iter().any(|h| h.value == v.value)).collect(),
+                });
+            })
+        }
+        bound.unwrap_or_default()
+    }
+
+    fn assign_targets(&mut self, target: &Located<ast::AssignTarget>) -> Vec<Located<String>> {
+        let mut targets = vec![];
+        for arg in &target.value.args {
+            match &arg.value {
+                ast::Expression::Variable { name } => targets.push(arg.replace(name.clone())),
+                _ => self.error(arg, "C0007", "can only assign to a variable"),
+            }
+        }
+        targets
+    }
+
+    // == query expression ==
+    fn resolve_query_expression(&mut self, query: &Located<ast::QueryExpression>) {
+        for goal in &query.value.items {
+            self.resolve_goal(goal, false)
+        }
+    }
+
+    // a not can't bind anything, so everything it uses has to be bound already
+    fn resolve_goal(&mut self, goal: &Located<ast::QueryGoal>, negated: bool) {
+        match &goal.value {
+            ast::QueryGoal::In { args, from } => {
+                self.resolve_table(from);
+                // bare variables are patterns: they're matched if bound and bound if not
+                for arg in &args.value.args {
+                    match &arg.value {
+                        ast::Expression::Variable { name } => {
+                            if self.lookup_from(arg, name) { continue }
+                            if let Some(op) = self.aggregated_by(name) {
+                                self.error(arg, "C0008", &format!("{} is only bound inside the {} before this", name, op));
+                            } else if negated {
+                                self.error(arg, "C0009", &format!("{} has to be bound before the not that uses it", name));
+                            }
+                            // even in a not: it's only been complained about once
+                            self.declare(&arg.replace(name.clone()));
+                        }
+                        _ => self.resolve_expression(arg),
+                    }
+                }
+            }
+            ast::QueryGoal::Assign { args, expression } => {
+                self.resolve_expression(expression);
+                for target in self.assign_targets(args) {
+                    if negated {
+                        self.error(&target, "C0010", &format!("can't bind {} inside a not", target.value));
+                    } else if self.lookup(&target.value).is_some() {
+                        self.error(&target, "C0011", &format!("{} is already bound", target.value));
+                    } else {
+                        self.declare(&target);
+                    }
+                }
+            }
+            ast::QueryGoal::Filter { condition } => self.resolve_expression(condition),
+            // the value's variables stay inside; anything else the body binds is a group
+            ast::QueryGoal::Aggregate { args, op, value, body } => {
+                let op = op.value.name();
+                let locals = value.value.variables();
+                for name in &locals {
+                    if self.lookup(name).is_some() {
+                        self.error(value, "C0012", &format!("can't {} over {}: it's already bound", op, name));
+                    }
+                }
+
+                // another aggregate can have a variable with the same name
+                self.aggregated.retain(|(n, _, _)| !locals.contains(&n.as_str()));
+                let mut groups = vec![];
+                self.scoped(|s| {
+                    for goal in &body.value.items { s.resolve_goal(goal, false) }
+                    s.resolve_expression(value);
+                    groups = s.scopes.last().unwrap().iter().filter(|v| !locals.contains(&v.value.as_str())).cloned().collect();
+                });
+                let depth = self.scopes.len();
+                self.aggregated.extend(locals.iter().map(|name| (name.to_string(), op, depth)));
+
+                for group in groups {
+                    if negated {
+                        self.error(&group, "C0009", &format!("{} has to be bound before the not that uses it", group.value));
+                    }
+                    self.declare(&group);
+                }
+                for target in self.assign_targets(args) {
+                    if negated {
+                        self.error(&target, "C0010", &format!("can't bind {} inside a not", target.value));
+                    } else if self.lookup(&target.value).is_some() {
+                        self.error(&target, "C0011", &format!("{} is already bound", target.value));
+                    } else {
+                        self.declare(&target);
+                    }
+                }
+            }
+            ast::QueryGoal::Not { body } => self.scoped(|s| {
+                for goal in &body.value.items {
+                    s.resolve_goal(goal, true)
+                }
+            }),
+            // like the head of a for: what comes after only sees what every clause binds
+            ast::QueryGoal::Disjunction { clauses } => {
+                for v in self.resolve_clauses(clauses, negated) { self.declare(&v) }
+            }
+        }
+    }
+
+    fn resolve_table(&mut self, name: &Located<String>) {
+        if self.env.table_named(&name.value).is_some() { return }
+        match self.item(&name.value) {
+            Some(ItemKind::View) => self.refer(name, &name.value),
+            Some(ItemKind::Def) => self.error(name, "C0013", &format!("{} is a def, not a table or view", name.value)),
+            None => self.error(name, "C0014", &format!("unknown table: {}", name.value)),
+        }
+    }
+
+    // views are worked out from everything else, so only the host's tables can be changed
+    fn resolve_written_table(&mut self, name: &Located<String>) {
+        if self.env.table_named(&name.value).is_some() { return }
+        match self.item(&name.value) {
+            Some(ItemKind::View) => {
+                self.refer(name, &name.value);
+                self.error(name, "C0015", &format!("{} is a view: only tables can be written to", name.value))
+            }
+            Some(ItemKind::Def) => self.error(name, "C0016", &format!("{} is a def, not a table", name.value)),
+            None if self.env.function_named(&name.value).is_some() => {
+                self.error(name, "C0017", &format!("{} is a host function, not a table", name.value))
+            }
+            None => self.error(name, "C0014", &format!("unknown table: {}", name.value)),
+        }
+    }
+
+    // == expression ==
+    fn resolve_expression(&mut self, expr: &Located<ast::Expression>) {
+        match &expr.value {
+            ast::Expression::StringLiteral { .. } | ast::Expression::IntegerLiteral { .. } => {}
+            ast::Expression::Variable { name } => {
+                if self.lookup_from(expr, name) { return }
+                match self.aggregated_by(name) {
+                    Some(op) => self.error(expr, "C0008", &format!("{} is only bound inside the {} before this", name, op)),
+                    None => self.error(expr, "C0018", &format!("unknown variable: {}", name)),
+                }
+            }
+            ast::Expression::Call { call } => self.resolve_call(call),
+            ast::Expression::UOp { arg, .. } => self.resolve_expression(arg),
+            ast::Expression::BinOp { arg1, arg2, .. } => {
+                self.resolve_expression(arg1);
+                self.resolve_expression(arg2);
+            }
+        }
+    }
+
+    fn resolve_call(&mut self, call: &Located<ast::Call>) {
+        let name = &call.value.name;
+        if name.value != "print" && self.env.function_named(&name.value).is_none() {
+            match self.item(&name.value) {
+                Some(ItemKind::Def) => self.refer(name, &name.value),
+                Some(ItemKind::View) => self.error(name, "C0019", &format!("{} is a view: query it with `in {}`", name.value, name.value)),
+                None => self.error(name, "C0020", &format!("unknown function: {}", name.value)),
+            }
+        }
+        for arg in &call.value.args {
+            self.resolve_expression(arg);
+        }
+    }
+}
+
+// locations run up to the next token: this is just the name
+fn span<T>(at: &Located<T>, name: &str) -> Located<()> {
+    Located { value: (), start: at.start, end: at.start + name.len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::compile;
+
+    fn codes(source: &str) -> Vec<&'static str> {
+        compile(source).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn items() {
+        assert_eq!(codes("def f() {}\ndef f() {}"), vec!["C0001"]);
+        assert_eq!(codes("view [@x NPC] in vampire { @x in lonely }"), vec!["C0002"]);
+        assert_eq!(codes("def true_name() {}"), vec!["C0003"]);
+        assert_eq!(codes("def f(@x Integer, @x Integer) {}"), vec!["C0004"]);
+        assert_eq!(codes("def f() { g() }"), vec!["C0020"]);
+        assert_eq!(codes("def f() { for @x in nowhere {} }"), vec!["C0014"]);
+        assert_eq!(codes("def f() {}\ndef g() { for @x in f {} }"), vec!["C0013"]);
+        assert_eq!(codes("view [@x NPC] in v { @x in vampire }\ndef g() { v() }"), vec!["C0019"]);
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(codes("def f() { print(@x) }"), vec!["C0018"]);
+        assert_eq!(codes("def f() { @x := 1\n @x := 2 }"), vec!["C0005"]);
+        assert_eq!(codes("def f() { @x = 1 }"), vec!["C0006"]);
+        assert_eq!(codes("def f() { @x := 1\n @x = 2\n print(@x) }"), Vec::<&str>::new());
+        assert_eq!(codes("def f() [Integer, Integer] { return [1, 2] }\ndef g() { [@x, 1] := f() }"), vec!["C0007"]);
+    }
+
+    #[test]
+    fn scopes() {
+        // the body of a for sees what its head binds, but nothing after it does
+        assert_eq!(codes("def f() { for @x in vampire { print(@x) } }"), Vec::<&str>::new());
+        assert_eq!(codes("def f() { for @x in vampire {}\n print(@x) }"), vec!["C0018"]);
+        assert_eq!(codes("def f() { if @x in vampire { @y := 1 }\n print(@y) }"), vec!["C0018"]);
+        // every clause of a head has to bind a variable for the body to see it
+        assert_eq!(codes("def f() { for @x in vampire or @y in lonely { print(@x) } }"), vec!["C0018"]);
+    }
+
+    #[test]
+    fn view_clauses() {
+        assert_eq!(codes("view [@x NPC] in v { @y in vampire }"), vec!["C0046"]);
+        assert_eq!(codes("view [@x NPC] in v { @x in vampire } or { @y in lonely }"), vec!["C0046"]);
+        assert_eq!(codes("view [@n Integer] in v { @m := count(@n : @n in w) }\nview [@n Integer] in w { @n := 1 }"), vec!["C0045"]);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::frontend::{Located, ast};
+
+use super::{Errors, kce};
+
+// views can be recursive, but not through a not, an aggregate or a def.
+// hands back each group of views that depend on each other
+pub fn stratify_module(module: &ast::Module) -> Result<Vec<Vec<String>>, Errors> {
+    let items: Vec<&ast::Item> = module.items.iter().map(|i| &i.value).collect();
+    let mut edges = vec![];
+    for item in &items {
+        let mut collector = Collector { items: &items, edges: vec![] };
+        match item {
+            ast::Item::Def(d) => collector.block(&d.body),
+            ast::Item::View(v) => for clause in &v.clauses { collector.query(clause, None) },
+        }
+        edges.push(collector.edges);
+    }
+
+    let mut components = vec![];
+    let mut errors = vec![];
+    for component in strongly_connected(&edges) {
+        let recursive = component.len() > 1 || edges[component[0]].iter().any(|e| e.to == component[0]);
+        if !recursive { continue }
+
+        for &from in &component {
+            for edge in edges[from].iter().filter(|e| component.contains(&e.to)) {
+                let name = &items[from].name().value;
+                if let Some(through) = edge.through {
+                    errors.push(edge.at.replace(kce("C0038", &format!("{} depends on itself through this {}", name, through))));
+                } else if let (ast::Item::View(_), ast::Item::Def(d)) = (items[from], items[edge.to]) {
+                    errors.push(edge.at.replace(kce("C0039", &format!("{} depends on itself through {}, but only views can be recursive", name, d.name.value))));
+                }
+            }
+        }
+        // defs calling each other is just recursion
+        if component.iter().all(|i| matches!(items[*i], ast::Item::View(_))) {
+            components.push(component.iter().map(|i| items[*i].name().value.clone()).collect());
+        }
+    }
+    if errors.is_empty() { Ok(components) } else { Err(errors) }
+}
+
+// an item using another one
+struct Edge {
+    to: usize,
+    at: Located<()>,
+    through: Option<&'static str>,  // inside a not or an aggregate, which can't be part of a fixpoint
+}
+
+struct Collector<'m> {
+    items: &'m [&'m ast::Item],
+    edges: Vec<Edge>,
+}
+
+impl<'m> Collector<'m> {
+    fn refer(&mut self, name: &Located<String>, through: Option<&'static str>) {
+        // tables and host functions don't depend on anything
+        if let Some(to) = self.items.iter().position(|i| i.name().value == name.value) {
+            self.edges.push(Edge { to, at: name.location(), through })
+        }
+    }
+
+    fn block(&mut self, block: &Located<ast::Block>) {
+        for statement in &block.value.items {
+            match &statement.value {
+                ast::Statement::For { clauses, body } => {
+                    for clause in clauses { self.query(clause, None) }
+                    self.block(body);
+                }
+                ast::Statement::If { clauses, body, else_ } => {
+                    for clause in clauses { self.query(clause, None) }
+                    self.block(body);
+                    if let Some(else_) = else_ { self.block(else_) }
+                }
+                ast::Statement::Return { args } => self.target(args, None),
+                ast::Statement::Call { call } => self.call(call, None),
+                ast::Statement::Assign { variable, arg, .. } => {
+                    self.target(variable, None);
+                    self.expression(arg, None);
+                }
+                ast::Statement::Write { args, .. } => self.target(args, None),
+            }
+        }
+    }
+
+    fn query(&mut self, query: &Located<ast::QueryExpression>, through: Option<&'static str>) {
+        for goal in &query.value.items {
+            match &goal.value {
+                ast::QueryGoal::In { args, from } => {
+                    self.target(args, through);
+                    self.refer(from, through);
+                }
+                ast::QueryGoal::Assign { args, expression } => {
+                    self.target(args, through);
+                    self.expression(expression, through);
+                }
+                ast::QueryGoal::Filter { condition } => self.expression(condition, through),
+                ast::QueryGoal::Not { body } => self.query(body, Some("not")),
+                ast::QueryGoal::Aggregate { args, op, value, body } => {
+                    self.target(args, through);
+                    self.expression(value, Some(op.value.name()));
+                    self.query(body, Some(op.value.name()));
+                }
+                ast::QueryGoal::Disjunction { clauses } => for clause in clauses { self.query(clause, through) },
+            }
+        }
+    }
+
+    fn target(&mut self, target: &Located<ast::AssignTarget>, through: Option<&'static str>) {
+        for arg in &target.value.args { self.expression(arg, through) }
+    }
+
+    fn call(&mut self, call: &Located<ast::Call>, through: Option<&'static str>) {
+        self.refer(&call.value.name, through);
+        for arg in &call.value.args { self.expression(arg, through) }
+    }
+
+    fn expression(&mut self, expression: &Located<ast::Expression>, through: Option<&'static str>) {
+        match &expression.value {
+            ast::Expression::StringLiteral { .. } | ast::Expression::IntegerLiteral { .. } | ast::Expression::Variable { .. } => {}
+            ast::Expression::Call { call } => self.call(call, through),
+            ast::Expression::UOp { arg, .. } => self.expression(arg, through),
+            ast::Expression::BinOp { arg1, arg2, .. } => {
+                self.expression(arg1, through);
+                self.expression(arg2, through);
+            }
+        }
+    }
+}
+
+// Tarjan's algorithm: each component comes out after everything it depends on
+fn strongly_connected(edges: &[Vec<Edge>]) -> Vec<Vec<usize>> {
+    struct Tarjan<'e> {
+        edges: &'e [Vec<Edge>],
+        next: usize,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, v: usize) {
+            let index = self.next;
+            self.next += 1;
+            self.index[v] = Some(index);
+            self.low[v] = index;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+
+            for edge in &self.edges[v] {
+                match self.index[edge.to] {
+                    None => {
+                        self.visit(edge.to);
+                        self.low[v] = self.low[v].min(self.low[edge.to]);
+                    }
+                    Some(i) if self.on_stack[edge.to] => self.low[v] = self.low[v].min(i),
+                    Some(_) => {}
+                }
+            }
+
+            if self.low[v] == index {
+                let mut component = vec![];
+                loop {
+                    let w = self.stack.pop().unwrap();
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v { break }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let n = edges.len();
+    let mut tarjan = Tarjan { edges, next: 0, index: vec![None; n], low: vec![0; n], stack: vec![], on_stack: vec![false; n], components: vec![] };
+    for v in 0..n {
+        if tarjan.index[v].is_none() { tarjan.visit(v) }
+    }
+    tarjan.components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stratify_module;
+    use crate::frontend::parse_module;
+    use crate::testing::compile;
+
+    #[test]
+    fn components() {
+        let source = "view [@n Integer] in a { @n := 1 } or { @n in b }\n\
+            view [@n Integer] in b { @n in a }\n\
+            view [@n Integer] in c { @n in c }\n\
+            view [@n Integer] in d { @n in a }\n\
+            def e(@n Integer) Integer {\n    return f(@n)\n}\ndef f(@n Integer) Integer {\n    return e(@n)\n}";
+        let module = parse_module(source).unwrap().value;
+        let mut components = stratify_module(&module).unwrap();
+        for c in &mut components { c.sort() }
+        components.sort();
+        assert_eq!(components, vec![vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn errors() {
+        let codes = |source| compile(source).err().unwrap_or_default();
+        assert_eq!(codes("view [@x NPC] in v { @x in vampire, not @x in v }"), vec!["C0038"]);
+        assert_eq!(codes("view [@n Integer] in v { @n := count(@x : @x in w) }\nview [@x NPC] in w { @x in vampire } or { @n in v, @x in lonely }"), vec!["C0038"]);
+        assert_eq!(codes("view [@n Integer] in v { @n := f() }\ndef f() Integer {\n    for @n in v { return @n }\n    return 0\n}"), vec!["C0039"]);
+    }
+}
@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+
+use crate::codegen::TypeData;
+use crate::frontend::{Located, ast};
+
+use super::{Environment, Errors, HostFunctionKind, kce};
+
+// The result of type checking: everything codegen needs to lay out its structs.
+// Expressions are keyed by their span, which is unique for every expression that has a type.
+pub struct Typing {
+    signatures: Vec<(String, Signature)>,
+    expressions: HashMap<(usize, usize), Vec<TypeData>>,
+    pub(super) components: Vec<Vec<String>>,  // views that use each other, from stratify_module
+}
+
+#[derive(Clone, Debug)]
+pub struct Signature {
+    pub args: Vec<TypeData>,
+    pub returns: Vec<TypeData>,  // always empty for views
+}
+
+impl Typing {
+    pub fn signature(&self, name: &str) -> Option<&Signature> {
+        self.signatures.iter().find(|(n, _)| n == name).map(|(_, s)| s)
+    }
+
+    pub fn components(&self) -> &[Vec<String>] {
+        &self.components
+    }
+
+    // the views that have to be evaluated along with this one, if it's recursive (itself included)
+    pub fn component(&self, view: &str) -> Option<&[String]> {
+        self.components.iter().find(|c| c.iter().any(|v| v == view)).map(|c| c.as_slice())
+    }
+
+    // calls can have zero types, or several
+    pub fn expression<T>(&self, expr: &Located<T>) -> &[TypeData] {
+        &self.expressions[&(expr.start, expr.end)]
+    }
+
+    pub fn single<T>(&self, expr: &Located<T>) -> TypeData {
+        let types = self.expression(expr);
+        assert_eq!(types.len(), 1);
+        types[0]
+    }
+}
+
+pub fn typecheck_module(module: &ast::Module, env: &Environment) -> Result<Typing, Errors> {
+    let mut checker = Checker {
+        env,
+        typing: Typing { signatures: vec![], expressions: HashMap::new(), components: vec![] },
+        views: vec![],
+        checking: vec![],
+        self_reference: false,
+        scopes: vec![],
+        returns: None,
+        errors: vec![],
+    };
+    checker.check_module(module);
+    if checker.errors.is_empty() { Ok(checker.typing) } else { Err(checker.errors) }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ViewState { Unchecked, InProgress, Checked }
+
+// None means "unknown because of an earlier error": don't complain about it again
+type Checked = Option<Vec<TypeData>>;
+
+struct Checker<'a> {
+    env: &'a Environment,
+    typing: Typing,
+    views: Vec<(&'a ast::View, ViewState)>,
+    checking: Vec<usize>,  // views in progress, innermost last
+    self_reference: bool,  // whether the innermost one has used itself before its types were known
+    scopes: Vec<Vec<(String, Option<TypeData>)>>,
+    returns: Option<Vec<TypeData>>,
+    errors: Errors,
+}
+
+impl<'a> Checker<'a> {
+    fn error<T>(&mut self, loc: &Located<T>, code: &'static str, s: &str) {
+        self.errors.push(loc.replace(kce(code, s)))
+    }
+
+    fn type_name(&self, t: &TypeData) -> String {
+        self.env.type_name(t).to_string()
+    }
+
+    fn type_names(&self, ts: &[TypeData]) -> String {
+        match ts {
+            [] => "nothing".to_string(),
+            [t] => self.type_name(t),
+            _ => format!("[{}]", ts.iter().map(|t| self.type_name(t)).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    fn resolve_type(&mut self, t: &Located<ast::Type>) -> Option<TypeData> {
+        let found = self.env.type_named(&t.value.name.value);
+        if found.is_none() {
+            self.error(&t.value.name, "C0021", &format!("unknown type: {}", t.value.name.value));
+        }
+        found
+    }
+
+    fn record<T>(&mut self, expr: &Located<T>, types: Checked) -> Checked {
+        if let Some(ts) = &types {
+            self.typing.expressions.insert((expr.start, expr.end), ts.clone());
+        }
+        types
+    }
+
+    // == scopes ==
+    fn lookup(&self, name: &str) -> Option<Option<TypeData>> {
+        self.scopes.iter().rev().flat_map(|s| s.iter().rev()).find(|(n, _)| n == name).map(|(_, t)| *t)
+    }
+
+    fn bind(&mut self, name: &str, t: Option<TypeData>) {
+        self.scopes.last_mut().unwrap().push((name.to_string(), t))
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(vec![]);
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn expect_type<T>(&mut self, loc: &Located<T>, expected: TypeData, found: TypeData) {
+        if expected != found {
+            let (e, f) = (self.type_name(&expected), self.type_name(&found));
+            self.error(loc, "C0022", &format!("expected {}, found {}", e, f));
+        }
+    }
+
+    // == structural ==
+    fn check_module(&mut self, module: &'a ast::Module) {
+        for item in &module.items {
+            match &item.value {
+                ast::Item::Def(d) => {
+                    let mut args = vec![];
+                    for arg in &d.args {
+                        match &arg.value.type_name {
+                            Some(t) => args.push(self.resolve_type(t)),
+                            None => {
+                                self.error(&arg.value.name, "C0023", &format!("{} needs a type", arg.value.name.value));
+                                args.push(None)
+                            }
+                        }
+                    }
+                    let mut returns = vec![];
+                    for t in d.return_type.iter().flatten() {
+                        returns.push(self.resolve_type(t));
+                    }
+                    // if any of it's missing, calls to this def will go unchecked
+                    let args: Option<Vec<TypeData>> = args.into_iter().collect();
+                    let returns: Option<Vec<TypeData>> = returns.into_iter().collect();
+                    if let (Some(args), Some(returns)) = (args, returns) {
+                        self.typing.signatures.push((d.name.value.clone(), Signature { args, returns }));
+                    }
+                }
+                ast::Item::View(v) => {
+                    self.views.push((v, ViewState::Unchecked));
+                }
+            }
+        }
+
+        for ix in 0..self.views.len() {
+            self.check_view(ix);
+        }
+
+        for item in &module.items {
+            if let ast::Item::Def(d) = &item.value {
+                self.check_def(d);
+            }
+        }
+    }
+
+    fn check_def(&mut self, def: &ast::Def) {
+        let signature = if let Some(s) = self.typing.signature(&def.name.value) { s.clone() } else { return };
+
+        self.scopes = vec![vec![]];
+        for (arg, t) in def.args.iter().zip(signature.args.iter()) {
+            self.bind(&arg.value.name.value, Some(*t));
+        }
+        let returns = !signature.returns.is_empty();
+        self.returns = Some(signature.returns);
+        self.check_block(&def.body);
+        self.returns = None;
+        if returns && !always_returns(&def.body.value) {
+            self.error(&def.name, "C0047", &format!("{} doesn't return a value on every path", def.name.value));
+        }
+    }
+
+    fn view_signature(&mut self, name: &str) -> Option<Vec<TypeData>> {
+        if let Some(s) = self.typing.signature(name) { return Some(s.args.clone()) }
+        let ix = self.views.iter().position(|(v, _)| v.name.value == name)?;
+        self.check_view(ix);
+        self.typing.signature(name).map(|s| s.args.clone())
+    }
+
+    fn check_view(&mut self, ix: usize) {
+        let (view, state) = self.views[ix];
+        match state {
+            ViewState::Checked => return,
+            ViewState::InProgress => {
+                // a view can use itself: its other clauses say what its types are
+                if self.checking.last() == Some(&ix) {
+                    self.self_reference = true;
+                    return
+                }
+                // otherwise we only get here if the view's own types depend on themselves
+                self.error(&view.name, "C0024", &format!("can't infer the types of {}: give its arguments types", view.name.value));
+                return
+            }
+            ViewState::Unchecked => {}
+        }
+        self.views[ix].1 = ViewState::InProgress;
+        self.checking.push(ix);
+        let outer_self_reference = std::mem::replace(&mut self.self_reference, false);
+
+        // if everything's declared, other views can see the signature before we're done
+        let mut declared = vec![];
+        for arg in &view.args {
+            declared.push(match &arg.value.type_name {
+                Some(t) => self.resolve_type(t),
+                None => None,
+            })
+        }
+        let fully_declared = declared.iter().all(|t| t.is_some());
+        if fully_declared {
+            let args = declared.iter().map(|t| t.unwrap()).collect();
+            self.typing.signatures.push((view.name.value.clone(), Signature { args, returns: vec![] }));
+        }
+
+        let errors = self.errors.len();
+        let inferred = self.check_view_clauses(view, declared);
+        if !fully_declared && inferred.iter().all(|t| t.is_some()) {
+            let args = inferred.iter().map(|t| t.unwrap()).collect();
+            self.typing.signatures.push((view.name.value.clone(), Signature { args, returns: vec![] }));
+        }
+
+        // clauses that used the view before its types were known need checking again (keeping only the new errors)
+        if self.self_reference {
+            if self.typing.signature(&view.name.value).is_some() {
+                let first = self.errors.split_off(errors);
+                self.check_view_clauses(view, inferred);
+                let second = self.errors.split_off(errors);
+                self.errors.extend(first);
+                for e in second {
+                    if !self.errors[errors..].iter().any(|f| (f.start, f.end, &f.value.message) == (e.start, e.end, &e.value.message)) {
+                        self.errors.push(e)
+                    }
+                }
+            } else {
+                self.error(&view.name, "C0024", &format!("can't infer the types of {}: give its arguments types", view.name.value));
+            }
+        }
+
+        self.self_reference = outer_self_reference;
+        self.checking.pop();
+        self.views[ix].1 = ViewState::Checked;
+    }
+
+    // returns the type of each argument, as far as the clauses agree on it
+    fn check_view_clauses(&mut self, view: &'a ast::View, mut inferred: Vec<Option<TypeData>>) -> Vec<Option<TypeData>> {
+        let outer_scopes = std::mem::take(&mut self.scopes);
+        for clause in &view.clauses {
+            self.scopes = vec![vec![]];
+            self.check_query_expression(clause);
+            for (arg, t) in view.args.iter().zip(inferred.iter_mut()) {
+                let name = &arg.value.name.value;
+                let found = if let Some(Some(found)) = self.lookup(name) { found } else { continue };
+                match t {
+                    Some(expected) => if *expected != found {
+                        let (e, f) = (self.type_name(expected), self.type_name(&found));
+                        self.error(clause, "C0025", &format!("this clause makes {} {}, but it should be {}", name, f, e));
+                    }
+                    None => *t = Some(found),
+                }
+            }
+        }
+        self.scopes = outer_scopes;
+        inferred
+    }
+
+    // == statement ==
+    fn check_block(&mut self, block: &Located<ast::Block>) {
+        self.scoped(|s| {
+            for statement in &block.value.items {
+                s.check_statement(statement);
+            }
+        })
+    }
+
+    fn check_statement(&mut self, statement: &Located<ast::Statement>) {
+        match &statement.value {
+            ast::Statement::For { clauses, body } => {
+                self.check_head(clauses, body)
+            }
+            ast::Statement::If { clauses, body, else_ } => {
+                self.check_head(clauses, body);
+                if let Some(else_) = else_ {
+                    self.check_block(else_);
+                }
+            }
+            ast::Statement::Return { args } => {
+                let found = self.check_values(args);
+                let expected = self.returns.clone().unwrap_or_default();
+                if let Some(found) = found {
+                    if found != expected {
+                        let (e, f) = (self.type_names(&expected), self.type_names(&found));
+                        self.error(args, "C0026", &format!("expected to return {}, found {}", e, f));
+                    }
+                }
+            }
+            ast::Statement::Call { call } => {
+                self.check_call(call);
+            }
+            ast::Statement::Assign { first, variable, arg } => {
+                let found = self.check_expression(arg);
+                self.check_assign_target(variable, found, |s, name, t| {
+                    if *first {
+                        s.bind(&name.value, t);
+                        return
+                    }
+                    if let (Some(Some(expected)), Some(t)) = (s.lookup(&name.value), t) {
+                        s.expect_type(arg, expected, t);
+                    }
+                });
+            }
+            ast::Statement::Write { op, args, table } => {
+                self.check_write(op, args, table);
+            }
+        }
+    }
+
+    fn check_write(&mut self, op: &Located<ast::Write>, args: &Located<ast::AssignTarget>, table: &Located<String>) {
+        let found = self.check_values(args);
+        let table = if let Some(table) = self.env.table_named(&table.value) { table } else { return };
+        if !table.relation().writable() {
+            self.error(op, "C0027", &format!("{} can't be written to", table.name));
+            return
+        }
+
+        // an expunge only names the key
+        let expected = match op.value {
+            ast::Write::Insert | ast::Write::Remove => table.columns.clone(),
+            ast::Write::Expunge => vec![table.columns[0]],
+        };
+        if let Some(found) = found {
+            if found != expected {
+                let (e, f) = (self.type_names(&expected), self.type_names(&found));
+                self.error(args, "C0028", &format!("expected {} to {} {} {}, found {}", e, op.value.name(), op.value.preposition(), table.name, f));
+            }
+        }
+        // finding the rows to drop means comparing them
+        let compared = if op.value == ast::Write::Insert { &[][..] } else { &expected[..] };
+        if let Some(t) = compared.iter().find(|t| !t.is_ordered()) {
+            let t = self.type_name(t);
+            self.error(op, "C0029", &format!("can't {} {} {}: values of type {} can't be compared", op.value.name(), op.value.preposition(), table.name, t));
+        }
+    }
+
+    // the clauses have to agree on the types of the variables the body gets to see
+    fn check_head(&mut self, clauses: &[Located<ast::QueryExpression>], body: &Located<ast::Block>) {
+        let bound = self.check_clauses(clauses);
+        self.scoped(|s| {
+            for (name, t) in bound { s.bind(&name, t) }
+            s.check_block(body);
+        })
+    }
+
+    // the variables that every clause binds, and their types
+    fn check_clauses(&mut self, clauses: &[Located<ast::QueryExpression>]) -> Vec<(String, Option<TypeData>)> {
+        let mut bound: Option<Vec<(String, Option<TypeData>)>> = None;
+        for clause in clauses {
+            self.scoped(|s| {
+                s.check_query_expression(clause);
+                let here = s.scopes.last().unwrap().clone();
+                let previous = match bound.take() {
+                    None => { bound = Some(here); return }
+                    Some(b) => b,
+                };
+
+                let mut shared = vec![];
+                for (name, t) in previous {
+                    let t2 = if let Some((_, t2)) = here.iter().find(|(n, _)| *n == name) { *t2 } else { continue };
+                    if let (Some(t), Some(t2)) = (t, t2) {
+                        if t != t2 {
+                            let (e, f) = (s.type_name(&t), s.type_name(&t2));
+                            s.error(clause, "C0025", &format!("this clause makes {} {}, but it should be {}", name, f, e));
+                        }
+                    }
+                    shared.push((name, t.or(t2)));
+                }
+                bound = Some(shared);
+            })
+        }
+        bound.unwrap_or_default()
+    }
+
+    // calls `each` once per target, with its type if we know it
+    fn check_assign_target(
+        &mut self,
+        target: &Located<ast::AssignTarget>,
+        types: Checked,
+        mut each: impl FnMut(&mut Self, &Located<String>, Option<TypeData>),
+    ) {
+        let args = &target.value.args;
+        let types = match types {
+            Some(ts) if ts.len() != args.len() => {
+                let n = ts.len();
+                self.error(target, "C0030", &format!("can't assign {} value(s) to {} variable(s)", n, args.len()));
+                None
+            }
+            ts => ts,
+        };
+        for (i, arg) in args.iter().enumerate() {
+            if let ast::Expression::Variable { name } = &arg.value {
+                let t = types.as_ref().map(|ts| ts[i]);
+                self.record(arg, t.map(|t| vec![t]));
+                each(self, &arg.replace(name.clone()), t);
+            }
+        }
+    }
+
+    // == query expression ==
+    fn check_query_expression(&mut self, query: &Located<ast::QueryExpression>) {
+        for goal in &query.value.items {
+            match &goal.value {
+                ast::QueryGoal::In { args, from } => {
+                    let columns = if let Some(table) = self.env.table_named(&from.value) {
+                        Some(table.columns.clone())
+                    } else {
+                        self.view_signature(&from.value)
+                    };
+                    let columns = match columns {
+                        Some(cs) if cs.len() != args.value.args.len() => {
+                            let n = cs.len();
+                            self.error(args, "C0031", &format!("{} has {} column(s), found {}", from.value, n, args.value.args.len()));
+                            None
+                        }
+                        cs => cs,
+                    };
+
+                    for (i, arg) in args.value.args.iter().enumerate() {
+                        let column = columns.as_ref().map(|cs| cs[i]);
+                        let unbound_variable = match &arg.value {
+                            ast::Expression::Variable { name } if self.lookup(name).is_none() => Some(name),
+                            _ => None,
+                        };
+                        match unbound_variable {
+                            Some(name) => {
+                                self.bind(name, column);
+                                self.record(arg, column.map(|c| vec![c]));
+                            }
+                            None => {
+                                if let (Some(found), Some(column)) = (self.check_value(arg), column) {
+                                    self.expect_type(arg, column, found);
+                                }
+                            }
+                        }
+                    }
+                }
+                ast::QueryGoal::Assign { args, expression } => {
+                    let found = self.check_expression(expression);
+                    self.check_assign_target(args, found, |s, name, t| s.bind(&name.value, t));
+                }
+                ast::QueryGoal::Filter { condition } => {
+                    let bool = self.env.type_named("Bool").unwrap();
+                    if let Some(found) = self.check_value(condition) { self.expect_type(condition, bool, found); }
+                }
+                ast::QueryGoal::Aggregate { args, op, value, body } => {
+                    let locals = value.value.variables();
+                    let mut found = None;
+                    let mut groups = vec![];
+                    self.scoped(|s| {
+                        s.check_query_expression(body);
+                        found = s.check_value(value);
+                        groups = s.scopes.last().unwrap().iter().filter(|(n, _)| !locals.contains(&n.as_str())).cloned().collect();
+                    });
+                    for (name, t) in groups { self.bind(&name, t) }
+                    let result = found.and_then(|t| self.aggregate_type(value, op.value, t));
+                    self.check_assign_target(args, result.map(|t| vec![t]), |s, name, t| s.bind(&name.value, t));
+                }
+                ast::QueryGoal::Not { body } => self.scoped(|s| s.check_query_expression(body)),
+                ast::QueryGoal::Disjunction { clauses } => {
+                    for (name, t) in self.check_clauses(clauses) { self.bind(&name, t) }
+                }
+            }
+        }
+    }
+
+    // what aggregating values of type t gives you
+    fn aggregate_type(&mut self, value: &Located<ast::Expression>, op: ast::Aggregate, t: TypeData) -> Option<TypeData> {
+        let integer = self.env.type_named("Integer").unwrap();
+        match op {
+            ast::Aggregate::Count => return Some(integer),
+            ast::Aggregate::Sum if t == integer => return Some(integer),
+            ast::Aggregate::Min | ast::Aggregate::Max if t.is_ordered() => return Some(t),
+            ast::Aggregate::Collect if t.is_ordered() => return Some(self.env.type_named("Set").unwrap()),
+            _ => {}
+        }
+        self.error(value, "C0032", &format!("can't {} values of type {}", op.name(), self.type_name(&t)));
+        None
+    }
+
+    // == expression ==
+    fn check_expression(&mut self, expr: &Located<ast::Expression>) -> Checked {
+        let types = match &expr.value {
+            ast::Expression::StringLiteral { .. } => Some(vec![self.env.type_named("String").unwrap()]),
+            ast::Expression::IntegerLiteral { it } => {
+                if *it > i64::MAX as u64 {
+                    self.error(expr, "C0033", &format!("integer literal too large: {}", it));
+                    None
+                } else {
+                    Some(vec![self.env.type_named("Integer").unwrap()])
+                }
+            }
+            ast::Expression::Variable { name } => {
+                self.lookup(name).flatten().map(|t| vec![t])
+            }
+            ast::Expression::Call { call } => self.check_call(call),
+            ast::Expression::UOp { op: ast::UOp::Not, arg } => {
+                let bool = self.env.type_named("Bool").unwrap();
+                let found = self.check_value(arg);
+                if let Some(found) = found { self.expect_type(arg, bool, found); }
+                Some(vec![bool])
+            }
+            ast::Expression::UOp { arg, .. } => {
+                let integer = self.env.type_named("Integer").unwrap();
+                let found = self.check_value(arg);
+                if let Some(found) = found { self.expect_type(arg, integer, found); }
+                Some(vec![integer])
+            }
+            ast::Expression::BinOp { arg1, op, arg2 } => {
+                let integer = self.env.type_named("Integer").unwrap();
+                let string = self.env.type_named("String").unwrap();
+                let bool = self.env.type_named("Bool").unwrap();
+                match (self.check_value(arg1), self.check_value(arg2)) {
+                    (Some(t1), Some(t2)) => {
+                        let result = match op {
+                            ast::BinOp::Add if t1 == t2 && (t1 == integer || t1 == string) => Some(t1),
+                            ast::BinOp::Subtract | ast::BinOp::Multiply | ast::BinOp::Divide 
+                                if t1 == integer && t2 == integer => Some(integer),
+                            // anything ordered can be compared with itself
+                            ast::BinOp::Equal | ast::BinOp::NotEqual |
+                            ast::BinOp::Less | ast::BinOp::LessEqual |
+                            ast::BinOp::Greater | ast::BinOp::GreaterEqual 
+                                if t1 == t2 && t1.is_ordered() => Some(bool),
+                            ast::BinOp::And | ast::BinOp::Or if t1 == bool && t2 == bool => Some(bool),
+                            _ => None,
+                        };
+                        if result.is_none() {
+                            let (n1, n2) = (self.type_name(&t1), self.type_name(&t2));
+                            self.error(expr, "C0034", &format!("can't apply {:?} to {} and {}", op, n1, n2));
+                        }
+                        result.map(|t| vec![t])
+                    }
+                    _ => None,
+                }
+            }
+        };
+        self.record(expr, types)
+    }
+
+    // a single expression can produce any number of values: otherwise each one produces one
+    fn check_values(&mut self, values: &Located<ast::AssignTarget>) -> Checked {
+        let args = &values.value.args;
+        if args.len() == 1 {
+            return self.check_expression(&args[0])
+        }
+        let types: Vec<Option<TypeData>> = args.iter().map(|a| self.check_value(a)).collect();
+        types.into_iter().collect()
+    }
+
+    // an expression that has to produce exactly one value
+    fn check_value(&mut self, expr: &Located<ast::Expression>) -> Option<TypeData> {
+        let types = self.check_expression(expr)?;
+        if types.len() != 1 {
+            let found = self.type_names(&types);
+            self.error(expr, "C0035", &format!("expected a single value, found {}", found));
+            return None
+        }
+        Some(types[0])
+    }
+
+    fn check_call(&mut self, call: &Located<ast::Call>) -> Checked {
+        let name = &call.value.name;
+        let args: Vec<Option<TypeData>> = call.value.args.iter().map(|a| self.check_value(a)).collect();
+
+        let (expected, returns) = if name.value == "print" {
+            if args.len() != 1 {
+                self.error(call, "C0036", "print takes exactly one argument");
+            }
+            return Some(vec![])
+        } else if let Some((_, f)) = self.env.function_named(&name.value) {
+            let returns = match f.kind {
+                HostFunctionKind::Ref { .. } => vec![],
+                HostFunctionKind::Mut { returns, .. } => vec![returns],
+            };
+            (vec![f.arg], returns)
+        } else {
+            // unknown, or a def with a broken signature: already reported
+            let s = self.typing.signature(&name.value)?;
+            (s.args.clone(), s.returns.clone())
+        };
+
+        if args.len() != expected.len() {
+            self.error(call, "C0037", &format!("{} takes {} argument(s), found {}", name.value, expected.len(), args.len()));
+        } else {
+            for ((arg, found), expected) in call.value.args.iter().zip(args).zip(expected) {
+                if let Some(found) = found { self.expect_type(arg, expected, found); }
+            }
+        }
+        Some(returns)
+    }
+}
+
+// a for might not run at all, so only an if with an else can return on both sides
+fn always_returns(block: &ast::Block) -> bool {
+    block.items.iter().any(|statement| match &statement.value {
+        ast::Statement::Return { .. } => true,
+        ast::Statement::If { body, else_: Some(else_), .. } => always_returns(&body.value) && always_returns(&else_.value),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{compile, run};
+
+    fn codes(source: &str) -> Vec<&'static str> {
+        compile(source).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn every_path_returns() {
+        let ok = "def f(@x Integer) Integer {\n    if @x == 1 { return 1 } else { return 2 }\n}\n";
+        assert_eq!(codes(ok), Vec::<&str>::new());
+        assert_eq!(codes("def f() Integer { print(1) }"), vec!["C0047"]);
+        assert_eq!(codes("def f() Integer { if 1 == 1 { return 1 } }"), vec!["C0047"]);
+        assert_eq!(codes("def f() Integer { for @x in vampire { return 1 } }"), vec!["C0047"]);
+        // nothing to return, so nothing to check
+        assert_eq!(codes("def f() { print(1) }"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn example1_runs() {
+        assert!(run(include_str!("../../samplecode/example1.kupo"), "lonely_vampire").is_ok());
+    }
+
+    #[test]
+    fn types() {
+        assert_eq!(codes("def f() { print(1 + \"a\") }"), vec!["C0034"]);
+        assert_eq!(codes("def f() Integer { return \"a\" }"), vec!["C0026"]);
+        assert_eq!(codes("def f(@x Integer) { print(@x) }\ndef g() { f(\"a\") }"), vec!["C0022"]);
+        assert_eq!(codes("def f() { for [@x, @y] in vampire { print(@x) } }"), vec!["C0031"]);
+        assert_eq!(codes("view [@x Integer] in v { @x in vampire }"), vec!["C0025"]);
+        assert_eq!(codes("def f(@x Thing) { print(@x) }"), vec!["C0021"]);
+    }
+}
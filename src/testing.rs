@@ -0,0 +1,30 @@
+use crate::codegen::{self, Program};
+use crate::demo;
+use crate::frontend::parse_module;
+use crate::runtime::{UntaggedValue, VM};
+use crate::semantics;
+
+// kupo source for tests, against the demo's tables. Err has the code of everything that went wrong
+pub fn compile(source: &str) -> Result<Program, Vec<&'static str>> {
+    let module = parse_module(source).map_err(|errors| errors.iter().map(|e| e.value.code).collect::<Vec<_>>())?;
+    let env = demo::environment();
+    let codes = |errors: semantics::Errors| errors.iter().map(|e| e.value.code.unwrap_or("")).collect::<Vec<_>>();
+    let typing = semantics::check_module(&module.value, &env).map_err(codes)?;
+    codegen::lower_module(&module.value, &env, &typing).map_err(codes)
+}
+
+// runs main if there is one, then gives every row of view, each one the way `kupo <file> <view>` prints it
+pub fn run(source: &str, view: &str) -> Result<Vec<String>, String> {
+    let program = compile(source).unwrap_or_else(|codes| panic!("didn't compile: {:?}", codes));
+    let main = program.procedure_named("main").map(|m| (m, UntaggedValue::instantiate(&program.procedures[m].args)));
+    let procedure = program.procedure_named(view).expect("no such view");
+    let vm = VM::new(program);
+    if let Some((main, args)) = main {
+        vm.call(main, args).map_err(|e| e.message)?;
+    }
+    let rows = vm.evaluate_view(procedure).map_err(|e| e.message)?;
+    Ok((0..rows.len()).map(|r| {
+        let row: Vec<_> = (0..rows.arity()).map(|c| rows.column_type(c).debug(rows.get(r, c))).collect();
+        format!("{:?}", row)
+    }).collect())
+}